@@ -24,7 +24,7 @@ fn bench_user_handle_response(c: &mut Criterion) {
                 let mut rng = thread_rng();
                 let user = UserState::new(public_params.clone());
                 let (req, state) = user.request(&mut rng).unwrap();
-                let resp = server.open_registration(req).unwrap();
+                let resp = server.open_registration(&mut rng, req).unwrap();
                 (user, state, resp)
             },
             |(mut user, state, resp)| {
@@ -42,7 +42,7 @@ fn bench_user_update_request(c: &mut Criterion) {
     let mut user = UserState::new(public_params);
 
     let (reg_req, reg_state) = user.request(&mut rng).unwrap();
-    let reg_resp = old_server.open_registration(reg_req).unwrap();
+    let reg_resp = old_server.open_registration(&mut rng, reg_req).unwrap();
     user.handle_response(reg_state, reg_resp).unwrap();
 
     let new_server = ServerState::new(&mut rng);
@@ -62,7 +62,7 @@ fn bench_user_handle_update_response(c: &mut Criterion) {
                 let mut user = UserState::new(old_server.public_parameters());
 
                 let (reg_req, reg_state) = user.request(&mut rng).unwrap();
-                let reg_resp = old_server.open_registration(reg_req).unwrap();
+                let reg_resp = old_server.open_registration(&mut rng, reg_req).unwrap();
                 user.handle_response(reg_state, reg_resp).unwrap();
 
                 let new_server = ServerState::new(&mut rng);
@@ -104,7 +104,7 @@ fn bench_user_submit_request(c: &mut Criterion) {
                 let mut rng = thread_rng();
                 let mut user = UserState::new(public_params.clone());
                 let (req, state) = user.request(&mut rng).unwrap();
-                let resp = server.open_registration(req).unwrap();
+                let resp = server.open_registration(&mut rng, req).unwrap();
                 user.handle_response(state, resp).unwrap();
                 (rng, user)
             },
@@ -139,7 +139,7 @@ fn bench_user_handle_submit_response(c: &mut Criterion) {
                 let mut rng = thread_rng();
                 let mut user = UserState::new(public_params.clone());
                 let (req, state) = user.request(&mut rng).unwrap();
-                let resp = server.open_registration(req).unwrap();
+                let resp = server.open_registration(&mut rng, req).unwrap();
                 user.handle_response(state, resp).unwrap();
 
                 let ((submit_req, submit_state), nym) = user
@@ -28,7 +28,7 @@ fn bench_registration(c: &mut Criterion) {
     // Create the request
     let (registration_req, _) = user.request(&mut rng).unwrap();
     c.bench_function("server.open_registration", |b| {
-        b.iter(|| server.open_registration(black_box(registration_req.clone())))
+        b.iter(|| server.open_registration(&mut rng, black_box(registration_req.clone())))
     });
 }
 
@@ -37,7 +37,9 @@ fn bench_submit(c: &mut Criterion) {
 
     c.bench_function("server.handle_submit", |b| {
         let (registration_req, reg_state) = user.request(&mut rng).unwrap();
-        let resp = server.open_registration(registration_req).unwrap();
+        let resp = server
+            .open_registration(&mut rng, registration_req)
+            .unwrap();
         user.handle_response(reg_state, resp)
             .expect("Should handle response properly");
         let today = ServerState::today();
@@ -78,7 +80,7 @@ fn bench_update(c: &mut Criterion) {
     let mut user = UserState::new(old_server.public_parameters());
 
     let (reg_request, reg_state) = user.request(&mut rng).unwrap();
-    let reg_response = old_server.open_registration(reg_request).unwrap();
+    let reg_response = old_server.open_registration(&mut rng, reg_request).unwrap();
     user.handle_response(reg_state, reg_response).unwrap();
 
     let new_server = ServerState::new(&mut rng);
@@ -1,6 +1,7 @@
 use std::time::Instant;
 
 use ooniauth_core::submit::submit_measurement_hash;
+use ooniauth_core::wire::{Encoding, Framing};
 use ooniauth_core::{scalar_u32, ServerState, UserState};
 use tracing_forest::util::LevelFilter;
 use tracing_forest::ForestLayer;
@@ -48,20 +49,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // Convert request to bytes for transmission
-    let request_bytes = reg_request.as_bytes();
+    let request_bytes = user.encode_request(&reg_request);
     println!("   Request size: {} bytes", request_bytes.len());
     println!("   Request payload (hex): {}", hex::encode(&request_bytes));
 
     // Server processes registration
     let now = Instant::now();
-    let reg_response = server.open_registration(reg_request)?;
+    let reg_response = server.open_registration(&mut rng, reg_request)?;
     println!(
         "   Server processed registration in {} ms",
         now.elapsed().as_millis()
     );
 
     // Convert response to bytes for transmission
-    let response_bytes = reg_response.as_bytes();
+    let response_bytes = server.encode_reply(&reg_response, Framing::Envelope(Encoding::Bincode));
     println!("   Response size: {} bytes", response_bytes.len());
 
     // User receives credential
@@ -122,12 +123,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("   NYM (hex): {}", hex::encode(nym));
 
     // Convert to bytes
-    let submit_request_bytes = submit_request.as_bytes();
+    let submit_request_bytes = user.encode_request(&submit_request);
     println!("   Request size: {} bytes", submit_request_bytes.len());
 
     // Server processes submission
     let now = Instant::now();
-    let submit_response = server.handle_submit(
+    let (submit_response, outcome) = server.handle_submit_with_outcome(
         &mut rng,
         submit_request,
         &nym,
@@ -141,9 +142,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "   Server validated submission and issued updated credential in {} ms",
         now.elapsed().as_millis()
     );
+    println!("   Verified probe ID: {}", hex::encode(outcome.probe_id));
+    println!("   Session ID: {}", hex::encode(outcome.session_id));
 
     // Convert response to bytes
-    let submit_response_bytes = submit_response.as_bytes();
+    let submit_response_bytes =
+        server.encode_reply(&submit_response, Framing::Envelope(Encoding::Bincode));
     println!("   Response size: {} bytes", submit_response_bytes.len());
 
     // User receives updated credential
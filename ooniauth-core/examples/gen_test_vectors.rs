@@ -0,0 +1,47 @@
+//! Regenerate the known-answer test vectors in `ooniauth-core/test-vectors/`.
+//!
+//! ```bash
+//! cargo run -p ooniauth-core --example gen_test_vectors
+//! ```
+//!
+//! The vectors only change when the protocol or its encoding does; commit the
+//! regenerated files together with such a change.
+
+use std::path::{Path, PathBuf};
+
+#[path = "../tests/vectors/mod.rs"]
+mod vectors;
+
+use vectors::{SEEDS, VECTOR_DAY};
+
+fn write<T: serde::Serialize>(dir: &Path, name: &str, vectors: &[T]) -> std::io::Result<()> {
+    let path = dir.join(name);
+    let mut json = serde_json::to_string_pretty(vectors)?;
+    json.push('\n');
+    std::fs::write(&path, json)?;
+    println!("Wrote {} vector(s) to {}", vectors.len(), path.display());
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-vectors");
+    std::fs::create_dir_all(&dir)?;
+
+    let registration: Vec<_> = SEEDS
+        .iter()
+        .map(|seed| vectors::registration(*seed, VECTOR_DAY))
+        .collect();
+    let submit: Vec<_> = SEEDS
+        .iter()
+        .map(|seed| vectors::submit(*seed, VECTOR_DAY))
+        .collect();
+    let update: Vec<_> = SEEDS
+        .iter()
+        .map(|seed| vectors::update(*seed, VECTOR_DAY))
+        .collect();
+
+    write(&dir, "registration.json", &registration)?;
+    write(&dir, "submit.json", &submit)?;
+    write(&dir, "update.json", &update)?;
+    Ok(())
+}
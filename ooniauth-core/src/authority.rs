@@ -0,0 +1,728 @@
+//! The server side as a single service: keys, submission policy and stores
+//!
+//! [`Authority`] takes the framed requests of the probes and returns either
+//! the response to send back along with what was verified, or a [`Rejection`]
+//! saying why the request was refused.
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use cmz::CMZError;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::errors::{CredentialError, ErrorCode, RangeError, WireError};
+use crate::registration::open_registration;
+use crate::submit::{
+    digest_point, submit_measurement_hash, MeasurementHash, SubmitOutcome, SubmitPolicy,
+    SubmitRequest,
+};
+use crate::update::update;
+use crate::wire::{DecodeMode, Envelope, KeyId};
+use crate::ServerState;
+
+/// The current keys of the authority and the retired ones
+///
+/// New credentials are issued under the current keys. Credentials issued
+/// under retired keys can be updated to the current ones, until the retired
+/// keys are removed. An update request is verified against each retired key
+/// in turn, so a request that no key verifies costs one verification per
+/// retired key: keep only the few retired keys whose credentials are still
+/// expected.
+pub struct KeyRing {
+    current: ServerState,
+    /// Most recently retired first
+    retired: Vec<ServerState>,
+}
+
+impl KeyRing {
+    pub fn new(current: ServerState) -> Self {
+        Self {
+            current,
+            retired: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> &ServerState {
+        &self.current
+    }
+
+    pub fn retired(&self) -> &[ServerState] {
+        &self.retired
+    }
+
+    /// Replaces the current keys with new ones, retiring them. Returns the key
+    /// ID of the new keys
+    pub fn rotate(&mut self, rng: &mut (impl RngCore + CryptoRng)) -> KeyId {
+        let retired = std::mem::replace(&mut self.current, ServerState::new(rng));
+        self.retired.insert(0, retired);
+        self.current.key_id()
+    }
+
+    /// Forgets retired keys, whose credentials can't be updated anymore
+    pub fn remove(&mut self, key_id: &KeyId) -> Option<ServerState> {
+        let index = self
+            .retired
+            .iter()
+            .position(|server| &server.key_id() == key_id)?;
+        Some(self.retired.remove(index))
+    }
+
+    /// The keys with this key ID, and whether they are retired
+    fn get(&self, key_id: &KeyId) -> Option<(&ServerState, bool)> {
+        if &self.current.key_id() == key_id {
+            return Some((&self.current, false));
+        }
+        self.retired
+            .iter()
+            .find(|server| &server.key_id() == key_id)
+            .map(|server| (server, true))
+    }
+}
+
+/// Remembers the accepted submissions, and the probes whose submissions are
+/// refused
+pub trait SubmissionStore {
+    /// Whether submissions from this probe ID are refused
+    fn is_blocked(&self, _probe_id: &[u8; 32]) -> bool {
+        false
+    }
+
+    /// Records a verified submission. If the probe already submitted this
+    /// measurement, returns the earlier submission and records nothing
+    fn record(
+        &mut self,
+        probe_id: &[u8; 32],
+        measurement_hash: &MeasurementHash,
+        submission: RecordedSubmission,
+    ) -> Option<RecordedSubmission>;
+}
+
+/// What a [`SubmissionStore`] keeps of an accepted submission, to answer the
+/// probe again when it resends the same request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedSubmission {
+    /// The SHA-256 digest of the framed request
+    pub request_digest: [u8; 32],
+    /// The response sent back to the probe
+    pub response: Vec<u8>,
+}
+
+/// A [`SubmissionStore`] in memory, which forgets everything on restart
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    submissions: HashMap<([u8; 32], MeasurementHash), RecordedSubmission>,
+    blocked: HashSet<[u8; 32]>,
+}
+
+impl MemoryStore {
+    pub fn block(&mut self, probe_id: [u8; 32]) {
+        self.blocked.insert(probe_id);
+    }
+}
+
+impl SubmissionStore for MemoryStore {
+    fn is_blocked(&self, probe_id: &[u8; 32]) -> bool {
+        self.blocked.contains(probe_id)
+    }
+
+    fn record(
+        &mut self,
+        probe_id: &[u8; 32],
+        measurement_hash: &MeasurementHash,
+        submission: RecordedSubmission,
+    ) -> Option<RecordedSubmission> {
+        match self.submissions.entry((*probe_id, *measurement_hash)) {
+            Entry::Occupied(earlier) => Some(earlier.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(submission);
+                None
+            }
+        }
+    }
+}
+
+/// Why the [`Authority`] refused a request
+#[derive(Error, Debug)]
+pub enum Rejection {
+    #[error("message was produced for key {}, which is not held", hex::encode(.0))]
+    UnknownKey(KeyId),
+    #[error("key {} is retired, the credential must be updated first", hex::encode(.0))]
+    RetiredKey(KeyId),
+    #[error(transparent)]
+    Malformed(#[from] WireError),
+    #[error(transparent)]
+    Credential(#[from] CredentialError),
+    #[error("proof rejected: {0:?}")]
+    Proof(CMZError),
+    #[error("probe {} is blocked", hex::encode(.0))]
+    Blocked([u8; 32]),
+    #[error("probe {} already submitted this measurement", hex::encode(.0))]
+    Replay([u8; 32]),
+    #[error("the credential was not issued under any of the retired keys")]
+    NoIssuingKey,
+    #[error("invalid submit policy: {0}")]
+    Policy(#[from] RangeError),
+}
+
+impl From<CMZError> for Rejection {
+    fn from(err: CMZError) -> Self {
+        Self::Proof(err)
+    }
+}
+
+impl Rejection {
+    /// The stable [`ErrorCode`] to report to the client. Blocked probes and
+    /// replays have no code of their own and are reported as
+    /// [`ErrorCode::ProofRejected`]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Rejection::UnknownKey(_) | Rejection::RetiredKey(_) | Rejection::NoIssuingKey => {
+                ErrorCode::UnknownPublicParameters
+            }
+            Rejection::Malformed(e) => e.code(),
+            Rejection::Credential(e) => e.code(),
+            Rejection::Proof(e) => e.into(),
+            Rejection::Blocked(_) | Rejection::Replay(_) => ErrorCode::ProofRejected,
+            Rejection::Policy(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+/// A credential issued by [`Authority::register`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registered {
+    /// The response to send back to the probe
+    pub response: Vec<u8>,
+    /// The keys the credential was issued under
+    pub key_id: KeyId,
+    /// The registration day the credential carries
+    pub age: u32,
+}
+
+/// A submission accepted by [`Authority::submit`], with what its proof showed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submitted {
+    /// The response to send back to the probe
+    pub response: Vec<u8>,
+    pub outcome: SubmitOutcome,
+    /// Whether the probe resent a request already accepted, whose response
+    /// was lost. The response is the one sent the first time, and the
+    /// measurement is already stored
+    pub resent: bool,
+}
+
+/// A credential moved to the current keys by [`Authority::update`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Updated {
+    /// The response to send back to the probe
+    pub response: Vec<u8>,
+    /// The retired keys the credential was issued under
+    pub from_key_id: KeyId,
+    /// The current keys the new credential is issued under
+    pub key_id: KeyId,
+}
+
+/// The server side of the protocol: the [`KeyRing`], the [`SubmitPolicy`] and
+/// a [`SubmissionStore`]
+pub struct Authority<S = MemoryStore> {
+    keys: KeyRing,
+    policy: SubmitPolicy,
+    store: S,
+    accept_retired_keys: bool,
+    decode_mode: DecodeMode,
+}
+
+impl Authority {
+    /// An authority with the default policy and a [`MemoryStore`]
+    pub fn new(keys: KeyRing) -> Self {
+        Self {
+            keys,
+            policy: SubmitPolicy::default(),
+            store: MemoryStore::default(),
+            accept_retired_keys: false,
+            decode_mode: DecodeMode::Envelope,
+        }
+    }
+}
+
+impl<S: SubmissionStore> Authority<S> {
+    pub fn with_store<T: SubmissionStore>(self, store: T) -> Authority<T> {
+        Authority {
+            keys: self.keys,
+            policy: self.policy,
+            store,
+            accept_retired_keys: self.accept_retired_keys,
+            decode_mode: self.decode_mode,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SubmitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether submissions with credentials issued under retired keys are
+    /// accepted, `false` by default: they are rejected with
+    /// [`Rejection::RetiredKey`] so that the probe updates its credential
+    pub fn accept_retired_keys(mut self, accept: bool) -> Self {
+        self.accept_retired_keys = accept;
+        self
+    }
+
+    /// The framing of the requests, [`DecodeMode::Envelope`] by default.
+    /// Legacy requests carry no key ID and are handled with the current keys
+    pub fn with_decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = mode;
+        self
+    }
+
+    pub fn keys(&self) -> &KeyRing {
+        &self.keys
+    }
+
+    pub fn keys_mut(&mut self) -> &mut KeyRing {
+        &mut self.keys
+    }
+
+    pub fn policy(&self) -> &SubmitPolicy {
+        &self.policy
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Issues a credential under the current keys
+    pub fn register(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        request: &[u8],
+    ) -> Result<Registered, Rejection> {
+        let server = self.current_keys(request)?;
+        let (request, framing) =
+            server.decode_request_as::<open_registration::Request>(request, self.decode_mode)?;
+        let age = ServerState::today();
+        let reply = server.open_registration_at(rng, request, age)?;
+        Ok(Registered {
+            response: server.encode_reply(&reply, framing),
+            key_id: server.key_id(),
+            age,
+        })
+    }
+
+    /// Verifies a measurement submission, whose probe ID comes from the
+    /// request, against the policy of today
+    ///
+    /// A request identical to one already accepted is answered with the same
+    /// response, so that a probe which lost it can resend the request. Another
+    /// request for a measurement the probe already submitted is refused with
+    /// [`Rejection::Replay`].
+    pub fn submit(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        request: &[u8],
+        probe_cc: &str,
+        probe_asn: &str,
+        measurement: &[u8],
+    ) -> Result<Submitted, Rejection> {
+        let server = match self.keys_for(request)? {
+            (server, false) => server,
+            (server, true) if self.accept_retired_keys => server,
+            (server, true) => return Err(Rejection::RetiredKey(server.key_id())),
+        };
+        let request_digest = Sha256::digest(request).into();
+        let (request, framing) =
+            server.decode_request_as::<SubmitRequest>(request, self.decode_mode)?;
+        let probe_id = digest_point(request.nym_point);
+        if self.store.is_blocked(&probe_id) {
+            return Err(Rejection::Blocked(probe_id));
+        }
+
+        let measurement_hash = submit_measurement_hash(measurement);
+        let (age_range, measurement_count_range) = self.policy.ranges(ServerState::today())?;
+        let (reply, outcome) = server.handle_submit_with_outcome(
+            rng,
+            request,
+            &probe_id,
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            age_range,
+            measurement_count_range,
+        )?;
+        let recorded = RecordedSubmission {
+            request_digest,
+            response: server.encode_reply(&reply, framing),
+        };
+        match self
+            .store
+            .record(&probe_id, &measurement_hash, recorded.clone())
+        {
+            None => Ok(Submitted {
+                response: recorded.response,
+                outcome,
+                resent: false,
+            }),
+            Some(earlier) if earlier.request_digest == request_digest => Ok(Submitted {
+                response: earlier.response,
+                outcome,
+                resent: true,
+            }),
+            Some(_) => Err(Rejection::Replay(probe_id)),
+        }
+    }
+
+    /// Moves a credential issued under retired keys to the current ones
+    ///
+    /// The request is verified against each retired key, most recently
+    /// retired first, see [`KeyRing`].
+    pub fn update(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        request: &[u8],
+    ) -> Result<Updated, Rejection> {
+        let server = self.current_keys(request)?;
+        let (request, framing) =
+            server.decode_request_as::<update::Request>(request, self.decode_mode)?;
+        // The request doesn't say which keys issued the credential: only the
+        // right ones verify it
+        for retired in self.keys.retired() {
+            match server.handle_update(
+                rng,
+                request.clone(),
+                retired.secret_key_ref(),
+                retired.public_parameters_ref(),
+            ) {
+                Ok(reply) => {
+                    return Ok(Updated {
+                        response: server.encode_reply(&reply, framing),
+                        from_key_id: retired.key_id(),
+                        key_id: server.key_id(),
+                    })
+                }
+                Err(e) => debug!(
+                    key_id = %hex::encode(retired.key_id()),
+                    error = ?e,
+                    "Update not verified by retired key"
+                ),
+            }
+        }
+        Err(Rejection::NoIssuingKey)
+    }
+
+    /// The keys named by a framed request, the current ones for a legacy
+    /// request, and whether they are retired
+    fn keys_for(&self, request: &[u8]) -> Result<(&ServerState, bool), Rejection> {
+        if self.decode_mode == DecodeMode::Legacy {
+            return Ok((self.keys.current(), false));
+        }
+        let key_id = Envelope::try_from(request)?.key_id;
+        self.keys.get(&key_id).ok_or(Rejection::UnknownKey(key_id))
+    }
+
+    /// The current keys, if the request was made for them
+    fn current_keys(&self, request: &[u8]) -> Result<&ServerState, Rejection> {
+        match self.keys_for(request)? {
+            (server, false) => Ok(server),
+            (server, true) => Err(Rejection::RetiredKey(server.key_id())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::bindings;
+    use crate::client::{ProbeClient, RetryPolicy, Submission, Transport, TransportError};
+    use crate::wire::WireMessage;
+    use crate::UserState;
+
+    fn register(authority: &Authority, rng: &mut (impl RngCore + CryptoRng)) -> UserState {
+        let mut user = UserState::new(authority.keys().current().public_parameters());
+        let (request, state) = user.request(rng).unwrap();
+        let registered = authority
+            .register(rng, &user.encode_request(&request))
+            .unwrap();
+        assert_eq!(registered.key_id, user.key_id());
+        assert_eq!(registered.age, ServerState::today());
+        user.handle_response(state, user.decode_reply(&registered.response).unwrap())
+            .unwrap();
+        user
+    }
+
+    fn submit(
+        authority: &mut Authority,
+        user: &mut UserState,
+        rng: &mut (impl RngCore + CryptoRng),
+        measurement: &[u8],
+    ) -> Result<Submitted, Rejection> {
+        let (age_range, measurement_count_range) =
+            authority.policy().ranges(ServerState::today()).unwrap();
+        let ((request, state), nym) = user
+            .submit_request(
+                rng,
+                "VE".into(),
+                "AS1234".into(),
+                &submit_measurement_hash(measurement),
+                age_range,
+                measurement_count_range,
+            )
+            .unwrap();
+        let submitted = authority.submit(
+            rng,
+            &user.encode_request(&request),
+            "VE",
+            "AS1234",
+            measurement,
+        )?;
+        assert_eq!(submitted.outcome.probe_id, nym);
+        user.handle_submit_response(state, user.decode_reply(&submitted.response).unwrap())
+            .unwrap();
+        Ok(submitted)
+    }
+
+    #[test]
+    fn test_submit() {
+        let rng = &mut rand::thread_rng();
+        let mut authority = Authority::new(KeyRing::new(ServerState::new(rng)));
+        let mut user = register(&authority, rng);
+
+        let submitted = submit(&mut authority, &mut user, rng, b"measurement").unwrap();
+        let today = ServerState::today();
+        assert_eq!(
+            submitted.outcome.key_id,
+            authority.keys().current().key_id()
+        );
+        assert_eq!(submitted.outcome.age_range, today - 30..today + 1);
+        assert_eq!(submitted.outcome.measurement_count_range, 0..u32::MAX);
+        assert_eq!(
+            submitted.outcome.measurement_hash,
+            submit_measurement_hash(b"measurement")
+        );
+
+        assert!(!submitted.resent);
+
+        let rejection = submit(&mut authority, &mut user, rng, b"measurement").unwrap_err();
+        assert!(matches!(rejection, Rejection::Replay(id) if id == submitted.outcome.probe_id));
+        assert_eq!(rejection.code(), ErrorCode::ProofRejected);
+
+        authority.store_mut().block(submitted.outcome.probe_id);
+        let rejection = submit(&mut authority, &mut user, rng, b"other").unwrap_err();
+        assert!(matches!(rejection, Rejection::Blocked(_)));
+
+        let rejection = authority
+            .submit(rng, b"garbage", "VE", "AS1234", b"other")
+            .unwrap_err();
+        assert_eq!(rejection.code(), ErrorCode::MalformedMessage);
+
+        let other = register(&authority, rng);
+        let (age_range, measurement_count_range) =
+            authority.policy().ranges(ServerState::today()).unwrap();
+        let ((request, _), _) = other
+            .submit_request(
+                rng,
+                "VE".into(),
+                "AS1234".into(),
+                &submit_measurement_hash(b"other"),
+                age_range,
+                measurement_count_range,
+            )
+            .unwrap();
+        let rejection = authority
+            .submit(
+                rng,
+                &other.encode_request(&request),
+                "ve",
+                "AS1234",
+                b"other",
+            )
+            .unwrap_err();
+        assert_eq!(rejection.code(), ErrorCode::BadDomain);
+    }
+
+    /// Passes the requests to an [`Authority`], losing the response of the
+    /// first `drop_submit` submissions
+    struct DroppingTransport {
+        authority: Authority,
+        drop_submit: u32,
+    }
+
+    impl Transport for DroppingTransport {
+        fn public_parameters(&mut self) -> Result<Vec<u8>, TransportError> {
+            Ok(bindings::to_bytes(
+                self.authority.keys().current().public_parameters_ref(),
+            ))
+        }
+
+        fn register(&mut self, request: &[u8]) -> Result<Vec<u8>, TransportError> {
+            let registered = self
+                .authority
+                .register(&mut rand::thread_rng(), request)
+                .map_err(|e| TransportError::Rejected {
+                    code: e.code(),
+                    message: e.to_string(),
+                })?;
+            Ok(registered.response)
+        }
+
+        fn submit(&mut self, submission: &Submission<'_>) -> Result<Vec<u8>, TransportError> {
+            let submitted = self
+                .authority
+                .submit(
+                    &mut rand::thread_rng(),
+                    submission.request,
+                    submission.probe_cc,
+                    submission.probe_asn,
+                    submission.measurement,
+                )
+                .map_err(|e| TransportError::Rejected {
+                    code: e.code(),
+                    message: e.to_string(),
+                })?;
+            if self.drop_submit > 0 {
+                self.drop_submit -= 1;
+                return Err(TransportError::Unavailable(String::from("response lost")));
+            }
+            Ok(submitted.response)
+        }
+
+        fn update(&mut self, _request: &[u8]) -> Result<Vec<u8>, TransportError> {
+            unreachable!("the keys are not rotated")
+        }
+    }
+
+    #[test]
+    fn test_resent_submission() {
+        let rng = &mut rand::thread_rng();
+        let mut authority = Authority::new(KeyRing::new(ServerState::new(rng)));
+        let mut user = register(&authority, rng);
+        let (age_range, measurement_count_range) =
+            authority.policy().ranges(ServerState::today()).unwrap();
+        let ((request, state), _) = user
+            .submit_request(
+                rng,
+                "VE".into(),
+                "AS1234".into(),
+                &submit_measurement_hash(b"measurement"),
+                age_range,
+                measurement_count_range,
+            )
+            .unwrap();
+        let request = user.encode_request(&request);
+        let first = authority
+            .submit(rng, &request, "VE", "AS1234", b"measurement")
+            .unwrap();
+        let resent = authority
+            .submit(rng, &request, "VE", "AS1234", b"measurement")
+            .unwrap();
+        assert!(resent.resent);
+        assert_eq!(resent.response, first.response);
+        assert_eq!(resent.outcome.probe_id, first.outcome.probe_id);
+        user.handle_submit_response(state, user.decode_reply(&resent.response).unwrap())
+            .unwrap();
+
+        // The probe lost the response: the client resends the same request
+        let transport = DroppingTransport {
+            authority,
+            drop_submit: 1,
+        };
+        let retry = RetryPolicy {
+            attempts: 2,
+            backoff: Duration::ZERO,
+        };
+        let mut client = ProbeClient::connect(transport, retry).unwrap();
+        client.submit(rng, "VE", "AS1234", b"measurement").unwrap();
+        assert_eq!(
+            client
+                .user()
+                .credential_info()
+                .unwrap()
+                .unwrap()
+                .measurement_count,
+            1
+        );
+        assert_eq!(client.transport().drop_submit, 0);
+        // The new credential is valid for the next submission
+        client.submit(rng, "VE", "AS1234", b"other").unwrap();
+    }
+
+    #[test]
+    fn test_legacy_requests() {
+        let rng = &mut rand::thread_rng();
+        let authority = Authority::new(KeyRing::new(ServerState::new(rng)))
+            .with_decode_mode(DecodeMode::Legacy);
+        let user = UserState::new(authority.keys().current().public_parameters());
+        let (request, _) = user.request(rng).unwrap();
+        let registered = authority.register(rng, &request.to_payload()).unwrap();
+        assert!(!registered.response.starts_with(b"OOAU"));
+
+        let rejection = authority
+            .register(rng, &user.encode_request(&request))
+            .unwrap_err();
+        assert_eq!(rejection.code(), ErrorCode::MalformedMessage);
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let rng = &mut rand::thread_rng();
+        let mut authority = Authority::new(KeyRing::new(ServerState::new(rng)));
+        let mut user = register(&authority, rng);
+        let old_key_id = user.key_id();
+
+        let new_key_id = authority.keys_mut().rotate(rng);
+        let rejection = submit(&mut authority, &mut user, rng, b"first").unwrap_err();
+        assert!(matches!(rejection, Rejection::RetiredKey(id) if id == old_key_id));
+        assert_eq!(rejection.code(), ErrorCode::UnknownPublicParameters);
+
+        let mut authority = authority.accept_retired_keys(true);
+        let submitted = submit(&mut authority, &mut user, rng, b"first").unwrap();
+        assert_eq!(submitted.outcome.key_id, old_key_id);
+
+        // Registrations are only made under the current keys
+        let (request, _) = user.request(rng).unwrap();
+        let rejection = authority
+            .register(rng, &user.encode_request(&request))
+            .unwrap_err();
+        assert!(matches!(rejection, Rejection::RetiredKey(_)));
+
+        // A second rotation: the update has to find the right retired keys
+        authority.keys_mut().rotate(rng);
+        let current = authority.keys().current().key_id();
+        user.pp = authority.keys().current().public_parameters();
+        let (request, state) = user.update_request(rng).unwrap();
+        let request = user.encode_request(&request);
+        let updated = authority.update(rng, &request).unwrap();
+        assert_eq!(updated.from_key_id, old_key_id);
+        assert_eq!(updated.key_id, current);
+        assert_ne!(new_key_id, current);
+
+        // Once the issuing keys are removed, the credential can't be updated
+        authority.keys_mut().remove(&old_key_id).unwrap();
+        let rejection = authority.update(rng, &request).unwrap_err();
+        assert!(matches!(rejection, Rejection::NoIssuingKey));
+
+        user.handle_update_response(state, user.decode_reply(&updated.response).unwrap())
+            .unwrap();
+        let submitted = submit(&mut authority, &mut user, rng, b"second").unwrap();
+        assert_eq!(submitted.outcome.key_id, current);
+        assert_eq!(
+            user.credential_info().unwrap().unwrap().measurement_count,
+            2
+        );
+
+        let stranger = UserState::new(ServerState::new(rng).public_parameters());
+        let (request, _) = stranger.request(rng).unwrap();
+        let rejection = authority
+            .register(rng, &stranger.encode_request(&request))
+            .unwrap_err();
+        assert!(matches!(rejection, Rejection::UnknownKey(_)));
+    }
+}
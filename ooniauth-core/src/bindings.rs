@@ -0,0 +1,117 @@
+//! Building blocks shared by the language bindings
+//!
+//! Keys, credentials, client states and pending requests cross every binding
+//! (the `_bytes` methods of the Python bindings, the C API, UniFFI and
+//! WebAssembly) in the same `bincode` form, written by [`to_bytes`] and read by
+//! [`from_bytes`], so that a value exported by one is readable by the others.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::errors::WireError;
+
+/// Export a value to the form shared by the bindings
+pub fn to_bytes<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).unwrap_or_else(|e| panic!("Could not serialize value: {e}"))
+}
+
+/// Import a value exported by [`to_bytes`]. Fails with [`WireError::Export`]
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, WireError> {
+    bincode::deserialize(bytes).map_err(|e| WireError::Export {
+        type_name: std::any::type_name::<T>(),
+        reason: e.to_string(),
+    })
+}
+
+/// The response to a pending request was already handled
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{0} was already handled")]
+pub struct AlreadyHandled(pub &'static str);
+
+/// A request waiting for the server response, along with the client state
+/// needed to handle it. `nym` is `()` for the requests that don't send one
+///
+/// Every binding keeps its pending requests in this form, so that a request
+/// exported by one is readable by the others.
+#[derive(Serialize, Deserialize)]
+pub struct Pending<N, S> {
+    pub nym: N,
+    pub request: Vec<u8>,
+    state: Option<S>,
+}
+
+impl<N, S: Clone> Pending<N, S> {
+    pub fn new(nym: N, request: Vec<u8>, state: S) -> Self {
+        Self {
+            nym,
+            request,
+            state: Some(state),
+        }
+    }
+
+    /// Handle the response with the state, which is only consumed when `handle`
+    /// succeeds, so that a response that can't be handled leaves the request
+    /// pending. `what` names the request in the error
+    pub fn handle<E: From<AlreadyHandled>>(
+        &mut self,
+        what: &'static str,
+        handle: impl FnOnce(S) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let state = self.state.clone().ok_or(AlreadyHandled(what))?;
+        handle(state)?;
+        self.state = None;
+        Ok(())
+    }
+}
+
+impl<N: Serialize, S: Serialize> Pending<N, S> {
+    /// Export the request, which is only possible until it is handled
+    pub fn to_bytes(&self, what: &'static str) -> Result<Vec<u8>, AlreadyHandled> {
+        if self.state.is_none() {
+            return Err(AlreadyHandled(what));
+        }
+        Ok(to_bytes(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+
+    #[test]
+    fn test_from_bytes() {
+        assert_eq!(
+            from_bytes::<(u32, u8)>(&to_bytes(&(7u32, 1u8))).unwrap(),
+            (7, 1)
+        );
+        let err = from_bytes::<u32>(&[1]).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::MalformedMessage);
+        assert!(err.to_string().starts_with("could not decode u32"));
+    }
+
+    #[test]
+    fn test_pending() {
+        let mut pending = Pending::new((), vec![1, 2], 3u8);
+        let exported = pending.to_bytes("request").unwrap();
+
+        // A failed handling leaves the request pending
+        let result: Result<(), AlreadyHandled> = pending.handle("request", |state| {
+            assert_eq!(state, 3);
+            Err(AlreadyHandled("other"))
+        });
+        assert_eq!(result, Err(AlreadyHandled("other")));
+        assert_eq!(pending.to_bytes("request").unwrap(), exported);
+
+        pending
+            .handle::<AlreadyHandled>("request", |_| Ok(()))
+            .unwrap();
+        assert_eq!(
+            pending.handle::<AlreadyHandled>("request", |_| Ok(())),
+            Err(AlreadyHandled("request"))
+        );
+        assert_eq!(pending.to_bytes("request"), Err(AlreadyHandled("request")));
+
+        let restored: Pending<(), u8> = from_bytes(&exported).unwrap();
+        assert_eq!(restored.request, vec![1, 2]);
+    }
+}
@@ -0,0 +1,416 @@
+/* Canonical, language-neutral byte encoding of protocol messages and
+ * credentials, specified in `ooniauth-core/ENCODING.md`.
+ *
+ * Unlike the `bincode` payloads, which mirror whatever layout the Rust
+ * structs happen to have, every field here has a fixed position and width:
+ * scalars are 32-byte canonical little-endian encodings, group elements are
+ * 32-byte compressed Ristretto points, and the only variable-length fields
+ * (the proofs) carry a 4-byte big-endian length prefix. Decoding rejects
+ * non-canonical scalars, invalid points and trailing bytes, so every value
+ * has exactly one valid encoding.
+*/
+
+use super::{PublicParameters, Scalar, G};
+use crate::errors::WireError;
+use crate::registration::{open_registration, UserAuthCredential};
+use crate::submit::{submit, SubmitRequest};
+use crate::update::update;
+use cmz::{CMZCredential, CMZMac};
+use curve25519_dalek::ristretto::CompressedRistretto;
+
+pub const SCALAR_LEN: usize = 32;
+pub const POINT_LEN: usize = 32;
+
+/// A message or value with a canonical encoding.
+pub trait Canonical: Sized {
+    fn to_canonical(&self) -> Vec<u8>;
+    fn from_canonical(bytes: &[u8]) -> Result<Self, WireError>;
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn scalar(&mut self, s: &Scalar) -> &mut Self {
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+
+    fn point(&mut self, p: &G) -> &mut Self {
+        self.0.extend_from_slice(p.compress().as_bytes());
+        self
+    }
+
+    fn bytes(&mut self, b: &[u8]) -> &mut Self {
+        let len: u32 = b.len().try_into().expect("field does not fit in u32");
+        self.0.extend_from_slice(&len.to_be_bytes());
+        self.0.extend_from_slice(b);
+        self
+    }
+
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize, what: &str) -> Result<&'a [u8], WireError> {
+        if self.0.len() < n {
+            return Err(WireError::Payload(format!(
+                "truncated {what}: need {n} bytes, {} left",
+                self.0.len()
+            )));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn scalar(&mut self, what: &str) -> Result<Scalar, WireError> {
+        let bytes: [u8; SCALAR_LEN] = self.take(SCALAR_LEN, what)?.try_into().unwrap();
+        Option::from(Scalar::from_canonical_bytes(bytes))
+            .ok_or_else(|| WireError::Payload(format!("non-canonical scalar {what}")))
+    }
+
+    fn point(&mut self, what: &str) -> Result<G, WireError> {
+        CompressedRistretto::from_slice(self.take(POINT_LEN, what)?)
+            .ok()
+            .and_then(|c| c.decompress())
+            .ok_or_else(|| WireError::Payload(format!("invalid Ristretto point {what}")))
+    }
+
+    fn bytes(&mut self, what: &str) -> Result<Vec<u8>, WireError> {
+        let len = u32::from_be_bytes(self.take(4, what)?.try_into().unwrap()) as usize;
+        Ok(self.take(len, what)?.to_vec())
+    }
+
+    fn u8(&mut self, what: &str) -> Result<u8, WireError> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn finish(self) -> Result<(), WireError> {
+        if !self.0.is_empty() {
+            return Err(WireError::Payload(format!(
+                "{} trailing bytes after canonical encoding",
+                self.0.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Canonical for open_registration::Request {
+    fn to_canonical(&self) -> Vec<u8> {
+        Writer::default()
+            .point(&self.C_iss_cred_UAC)
+            .bytes(&self.cli_proof)
+            .finish()
+    }
+
+    fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut r = Reader(bytes);
+        let req = Self {
+            C_iss_cred_UAC: r.point("C_iss_cred_UAC")?,
+            cli_proof: r.bytes("cli_proof")?,
+        };
+        r.finish()?;
+        Ok(req)
+    }
+}
+
+impl Canonical for open_registration::Reply {
+    fn to_canonical(&self) -> Vec<u8> {
+        Writer::default()
+            .scalar(&self.iss_Sattr_UAC_age)
+            .scalar(&self.iss_Jattr_UAC_nym_id)
+            .point(&self.P_iss_cred_UAC)
+            .point(&self.R_iss_cred_UAC)
+            .bytes(&self.iss_proof)
+            .finish()
+    }
+
+    fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut r = Reader(bytes);
+        let rep = Self {
+            iss_Sattr_UAC_age: r.scalar("age")?,
+            iss_Jattr_UAC_nym_id: r.scalar("nym_id")?,
+            P_iss_cred_UAC: r.point("P_iss_cred_UAC")?,
+            R_iss_cred_UAC: r.point("R_iss_cred_UAC")?,
+            iss_proof: r.bytes("iss_proof")?,
+        };
+        r.finish()?;
+        Ok(rep)
+    }
+}
+
+// The submit and update requests share the same layout: both show the old
+// credential with all attributes hidden and ask for a new one to be issued.
+macro_rules! impl_canonical_show_request {
+    ($ty:ty) => {
+        impl Canonical for $ty {
+            fn to_canonical(&self) -> Vec<u8> {
+                Writer::default()
+                    .point(&self.C_iss_cred_New)
+                    .point(&self.P_show_cred_Old)
+                    .point(&self.CQ_show_cred_Old)
+                    .point(&self.C_show_Hattr_Old_age)
+                    .point(&self.C_show_Hattr_Old_measurement_count)
+                    .point(&self.C_show_Hattr_Old_nym_id)
+                    .bytes(&self.cli_proof)
+                    .finish()
+            }
+
+            fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+                let mut r = Reader(bytes);
+                let req = Self {
+                    C_iss_cred_New: r.point("C_iss_cred_New")?,
+                    P_show_cred_Old: r.point("P_show_cred_Old")?,
+                    CQ_show_cred_Old: r.point("CQ_show_cred_Old")?,
+                    C_show_Hattr_Old_age: r.point("C_show_Hattr_Old_age")?,
+                    C_show_Hattr_Old_measurement_count: r
+                        .point("C_show_Hattr_Old_measurement_count")?,
+                    C_show_Hattr_Old_nym_id: r.point("C_show_Hattr_Old_nym_id")?,
+                    cli_proof: r.bytes("cli_proof")?,
+                };
+                r.finish()?;
+                Ok(req)
+            }
+        }
+    };
+}
+
+impl_canonical_show_request!(submit::Request);
+impl_canonical_show_request!(update::Request);
+
+// The nym point comes first so that the core request, which ends with a
+// variable-length proof, can be decoded from the remaining bytes.
+impl Canonical for SubmitRequest {
+    fn to_canonical(&self) -> Vec<u8> {
+        let mut out = Writer::default().point(&self.nym_point).finish();
+        out.extend(self.core_request.to_canonical());
+        out
+    }
+
+    fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut r = Reader(bytes);
+        let nym_point = r.point("nym_point")?;
+        Ok(Self {
+            core_request: submit::Request::from_canonical(r.0)?,
+            nym_point,
+        })
+    }
+}
+
+// Submit and update replies only carry the MAC of the new credential and
+// the issuer's proof.
+macro_rules! impl_canonical_issue_reply {
+    ($ty:ty) => {
+        impl Canonical for $ty {
+            fn to_canonical(&self) -> Vec<u8> {
+                Writer::default()
+                    .point(&self.P_iss_cred_New)
+                    .point(&self.R_iss_cred_New)
+                    .bytes(&self.iss_proof)
+                    .finish()
+            }
+
+            fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+                let mut r = Reader(bytes);
+                let rep = Self {
+                    P_iss_cred_New: r.point("P_iss_cred_New")?,
+                    R_iss_cred_New: r.point("R_iss_cred_New")?,
+                    iss_proof: r.bytes("iss_proof")?,
+                };
+                r.finish()?;
+                Ok(rep)
+            }
+        }
+    };
+}
+
+impl_canonical_issue_reply!(submit::Reply);
+impl_canonical_issue_reply!(update::Reply);
+
+impl Canonical for PublicParameters {
+    fn to_canonical(&self) -> Vec<u8> {
+        let count: u8 = self
+            .X
+            .len()
+            .try_into()
+            .expect("too many credential attributes");
+        let mut w = Writer::default();
+        w.point(&self.X0.expect("public parameters without X0"))
+            .point(&self.Xr.expect("public parameters without Xr"))
+            .u8(count);
+        for X in &self.X {
+            w.point(X);
+        }
+        w.finish()
+    }
+
+    fn from_canonical(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut r = Reader(bytes);
+        let X0 = r.point("X0")?;
+        let Xr = r.point("Xr")?;
+        let count = r.u8("attribute count")?;
+        let X = (0..count)
+            .map(|_| r.point("X"))
+            .collect::<Result<Vec<_>, _>>()?;
+        r.finish()?;
+        Ok(Self {
+            X0: Some(X0),
+            Xr: Some(Xr),
+            X,
+        })
+    }
+}
+
+/// Encode a client credential: its three attributes followed by its MAC.
+///
+/// The issuer public key is not part of the encoding; it is supplied again
+/// when decoding with [`decode_credential`].
+///
+/// # Panics
+///
+/// If the credential is missing an attribute, which never happens for a
+/// credential obtained from the protocols.
+pub fn encode_credential(cred: &UserAuthCredential) -> Vec<u8> {
+    Writer::default()
+        .scalar(&cred.nym_id.expect("credential without nym_id"))
+        .scalar(&cred.age.expect("credential without age"))
+        .scalar(
+            &cred
+                .measurement_count
+                .expect("credential without measurement_count"),
+        )
+        .point(&cred.MAC.P)
+        .point(&cred.MAC.Q)
+        .finish()
+}
+
+/// Decode a client credential issued under the public parameters `pp`.
+pub fn decode_credential(
+    bytes: &[u8],
+    pp: &PublicParameters,
+) -> Result<UserAuthCredential, WireError> {
+    let mut r = Reader(bytes);
+    let mut cred = UserAuthCredential::using_pubkey(pp);
+    cred.nym_id = Some(r.scalar("nym_id")?);
+    cred.age = Some(r.scalar("age")?);
+    cred.measurement_count = Some(r.scalar("measurement_count")?);
+    cred.MAC = CMZMac {
+        P: r.point("MAC.P")?,
+        Q: r.point("MAC.Q")?,
+    };
+    r.finish()?;
+    Ok(cred)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submit::submit_measurement_hash;
+    use crate::{ServerState, UserState};
+
+    fn roundtrip<T: Canonical>(value: &T) -> T {
+        let bytes = value.to_canonical();
+        let decoded = T::from_canonical(&bytes).unwrap();
+        assert_eq!(decoded.to_canonical(), bytes);
+        decoded
+    }
+
+    #[test]
+    fn test_canonical_roundtrip() {
+        let rng = &mut rand::thread_rng();
+        let server = ServerState::new(rng);
+        let pp = roundtrip(server.public_parameters_ref());
+        assert_eq!(&pp, server.public_parameters_ref());
+
+        let mut user = UserState::new(pp);
+        let (req, state) = user.request(rng).unwrap();
+        let req = roundtrip(&req);
+        assert_eq!(
+            req.to_canonical().len(),
+            POINT_LEN + 4 + req.cli_proof.len()
+        );
+        let rep = roundtrip(&server.open_registration(rng, req).unwrap());
+        user.handle_response(state, rep).unwrap();
+
+        let cred = user.get_credential().unwrap();
+        let cred_bytes = encode_credential(cred);
+        assert_eq!(cred_bytes.len(), 3 * SCALAR_LEN + 2 * POINT_LEN);
+        let decoded = decode_credential(&cred_bytes, &user.pp).unwrap();
+        assert_eq!(decoded.MAC, cred.MAC);
+        user.set_credential(decoded);
+
+        let today = ServerState::today();
+        let measurement_hash = submit_measurement_hash(b"measurement");
+        let ((req, state), probe_id) = user
+            .submit_request(
+                rng,
+                "IT".into(),
+                "AS30722".into(),
+                &measurement_hash,
+                (today - 30)..(today + 1),
+                0..100,
+            )
+            .unwrap();
+        let req = roundtrip(&req);
+        let rep = server
+            .handle_submit(
+                rng,
+                req,
+                &probe_id,
+                "IT",
+                "AS30722",
+                &measurement_hash,
+                (today - 30)..(today + 1),
+                0..100,
+            )
+            .unwrap();
+        user.handle_submit_response(state, roundtrip(&rep)).unwrap();
+
+        let new_server = ServerState::new(rng);
+        user.pp = new_server.public_parameters();
+        let (req, state) = user.update_request(rng).unwrap();
+        let rep = new_server
+            .handle_update(
+                rng,
+                roundtrip(&req),
+                server.secret_key_ref(),
+                server.public_parameters_ref(),
+            )
+            .unwrap();
+        user.handle_update_response(state, roundtrip(&rep)).unwrap();
+    }
+
+    #[test]
+    fn test_canonical_rejects_malformed() {
+        let rng = &mut rand::thread_rng();
+        let server = ServerState::new(rng);
+        let user = UserState::new(server.public_parameters());
+        let (req, _state) = user.request(rng).unwrap();
+        let bytes = req.to_canonical();
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(open_registration::Request::from_canonical(&trailing).is_err());
+        assert!(open_registration::Request::from_canonical(&bytes[..bytes.len() - 1]).is_err());
+
+        // 0xff..ff is neither a canonical scalar nor a valid point encoding
+        let mut bad_point = bytes.clone();
+        bad_point[..POINT_LEN].copy_from_slice(&[0xff; POINT_LEN]);
+        assert!(open_registration::Request::from_canonical(&bad_point).is_err());
+
+        let mut cred_bytes = vec![0xff; SCALAR_LEN];
+        cred_bytes.extend_from_slice(&[0u8; 2 * SCALAR_LEN + 2 * POINT_LEN]);
+        assert!(decode_credential(&cred_bytes, &user.pp).is_err());
+    }
+}
@@ -0,0 +1,668 @@
+//! A probe-side client driving the protocol over a [`Transport`]
+//!
+//! [`ProbeClient`] registers when it holds no credential, submits
+//! measurements, retries requests that did not reach the server and moves its
+//! credential to the new public parameters when the server rotates its keys.
+//! [`LocalTransport`] runs the server in process, for tests.
+use std::time::Duration;
+
+use cmz::CMZError;
+use rand::{CryptoRng, RngCore};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::errors::{CredentialError, ErrorCode, RangeError, WireError};
+use crate::registration::open_registration;
+use crate::submit::{
+    submit, submit_measurement_hash, validate_probe_domain, SubmitPolicy, SubmitRequest,
+};
+use crate::update::update;
+use crate::{bindings, wire, PublicParameters, ServerState, UserState};
+
+/// Why a [`Transport`] call did not return a response
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+    /// The request did not reach the server, or its response was lost. The
+    /// client retries these, see [`RetryPolicy`]
+    #[error("server unavailable: {0}")]
+    Unavailable(String),
+    /// The server refused the request
+    #[error("server rejected the request ({code}): {message}")]
+    Rejected { code: ErrorCode, message: String },
+}
+
+impl TransportError {
+    fn rejected(code: ErrorCode, message: String) -> Self {
+        Self::Rejected { code, message }
+    }
+}
+
+impl From<CredentialError> for TransportError {
+    fn from(err: CredentialError) -> Self {
+        Self::rejected(err.code(), err.to_string())
+    }
+}
+
+impl From<RangeError> for TransportError {
+    fn from(err: RangeError) -> Self {
+        Self::rejected(ErrorCode::Internal, err.to_string())
+    }
+}
+
+impl From<WireError> for TransportError {
+    fn from(err: WireError) -> Self {
+        Self::rejected(err.code(), err.to_string())
+    }
+}
+
+impl From<CMZError> for TransportError {
+    fn from(err: CMZError) -> Self {
+        Self::rejected(ErrorCode::from(&err), format!("{err:?}"))
+    }
+}
+
+/// A measurement submission, as sent to the server
+#[derive(Debug, Clone, Copy)]
+pub struct Submission<'a> {
+    /// The probe ID the measurement is submitted under
+    pub nym: &'a [u8; 32],
+    /// The framed submit request
+    pub request: &'a [u8],
+    pub probe_cc: &'a str,
+    pub probe_asn: &'a str,
+    /// The measurement itself, which the server hashes to check the proof
+    pub measurement: &'a [u8],
+}
+
+/// Carries the messages of a [`ProbeClient`] to the server and back
+///
+/// Requests and responses are the framed messages of [`crate::wire`]. When
+/// the server refuses a request, the implementation returns the code it
+/// reported in [`TransportError::Rejected`]:
+/// [`ErrorCode::UnknownPublicParameters`] makes the client fetch the new
+/// public parameters and update its credential.
+pub trait Transport {
+    /// The public parameters the server currently issues credentials under, as
+    /// exported by [`bindings::to_bytes`]
+    fn public_parameters(&mut self) -> Result<Vec<u8>, TransportError>;
+
+    fn register(&mut self, request: &[u8]) -> Result<Vec<u8>, TransportError>;
+
+    fn submit(&mut self, submission: &Submission<'_>) -> Result<Vec<u8>, TransportError>;
+
+    fn update(&mut self, request: &[u8]) -> Result<Vec<u8>, TransportError>;
+
+    /// Waits before sending a request again, see [`RetryPolicy`]. Blocks the
+    /// thread by default: implementations driven by an event loop can wait on
+    /// it instead, and tests can skip the wait
+    fn wait(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Errors of the [`ProbeClient`] operations
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error(transparent)]
+    Credential(#[from] CredentialError),
+    #[error(transparent)]
+    Wire(#[from] WireError),
+    #[error("server rejected the request ({code}): {message}")]
+    Rejected { code: ErrorCode, message: String },
+    #[error("server unavailable after {attempts} attempts: {message}")]
+    Unavailable { attempts: u32, message: String },
+    #[error("invalid submit policy: {0}")]
+    Policy(#[from] RangeError),
+}
+
+impl From<CMZError> for ClientError {
+    fn from(err: CMZError) -> Self {
+        Self::Credential(CredentialError::CMZError(err))
+    }
+}
+
+impl ClientError {
+    /// The stable [`ErrorCode`] for this error. An unavailable server is
+    /// reported as [`ErrorCode::Internal`]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ClientError::Credential(e) => e.code(),
+            ClientError::Wire(e) => e.code(),
+            ClientError::Rejected { code, .. } => *code,
+            ClientError::Unavailable { .. } | ClientError::Policy(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+/// How many times a request is sent when the server is unavailable, waiting
+/// `backoff` after the first failure and doubling the wait after each one.
+/// The wait is left to [`Transport::wait`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// 3 attempts, 1 then 2 seconds apart
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// See [`ProbeClient::on_state_change`]
+type StateHook = Box<dyn FnMut(&UserState) + Send>;
+
+/// Drives registration, submission and credential updates for a probe
+///
+/// Submitted measurements are retried as-is when the server is unavailable, so
+/// the server may receive the same submission twice: it has to answer it with
+/// the response it sent the first time, as
+/// [`Authority::submit`](crate::authority::Authority::submit) does.
+pub struct ProbeClient<T> {
+    transport: T,
+    user: UserState,
+    policy: SubmitPolicy,
+    retry: RetryPolicy,
+    on_state_change: Option<StateHook>,
+}
+
+impl<T: Transport> ProbeClient<T> {
+    /// A client restored from a saved state, or created with the public
+    /// parameters of the server
+    pub fn new(transport: T, user: UserState) -> Self {
+        Self {
+            transport,
+            user,
+            policy: SubmitPolicy::default(),
+            retry: RetryPolicy::default(),
+            on_state_change: None,
+        }
+    }
+
+    /// A client without a credential, for the current public parameters of the
+    /// server, fetched and then sent with `retry`
+    pub fn connect(mut transport: T, retry: RetryPolicy) -> Result<Self, ClientError> {
+        let pp = fetch_public_parameters(&mut transport, &retry)?;
+        Ok(Self::new(transport, UserState::new(pp)).with_retry_policy(retry))
+    }
+
+    /// The submission policy of the server, [`SubmitPolicy::default`] if not set
+    pub fn with_policy(mut self, policy: SubmitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Calls `hook` with the new state after every change of the public
+    /// parameters or of the credential, to save it across restarts
+    pub fn on_state_change(mut self, hook: impl FnMut(&UserState) + Send + 'static) -> Self {
+        self.on_state_change = Some(Box::new(hook));
+        self
+    }
+
+    pub fn user(&self) -> &UserState {
+        &self.user
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Gets a new credential, replacing the current one if any
+    pub fn register(&mut self, rng: &mut (impl RngCore + CryptoRng)) -> Result<(), ClientError> {
+        let result = self.try_register(rng);
+        if is_key_rotation(&result) && self.refresh_public_parameters()? {
+            return self.try_register(rng);
+        }
+        result
+    }
+
+    /// Submits a measurement, registering first without a credential, and
+    /// returns the probe ID it was submitted under
+    ///
+    /// When the server reports new public parameters, the credential is moved
+    /// to them and the measurement submitted again.
+    pub fn submit(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        probe_cc: &str,
+        probe_asn: &str,
+        measurement: &[u8],
+    ) -> Result<[u8; 32], ClientError> {
+        validate_probe_domain(probe_cc, probe_asn)?;
+        match self.user.credential_info()? {
+            None => self.register(rng)?,
+            // A previous update did not complete
+            Some(info) if info.key_id != self.user.key_id() => self.update_credential(rng)?,
+            Some(_) => {}
+        }
+
+        let result = self.try_submit(rng, probe_cc, probe_asn, measurement);
+        if is_key_rotation(&result) && self.refresh_public_parameters()? {
+            self.update_credential(rng)?;
+            return self.try_submit(rng, probe_cc, probe_asn, measurement);
+        }
+        result
+    }
+
+    /// Fetches the public parameters of the server and, if they changed, moves
+    /// the credential to them. Returns whether they changed
+    pub fn refresh(&mut self, rng: &mut (impl RngCore + CryptoRng)) -> Result<bool, ClientError> {
+        let changed = self.refresh_public_parameters()?;
+        if changed && self.user.get_credential().is_some() {
+            self.update_credential(rng)?;
+        }
+        Ok(changed)
+    }
+
+    fn try_register(&mut self, rng: &mut (impl RngCore + CryptoRng)) -> Result<(), ClientError> {
+        let (request, state) = self.user.request(rng)?;
+        let request = self.user.encode_request(&request);
+        let response = self.send(|transport| transport.register(&request))?;
+        let reply = self
+            .user
+            .decode_reply::<open_registration::Reply>(&response)?;
+        self.user.handle_response(state, reply)?;
+        self.state_changed();
+        Ok(())
+    }
+
+    fn try_submit(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        probe_cc: &str,
+        probe_asn: &str,
+        measurement: &[u8],
+    ) -> Result<[u8; 32], ClientError> {
+        let (age_range, measurement_count_range) = self.policy.ranges(ServerState::today())?;
+        let ((request, state), nym) = self.user.submit_request(
+            rng,
+            probe_cc.into(),
+            probe_asn.into(),
+            &submit_measurement_hash(measurement),
+            age_range,
+            measurement_count_range,
+        )?;
+        let request = self.user.encode_request(&request);
+        let submission = Submission {
+            nym: &nym,
+            request: &request,
+            probe_cc,
+            probe_asn,
+            measurement,
+        };
+        let response = self.send(|transport| transport.submit(&submission))?;
+        let reply = self.user.decode_reply::<submit::Reply>(&response)?;
+        self.user.handle_submit_response(state, reply)?;
+        self.state_changed();
+        Ok(nym)
+    }
+
+    fn update_credential(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(), ClientError> {
+        let (request, state) = self.user.update_request(rng)?;
+        let request = self.user.encode_request(&request);
+        let response = self.send(|transport| transport.update(&request))?;
+        let reply = self.user.decode_reply::<update::Reply>(&response)?;
+        self.user.handle_update_response(state, reply)?;
+        debug!("Credential updated to the new public parameters");
+        self.state_changed();
+        Ok(())
+    }
+
+    /// Switches to the current public parameters of the server. Returns
+    /// whether they changed
+    fn refresh_public_parameters(&mut self) -> Result<bool, ClientError> {
+        let pp = fetch_public_parameters(&mut self.transport, &self.retry)?;
+        if wire::key_id(&pp) == self.user.key_id() {
+            return Ok(false);
+        }
+        debug!(key_id = %hex::encode(wire::key_id(&pp)), "Server rotated its keys");
+        self.user.pp = pp;
+        self.state_changed();
+        Ok(true)
+    }
+
+    fn send(
+        &mut self,
+        call: impl FnMut(&mut T) -> Result<Vec<u8>, TransportError>,
+    ) -> Result<Vec<u8>, ClientError> {
+        send(&mut self.transport, &self.retry, call)
+    }
+
+    fn state_changed(&mut self) {
+        if let Some(hook) = &mut self.on_state_change {
+            hook(&self.user);
+        }
+    }
+}
+
+/// Sends a request, retrying while the server is unavailable
+fn send<T: Transport>(
+    transport: &mut T,
+    retry: &RetryPolicy,
+    mut call: impl FnMut(&mut T) -> Result<Vec<u8>, TransportError>,
+) -> Result<Vec<u8>, ClientError> {
+    let mut backoff = retry.backoff;
+    let mut attempt = 1;
+    loop {
+        match call(transport) {
+            Ok(response) => return Ok(response),
+            Err(TransportError::Rejected { code, message }) => {
+                return Err(ClientError::Rejected { code, message })
+            }
+            Err(TransportError::Unavailable(message)) if attempt >= retry.attempts => {
+                return Err(ClientError::Unavailable {
+                    attempts: attempt,
+                    message,
+                })
+            }
+            Err(TransportError::Unavailable(message)) => {
+                debug!(attempt, %message, "Server unavailable, retrying");
+                transport.wait(backoff);
+                backoff = backoff.saturating_mul(2);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn fetch_public_parameters<T: Transport>(
+    transport: &mut T,
+    retry: &RetryPolicy,
+) -> Result<PublicParameters, ClientError> {
+    let bytes = send(transport, retry, |transport| transport.public_parameters())?;
+    Ok(bindings::from_bytes(&bytes)?)
+}
+
+/// Whether a request failed because the server holds other public parameters
+fn is_key_rotation<R>(result: &Result<R, ClientError>) -> bool {
+    matches!(result, Err(err) if err.code() == ErrorCode::UnknownPublicParameters)
+}
+
+/// A [`Transport`] handling the requests with a [`ServerState`] in process
+///
+/// The server applies its [`SubmitPolicy`] with the system clock, and updates
+/// credentials issued under the keys it had before the last [`rotate`]. Retries
+/// don't wait, the waits asked for are kept in [`waits`].
+///
+/// [`rotate`]: LocalTransport::rotate
+/// [`waits`]: LocalTransport::waits
+pub struct LocalTransport {
+    server: ServerState,
+    previous: Option<ServerState>,
+    policy: SubmitPolicy,
+    failures: u32,
+    waits: Vec<Duration>,
+}
+
+impl LocalTransport {
+    pub fn new(server: ServerState) -> Self {
+        Self {
+            server,
+            previous: None,
+            policy: SubmitPolicy::default(),
+            failures: 0,
+            waits: Vec::new(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SubmitPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn server(&self) -> &ServerState {
+        &self.server
+    }
+
+    /// Replaces the keys of the server, keeping the current ones for updates
+    pub fn rotate(&mut self, rng: &mut (impl RngCore + CryptoRng)) {
+        let previous = std::mem::replace(&mut self.server, ServerState::new(rng));
+        self.previous = Some(previous);
+    }
+
+    /// Makes the next `calls` calls fail with [`TransportError::Unavailable`]
+    pub fn fail_next(&mut self, calls: u32) {
+        self.failures = calls;
+    }
+
+    /// The waits between retries so far
+    pub fn waits(&self) -> &[Duration] {
+        &self.waits
+    }
+
+    fn check_available(&mut self) -> Result<(), TransportError> {
+        if self.failures == 0 {
+            return Ok(());
+        }
+        self.failures -= 1;
+        Err(TransportError::Unavailable(String::from(
+            "simulated failure",
+        )))
+    }
+}
+
+impl Transport for LocalTransport {
+    fn public_parameters(&mut self) -> Result<Vec<u8>, TransportError> {
+        self.check_available()?;
+        Ok(bindings::to_bytes(self.server.public_parameters_ref()))
+    }
+
+    fn register(&mut self, request: &[u8]) -> Result<Vec<u8>, TransportError> {
+        self.check_available()?;
+        let (request, framing) = self
+            .server
+            .decode_request::<open_registration::Request>(request)?;
+        let reply = self
+            .server
+            .open_registration(&mut rand::thread_rng(), request)?;
+        Ok(self.server.encode_reply(&reply, framing))
+    }
+
+    fn submit(&mut self, submission: &Submission<'_>) -> Result<Vec<u8>, TransportError> {
+        self.check_available()?;
+        let (request, framing) = self
+            .server
+            .decode_request::<SubmitRequest>(submission.request)?;
+        let (age_range, measurement_count_range) = self.policy.ranges(ServerState::today())?;
+        let reply = self.server.handle_submit(
+            &mut rand::thread_rng(),
+            request,
+            submission.nym,
+            submission.probe_cc,
+            submission.probe_asn,
+            &submit_measurement_hash(submission.measurement),
+            age_range,
+            measurement_count_range,
+        )?;
+        Ok(self.server.encode_reply(&reply, framing))
+    }
+
+    fn update(&mut self, request: &[u8]) -> Result<Vec<u8>, TransportError> {
+        self.check_available()?;
+        let previous = self.previous.as_ref().ok_or_else(|| {
+            TransportError::rejected(
+                ErrorCode::UnknownPublicParameters,
+                String::from("no previous keys to update from"),
+            )
+        })?;
+        let (request, framing) = self.server.decode_request::<update::Request>(request)?;
+        let reply = self.server.handle_update(
+            &mut rand::thread_rng(),
+            request,
+            previous.secret_key_ref(),
+            previous.public_parameters_ref(),
+        )?;
+        Ok(self.server.encode_reply(&reply, framing))
+    }
+
+    fn wait(&mut self, duration: Duration) {
+        self.waits.push(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn client(rng: &mut (impl RngCore + CryptoRng)) -> ProbeClient<LocalTransport> {
+        let transport = LocalTransport::new(ServerState::new(rng));
+        let retry = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::ZERO,
+        };
+        ProbeClient::connect(transport, retry).unwrap()
+    }
+
+    fn measurement_count<T: Transport>(client: &ProbeClient<T>) -> u32 {
+        client
+            .user()
+            .credential_info()
+            .unwrap()
+            .unwrap()
+            .measurement_count
+    }
+
+    #[test]
+    fn test_submit_registers_first() {
+        let rng = &mut rand::thread_rng();
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let hook_saved = saved.clone();
+        let mut client = client(rng).on_state_change(move |user| {
+            *hook_saved.lock().unwrap() = bincode::serialize(user).unwrap();
+        });
+        assert!(client.user().get_credential().is_none());
+
+        let nym = client.submit(rng, "VE", "AS1234", b"measurement").unwrap();
+        assert_eq!(measurement_count(&client), 1);
+        // The probe ID is stable for a domain
+        assert_eq!(
+            client.submit(rng, "VE", "AS1234", b"measurement").unwrap(),
+            nym
+        );
+        assert_eq!(measurement_count(&client), 2);
+
+        // The hook saw the last state
+        let restored: UserState = bincode::deserialize(&saved.lock().unwrap()).unwrap();
+        assert_eq!(
+            restored.credential_info().unwrap(),
+            client.user().credential_info().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_submit_updates_after_key_rotation() {
+        let rng = &mut rand::thread_rng();
+        let mut client = client(rng);
+        client.submit(rng, "VE", "AS1234", b"first").unwrap();
+
+        client.transport_mut().rotate(rng);
+        client.submit(rng, "VE", "AS1234", b"second").unwrap();
+        let info = client.user().credential_info().unwrap().unwrap();
+        assert_eq!(info.measurement_count, 2);
+        assert_eq!(info.key_id, client.transport().server().key_id());
+
+        client.transport_mut().rotate(rng);
+        assert!(client.refresh(rng).unwrap());
+        assert!(!client.refresh(rng).unwrap());
+        assert_eq!(
+            client.user().credential_info().unwrap().unwrap().key_id,
+            client.transport().server().key_id()
+        );
+    }
+
+    #[test]
+    fn test_retries() {
+        let rng = &mut rand::thread_rng();
+        let transport = LocalTransport::new(ServerState::new(rng));
+        let mut client = ProbeClient::connect(transport, RetryPolicy::default()).unwrap();
+        client.transport_mut().fail_next(2);
+        client.submit(rng, "VE", "AS1234", b"measurement").unwrap();
+        assert_eq!(
+            client.transport().waits(),
+            [Duration::from_secs(1), Duration::from_secs(2)]
+        );
+
+        client.transport_mut().fail_next(3);
+        let err = client
+            .submit(rng, "VE", "AS1234", b"measurement")
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Unavailable { attempts: 3, .. }));
+        assert_eq!(measurement_count(&client), 1);
+    }
+
+    #[test]
+    fn test_connect_retries() {
+        let rng = &mut rand::thread_rng();
+        let retry = |attempts| RetryPolicy {
+            attempts,
+            backoff: Duration::ZERO,
+        };
+        let mut transport = LocalTransport::new(ServerState::new(rng));
+        transport.fail_next(2);
+        let err = ProbeClient::connect(transport, retry(2)).err().unwrap();
+        assert!(matches!(err, ClientError::Unavailable { attempts: 2, .. }));
+
+        let mut transport = LocalTransport::new(ServerState::new(rng));
+        transport.fail_next(2);
+        let client = ProbeClient::connect(transport, retry(3)).unwrap();
+        assert_eq!(client.retry, retry(3));
+    }
+
+    #[test]
+    fn test_rejections() {
+        let rng = &mut rand::thread_rng();
+        let policy = SubmitPolicy {
+            max_measurement_count: Some(2),
+            ..SubmitPolicy::default()
+        };
+        let transport = LocalTransport::new(ServerState::new(rng)).with_policy(policy);
+        let mut client = ProbeClient::connect(transport, RetryPolicy::default())
+            .unwrap()
+            .with_policy(policy);
+        assert_eq!(
+            client
+                .submit(rng, "ve", "AS1234", b"measurement")
+                .unwrap_err()
+                .code(),
+            ErrorCode::BadDomain
+        );
+        client.submit(rng, "VE", "AS1234", b"measurement").unwrap();
+        client.submit(rng, "IT", "AS1234", b"measurement").unwrap();
+        // Refused by the client before anything is sent
+        assert_eq!(
+            client
+                .submit(rng, "VE", "AS1234", b"measurement")
+                .unwrap_err()
+                .code(),
+            ErrorCode::CountExceeded
+        );
+
+        let mut client = client.with_policy(SubmitPolicy {
+            max_age_days: u32::MAX,
+            ..policy
+        });
+        let err = client
+            .submit(rng, "VE", "AS1234", b"measurement")
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Policy(_)));
+        assert_eq!(err.code(), ErrorCode::Internal);
+    }
+}
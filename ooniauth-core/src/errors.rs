@@ -1,5 +1,112 @@
+use std::fmt;
+
+use cmz::CMZError;
 use thiserror::Error;
 
+/// Stable error codes shared by the Rust, Python and FFI interfaces
+///
+/// The numeric values and names are part of the public API: they are what
+/// clients match on, so existing entries must never be renumbered or reused.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The credential is older than the accepted age range
+    CredentialExpired = 1,
+    /// The credential is too recent, or has too few measurements, for the operation
+    NotYetEligible = 2,
+    /// The credential has reached the maximum measurement count
+    CountExceeded = 3,
+    /// The probe country code or ASN is not well formed
+    BadDomain = 4,
+    /// The message was produced for public parameters the receiver does not hold
+    UnknownPublicParameters = 5,
+    /// The message could not be decoded
+    MalformedMessage = 6,
+    /// A client or issuer proof did not verify
+    ProofRejected = 7,
+    /// A credential attribute is missing or out of range
+    InvalidField = 8,
+    /// The supplied credentials do not match
+    CredentialMismatch = 9,
+    /// The operation needs a credential and none is held
+    MissingCredential = 10,
+    /// An unexpected internal failure
+    Internal = 11,
+}
+
+impl ErrorCode {
+    /// All error codes, in numeric order
+    pub const ALL: [ErrorCode; 11] = [
+        ErrorCode::CredentialExpired,
+        ErrorCode::NotYetEligible,
+        ErrorCode::CountExceeded,
+        ErrorCode::BadDomain,
+        ErrorCode::UnknownPublicParameters,
+        ErrorCode::MalformedMessage,
+        ErrorCode::ProofRejected,
+        ErrorCode::InvalidField,
+        ErrorCode::CredentialMismatch,
+        ErrorCode::MissingCredential,
+        ErrorCode::Internal,
+    ];
+
+    /// Stable snake_case name of the code
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::CredentialExpired => "credential_expired",
+            ErrorCode::NotYetEligible => "not_yet_eligible",
+            ErrorCode::CountExceeded => "count_exceeded",
+            ErrorCode::BadDomain => "bad_domain",
+            ErrorCode::UnknownPublicParameters => "unknown_public_parameters",
+            ErrorCode::MalformedMessage => "malformed_message",
+            ErrorCode::ProofRejected => "proof_rejected",
+            ErrorCode::InvalidField => "invalid_field",
+            ErrorCode::CredentialMismatch => "credential_mismatch",
+            ErrorCode::MissingCredential => "missing_credential",
+            ErrorCode::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<ErrorCode> for u32 {
+    fn from(code: ErrorCode) -> u32 {
+        code as u32
+    }
+}
+
+impl TryFrom<u32> for ErrorCode {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, u32> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|code| *code as u32 == value)
+            .ok_or(value)
+    }
+}
+
+impl From<&CMZError> for ErrorCode {
+    fn from(err: &CMZError) -> Self {
+        match err {
+            CMZError::CliProofFailed | CMZError::IssProofFailed | CMZError::Unknown => {
+                ErrorCode::ProofRejected
+            }
+            CMZError::HideAttrMissing(..)
+            | CMZError::RevealAttrMissing(..)
+            | CMZError::ImplicitAttrCliMissing(..)
+            | CMZError::ImplicitAttrIssMissing(..)
+            | CMZError::SetAttrMissing(..) => ErrorCode::InvalidField,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
 /// Credential errors that can be encountered by the client or server
 #[derive(Error, Debug)]
 pub enum CredentialError {
@@ -13,4 +120,120 @@ pub enum CredentialError {
     CredentialMismatch,
     #[error("CMZ Error")]
     CMZError(cmz::CMZError),
+    #[error("measurement count {count} is at or above maximum {max}")]
+    CountExceeded { count: u32, max: u32 },
+    #[error("measurement count {count} is below minimum {min}")]
+    CountBelowMinimum { count: u32, min: u32 },
+    #[error("no credential available")]
+    MissingCredential,
+    #[error("invalid probe domain: {0}")]
+    BadDomain(String),
+}
+
+impl CredentialError {
+    /// The stable [`ErrorCode`] for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CredentialError::TimeThresholdNotMet(_) => ErrorCode::NotYetEligible,
+            CredentialError::CredentialExpired => ErrorCode::CredentialExpired,
+            CredentialError::InvalidField(..) => ErrorCode::InvalidField,
+            CredentialError::CredentialMismatch => ErrorCode::CredentialMismatch,
+            CredentialError::CMZError(e) => e.into(),
+            CredentialError::CountExceeded { .. } => ErrorCode::CountExceeded,
+            CredentialError::CountBelowMinimum { .. } => ErrorCode::NotYetEligible,
+            CredentialError::MissingCredential => ErrorCode::MissingCredential,
+            CredentialError::BadDomain(_) => ErrorCode::BadDomain,
+        }
+    }
+}
+
+/// Errors decoding a message framed by the [`crate::wire`] envelope
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WireError {
+    #[error("message truncated: {0} bytes is shorter than the envelope header")]
+    Truncated(usize),
+    #[error("bad magic, not an ooniauth message")]
+    BadMagic,
+    #[error("unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unknown message type {0}")]
+    UnknownMessageType(u8),
+    #[error("expected message type {expected}, got {found}")]
+    UnexpectedMessageType { expected: u8, found: u8 },
+    #[error("declared payload length {declared} does not match the {actual} bytes present")]
+    LengthMismatch { declared: usize, actual: usize },
+    #[error(
+        "message was produced for key {}, expected key {}",
+        hex::encode(found),
+        hex::encode(expected)
+    )]
+    KeyMismatch { expected: [u8; 8], found: [u8; 8] },
+    #[error("invalid payload: {0}")]
+    Payload(String),
+    #[error("could not decode {type_name}: {reason}")]
+    Export {
+        type_name: &'static str,
+        reason: String,
+    },
+}
+
+/// Submit ranges that no credential can fall in. `cmz` can't prove them, see
+/// [`crate::submit::check_submit_ranges`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    #[error("{what} range [{start}, {end}) is empty")]
+    Empty {
+        what: &'static str,
+        start: u32,
+        end: u32,
+    },
+    #[error("max_age_days ({max_age_days}) does not fit before day {today}")]
+    AgeOutOfDays { max_age_days: u32, today: u32 },
+}
+
+impl WireError {
+    /// The stable [`ErrorCode`] for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            WireError::KeyMismatch { .. } => ErrorCode::UnknownPublicParameters,
+            _ => ErrorCode::MalformedMessage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_values_are_stable() {
+        for (i, code) in ErrorCode::ALL.into_iter().enumerate() {
+            assert_eq!(u32::from(code), i as u32 + 1);
+            assert_eq!(ErrorCode::try_from(u32::from(code)), Ok(code));
+        }
+        assert_eq!(ErrorCode::try_from(0), Err(0));
+        assert_eq!(ErrorCode::CredentialExpired.as_str(), "credential_expired");
+        assert_eq!(ErrorCode::Internal.to_string(), "internal");
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(
+            CredentialError::TimeThresholdNotMet(3).code(),
+            ErrorCode::NotYetEligible
+        );
+        assert_eq!(
+            CredentialError::CMZError(CMZError::CliProofFailed).code(),
+            ErrorCode::ProofRejected
+        );
+        assert_eq!(
+            WireError::KeyMismatch {
+                expected: [0; 8],
+                found: [1; 8]
+            }
+            .code(),
+            ErrorCode::UnknownPublicParameters
+        );
+        assert_eq!(WireError::BadMagic.code(), ErrorCode::MalformedMessage);
+    }
 }
@@ -11,10 +11,19 @@ use registration::UserAuthCredential;
 use serde::{Deserialize, Serialize};
 use sha2::Sha512;
 use subtle::ConstantTimeEq;
+#[cfg(feature = "std")]
+pub mod authority;
+pub mod bindings;
+pub mod canonical;
+#[cfg(feature = "std")]
+pub mod client;
 pub mod errors;
+#[cfg(feature = "tracing")]
+pub mod logging;
 pub mod registration;
 pub mod submit;
 pub mod update;
+pub mod wire;
 
 /// Version of this crate (`ooniauth-core`), from `Cargo.toml`.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -29,6 +38,7 @@ pub struct ServerState {
 pub type PublicParameters = CMZPubkey<G>;
 pub type SecretKey = CMZPrivkey<G>;
 
+#[derive(Serialize, Deserialize)]
 pub struct UserState {
     /// The public parameters for the client
     pub pp: PublicParameters,
@@ -63,6 +73,12 @@ impl ServerState {
     }
 
     /// Get today's (real or simulated) date as u32
+    ///
+    /// On `wasm32-unknown-unknown` the date comes from the JavaScript `Date`.
+    /// The `_at` variants of the functions calling this take the date instead,
+    /// for callers with their own clock, and are the only ones available
+    /// without the `std` feature.
+    #[cfg(feature = "std")]
     pub fn today() -> u32 {
         // We will not encounter negative Julian dates (~6700 years ago)
         // or ones larger than 32 bits
@@ -92,6 +108,45 @@ impl UserState {
     pub fn get_credential(&self) -> Option<&UserAuthCredential> {
         self.credential.as_ref()
     }
+
+    /// The attributes of the current credential that can be shown to the user,
+    /// `None` without a credential
+    pub fn credential_info(&self) -> Result<Option<CredentialInfo>, errors::CredentialError> {
+        self.credential
+            .as_ref()
+            .map(CredentialInfo::new)
+            .transpose()
+    }
+}
+
+/// The public view of a credential. It leaves out `nym_id`, which would link
+/// the measurements submitted with the credential
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CredentialInfo {
+    /// Day of the registration, as a Julian day like [`ServerState::today`]
+    pub age: u32,
+    /// Number of measurements submitted with the credential
+    pub measurement_count: u32,
+    /// Fingerprint of the public parameters the credential was issued under
+    pub key_id: wire::KeyId,
+}
+
+impl CredentialInfo {
+    pub fn new(credential: &UserAuthCredential) -> Result<Self, errors::CredentialError> {
+        let attribute = |name: &str, value: Option<Scalar>| {
+            value.as_ref().and_then(scalar_u32).ok_or_else(|| {
+                errors::CredentialError::InvalidField(
+                    String::from(name),
+                    String::from("could not be converted to u32"),
+                )
+            })
+        };
+        Ok(Self {
+            age: attribute("age", credential.age)?,
+            measurement_count: attribute("measurement_count", credential.measurement_count)?,
+            key_id: wire::key_id(credential.get_pubkey()),
+        })
+    }
 }
 
 // Try to extract a u32 from a Scalar
@@ -104,3 +159,35 @@ pub fn scalar_u32(s: &Scalar) -> Option<u32> {
     }
     Some(u32::from_le_bytes(sbytes[..4].try_into().unwrap()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credential_info() {
+        let rng = &mut rand::thread_rng();
+        let server = ServerState::new(rng);
+        let mut user = UserState::new(server.public_parameters());
+        assert_eq!(user.credential_info().unwrap(), None);
+
+        let (request, client_state) = user.request(rng).unwrap();
+        let reply = server
+            .open_registration_at(rng, request, 2_460_000)
+            .unwrap();
+        user.handle_response_at(client_state, reply, 2_460_000)
+            .unwrap();
+
+        let info = user.credential_info().unwrap().unwrap();
+        assert_eq!(info.age, 2_460_000);
+        assert_eq!(info.measurement_count, 0);
+        assert_eq!(info.key_id, server.key_id());
+
+        // The fingerprint follows the credential, not the parameters set on the user
+        user.pp = ServerState::new(rng).public_parameters();
+        assert_eq!(
+            user.credential_info().unwrap().unwrap().key_id,
+            server.key_id()
+        );
+    }
+}
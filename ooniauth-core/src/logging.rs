@@ -0,0 +1,173 @@
+//! A `tracing` layer formatting the events and spans of the library for the
+//! logging facility of a host language
+//!
+//! The bindings forward the logs to the host (Python's `logging` module, the
+//! callback of the C API) with a [`ForwardingLayer`] built from two functions:
+//! one saying which records the host wants, the other passing it a record.
+use std::fmt::Write;
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Formats the fields of an event or span as `message key=value ...`
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, "{}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.record_debug(field, &format_args!("{value}"));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+/// Kept in the extensions of every span, to log its duration when it closes
+struct Timing {
+    start: Instant,
+    fields: Fields,
+}
+
+/// Passes every enabled record to `emit` as a single line
+///
+/// Events inside spans are prefixed with their names, e.g. `handle_submit:
+/// Submit request verified successfully`, and spans log their duration when
+/// they close, e.g. `handle_submit took 4.211ms`. `enabled` is asked for every
+/// record, so that the host can change its settings at any time: call
+/// `tracing::callsite::rebuild_interest_cache` after a change.
+pub struct ForwardingLayer {
+    enabled: fn(&Metadata<'_>) -> bool,
+    emit: fn(&Metadata<'_>, String),
+}
+
+impl ForwardingLayer {
+    pub fn new(enabled: fn(&Metadata<'_>) -> bool, emit: fn(&Metadata<'_>, String)) -> Self {
+        Self { enabled, emit }
+    }
+}
+
+impl<S> Layer<S> for ForwardingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The settings of the host can change at any time, so ask `enabled`
+        // every time
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        (self.enabled)(metadata)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(Timing {
+            start: Instant::now(),
+            fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<Timing>() {
+            values.record(&mut timing.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut message = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                let _ = write!(message, "{}: ", span.name());
+            }
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        message.push_str(&fields.0);
+        (self.emit)(event.metadata(), message);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let message = {
+            let extensions = span.extensions();
+            let Some(timing) = extensions.get::<Timing>() else {
+                return;
+            };
+            let mut message = format!("{} took {:.3?}", span.name(), timing.start.elapsed());
+            if !timing.fields.0.is_empty() {
+                let _ = write!(message, " {}", timing.fields.0);
+            }
+            message
+        };
+        (self.emit)(span.metadata(), message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+    fn emit(metadata: &Metadata<'_>, message: String) {
+        RECORDS.lock().unwrap().push((*metadata.level(), message));
+    }
+
+    #[test]
+    fn test_forwarding_layer() {
+        let layer = ForwardingLayer::new(|metadata| *metadata.level() <= Level::DEBUG, emit);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "handle_submit",
+                probe_cc = "VE",
+                age = tracing::field::Empty
+            );
+            span.in_scope(|| {
+                tracing::debug!(count = 2, "verified");
+                tracing::trace!("filtered");
+            });
+            span.record("age", 3);
+        });
+
+        let records = RECORDS.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            (
+                Level::DEBUG,
+                String::from("handle_submit: verified count=2")
+            )
+        );
+        assert_eq!(records[1].0, Level::INFO);
+        assert!(records[1].1.starts_with("handle_submit took "));
+        assert!(records[1].1.ends_with(" probe_cc=\"VE\" age=3"));
+    }
+}
@@ -67,11 +67,23 @@ impl UserState {
 }
 
 impl UserState {
+    #[cfg(feature = "std")]
     #[instrument(skip(self, state, rep))]
     pub fn handle_response(
         &mut self,
         state: open_registration::ClientState,
         rep: open_registration::Reply,
+    ) -> Result<(), CredentialError> {
+        self.handle_response_at(state, rep, ServerState::today())
+    }
+
+    /// Like [`UserState::handle_response`], but checks the issuance date
+    /// against the given `today` instead of the system clock.
+    pub fn handle_response_at(
+        &mut self,
+        state: open_registration::ClientState,
+        rep: open_registration::Reply,
+        today: u32,
     ) -> Result<(), CredentialError> {
         trace!("Handling registration response");
         let replybytes = rep.as_bytes();
@@ -93,7 +105,6 @@ impl UserState {
                 String::from("age"),
                 String::from("missing or does not fit in u32"),
             ))?;
-        let today = ServerState::today();
         if age.abs_diff(today) > REGISTRATION_AGE_SKEW_DAYS {
             return Err(CredentialError::InvalidField(
                 String::from("age"),
@@ -126,24 +137,36 @@ impl UserState {
 }
 
 impl ServerState {
-    #[instrument(skip(self, req))]
+    #[cfg(feature = "std")]
+    #[instrument(skip(self, rng, req))]
     pub fn open_registration(
         &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        req: open_registration::Request,
+    ) -> Result<open_registration::Reply, CMZError> {
+        self.open_registration_at(rng, req, ServerState::today())
+    }
+
+    /// Like [`ServerState::open_registration`], but dates the issued
+    /// credential to the given `today` instead of the system clock.
+    pub fn open_registration_at(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
         req: open_registration::Request,
+        today: u32,
     ) -> Result<open_registration::Reply, CMZError> {
         trace!("Server opening registration");
-        let mut rng = rand::thread_rng();
         let reqbytes = req.as_bytes();
 
         let recvreq = open_registration::Request::try_from(&reqbytes[..]).unwrap();
         match open_registration::handle(
-            &mut rng,
+            rng,
             SESSION_ID,
             recvreq,
             |UAC: &mut UserAuthCredential| {
                 UAC.set_keypair(self.sk.clone(), self.pp.clone());
                 UAC.measurement_count = Some(Scalar::ZERO);
-                UAC.age = Some(ServerState::today().into());
+                UAC.age = Some(today.into());
                 Ok(())
             },
             |_UAC: &UserAuthCredential| Ok(()),
@@ -170,7 +193,7 @@ mod tests {
         assert!(result.is_ok(), "Registration request should succeed");
         let (request, client_state) = result.unwrap();
 
-        let server_response = server_state.open_registration(request);
+        let server_response = server_state.open_registration(rng, request);
         assert!(
             server_response.is_ok(),
             "Server should process registration request successfully"
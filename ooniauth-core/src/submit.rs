@@ -1,6 +1,7 @@
 use super::{scalar_u32, ServerState, UserState, G};
-use crate::errors::CredentialError;
+use crate::errors::{CredentialError, RangeError};
 use crate::registration::UserAuthCredential;
+use crate::wire::{KeyId, WireMessage};
 use cmz::*;
 use curve25519_dalek::RistrettoPoint;
 use group::Group;
@@ -45,6 +46,24 @@ muCMZProtocol!(submit<min_age_today, max_age, min_measurement_count,
     (min_measurement_count..=max_measurement_count).contains(Old.measurement_count)
 );
 
+/// What the server verified when accepting a submission, to store next to the
+/// measurement as provenance metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmitOutcome {
+    pub probe_id: [u8; 32],
+    pub probe_cc: String,
+    pub probe_asn: String,
+    pub measurement_hash: MeasurementHash,
+    /// The credential was registered in this range of days
+    pub age_range: std::ops::Range<u32>,
+    /// The credential had submitted a number of measurements in this range
+    pub measurement_count_range: std::ops::Range<u32>,
+    /// The proof session ID, derived from the measurement hash
+    pub session_id: SubmitSessionId,
+    /// The key ID of the keys that verified the credential
+    pub key_id: KeyId,
+}
+
 /// A request for a measurement submission.
 ///
 /// A [`SubmitRequest`] embeds the core submission request and the
@@ -57,13 +76,17 @@ muCMZProtocol!(submit<min_age_today, max_age, min_measurement_count,
 /// The core verification funtionality still needs this points, it's added here for this reason.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SubmitRequest {
-    core_request: submit::Request,
-    nym_point: RistrettoPoint,
+    pub(crate) core_request: submit::Request,
+    pub(crate) nym_point: RistrettoPoint,
 }
 
 impl SubmitRequest {
+    /// The bare `bincode` payload, as sent before the envelope existed.
+    #[deprecated(
+        note = "frame requests with `UserState::encode_request`, or use `WireMessage::to_payload` for legacy peers"
+    )]
     pub fn as_bytes(&self) -> Vec<u8> {
-        bincode::serialize(self).expect("failed to serialize SubmitRequest")
+        self.to_payload()
     }
 }
 
@@ -77,17 +100,136 @@ pub fn digest_point(point: RistrettoPoint) -> [u8; 32] {
     out
 }
 
+/// Check that `probe_cc` and `probe_asn` have the format expected by the backend
+///
+/// - `probe_cc`: two uppercase ASCII letters
+/// - `probe_asn`: starts with "AS", 3 <= len <= 12, and the number after "AS"
+///   has no leading zeros
+pub fn validate_probe_domain(probe_cc: &str, probe_asn: &str) -> Result<(), CredentialError> {
+    if !(probe_cc.len() == 2 && probe_cc.bytes().all(|b| b.is_ascii_uppercase())) {
+        return Err(CredentialError::BadDomain(format!(
+            "probe_cc {probe_cc:?} is not a two-letter uppercase ASCII country code"
+        )));
+    }
+    let valid_asn = (3..=12).contains(&probe_asn.len())
+        && probe_asn.strip_prefix("AS").is_some_and(|asn| {
+            asn.bytes().all(|b| b.is_ascii_digit())
+                && asn.parse::<u32>().is_ok()
+                && (asn == "0" || !asn.starts_with('0'))
+        });
+    if !valid_asn {
+        return Err(CredentialError::BadDomain(format!(
+            "probe_asn {probe_asn:?} is not an AS-prefixed number with total length 3..=12"
+        )));
+    }
+    Ok(())
+}
+
+/// Check that the age and measurement count ranges are not empty, before
+/// passing them to [`UserState::submit_request`] or
+/// [`ServerState::handle_submit`], whose proofs can't handle empty ranges
+pub fn check_submit_ranges(
+    age_range: &std::ops::Range<u32>,
+    measurement_count_range: &std::ops::Range<u32>,
+) -> Result<(), RangeError> {
+    for (what, range) in [
+        ("age", age_range),
+        ("measurement count", measurement_count_range),
+    ] {
+        if range.is_empty() {
+            return Err(RangeError::Empty {
+                what,
+                start: range.start,
+                end: range.end,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Which credentials the server accepts submissions from
+///
+/// Client and server must use the same policy: the ranges it gives are part of
+/// the submit proof, which fails to verify if they differ. This is the policy
+/// of every binding, [`SubmitPolicy::new`] and [`SubmitPolicy::ranges`] refuse
+/// the policies no credential can satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitPolicy {
+    /// Credentials registered more than this many days ago are refused
+    pub max_age_days: u32,
+    pub min_measurement_count: u32,
+    /// Excluded upper bound of the measurement count, unbounded if `None`
+    pub max_measurement_count: Option<u32>,
+}
+
+impl Default for SubmitPolicy {
+    /// 30 days and any measurement count
+    fn default() -> Self {
+        Self {
+            max_age_days: 30,
+            min_measurement_count: 0,
+            max_measurement_count: None,
+        }
+    }
+}
+
+impl SubmitPolicy {
+    /// Fails with [`RangeError::Empty`] if `min_measurement_count` is not lower
+    /// than `max_measurement_count`
+    pub fn new(
+        max_age_days: u32,
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> Result<Self, RangeError> {
+        let policy = Self {
+            max_age_days,
+            min_measurement_count,
+            max_measurement_count,
+        };
+        policy.measurement_count_range()?;
+        Ok(policy)
+    }
+
+    /// The registration days accepted on `today`: from `max_age_days` ago to
+    /// today included
+    pub fn age_range(&self, today: u32) -> Result<std::ops::Range<u32>, RangeError> {
+        match (today.checked_sub(self.max_age_days), today.checked_add(1)) {
+            (Some(start), Some(end)) => Ok(start..end),
+            _ => Err(RangeError::AgeOutOfDays {
+                max_age_days: self.max_age_days,
+                today,
+            }),
+        }
+    }
+
+    /// The accepted measurement counts, up to `u32::MAX` excluded when
+    /// `max_measurement_count` is `None`
+    pub fn measurement_count_range(&self) -> Result<std::ops::Range<u32>, RangeError> {
+        let range = self.min_measurement_count..self.max_measurement_count.unwrap_or(u32::MAX);
+        if range.is_empty() {
+            return Err(RangeError::Empty {
+                what: "measurement count",
+                start: range.start,
+                end: range.end,
+            });
+        }
+        Ok(range)
+    }
+
+    /// The age and measurement count ranges accepted on `today`, to pass to
+    /// [`UserState::submit_request`] and [`ServerState::handle_submit`]
+    pub fn ranges(
+        &self,
+        today: u32,
+    ) -> Result<(std::ops::Range<u32>, std::ops::Range<u32>), RangeError> {
+        Ok((self.age_range(today)?, self.measurement_count_range()?))
+    }
+}
+
 fn submit_domain_generator(probe_cc: &str, probe_asn: &str) -> G {
     debug_assert!(
-        probe_cc.len() == 2 && probe_cc.bytes().all(|b| b.is_ascii_uppercase()),
-        "probe_cc must be a two-letter uppercase ASCII country code"
-    );
-    debug_assert!(
-        (3..=12).contains(&probe_asn.len())
-            && probe_asn.strip_prefix("AS").is_some_and(|asn| {
-                asn.bytes().all(|b| b.is_ascii_digit()) && asn.parse::<u32>().is_ok()
-            }),
-        "probe_asn must be AS-prefixed ASCII digits with total length 3..=12"
+        validate_probe_domain(probe_cc, probe_asn).is_ok(),
+        "probe_cc and probe_asn must be validated with validate_probe_domain"
     );
 
     let domain_str = format!("ooni.org/{}/{}", probe_cc, probe_asn);
@@ -124,13 +266,11 @@ impl UserState {
         let Old = self
             .credential
             .as_ref()
-            .ok_or(CredentialError::InvalidField(
-                String::from("credential"),
-                String::from("No credential available"),
-            ))?;
+            .ok_or(CredentialError::MissingCredential)?;
 
         // Domain-specific generator and NYM computation
         trace!("Computing DOMAIN for submit request");
+        validate_probe_domain(&probe_cc, &probe_asn)?;
         let DOMAIN = submit_domain_generator(&probe_cc, &probe_asn);
         let NYM = Old.nym_id.unwrap() * DOMAIN;
         debug!("NYM computed successfully");
@@ -170,22 +310,16 @@ impl UserState {
             }
         };
         if measurement_count < measurement_count_range.start {
-            return Err(CredentialError::InvalidField(
-                String::from("measurement_count"),
-                format!(
-                    "measurement_count {} is below minimum {}",
-                    measurement_count, measurement_count_range.start
-                ),
-            ));
+            return Err(CredentialError::CountBelowMinimum {
+                count: measurement_count,
+                min: measurement_count_range.start,
+            });
         }
         if measurement_count >= measurement_count_range.end {
-            return Err(CredentialError::InvalidField(
-                String::from("measurement_count"),
-                format!(
-                    "measurement_count {} is at or above maximum {}",
-                    measurement_count, measurement_count_range.end
-                ),
-            ));
+            return Err(CredentialError::CountExceeded {
+                count: measurement_count,
+                max: measurement_count_range.end,
+            });
         }
 
         //let NYM = PRF(nym_id, nym_scope.format(probe_cc, probe_asn))
@@ -214,8 +348,8 @@ impl UserState {
                 };
                 Ok(((request, client_state), probe_id))
             }
-            Err(_) => {
-                debug!("Failed to prepare submit request");
+            Err(e) => {
+                debug!(error = %e, "Failed to prepare submit request");
                 Err(CredentialError::CMZError(CMZError::CliProofFailed))
             }
         }
@@ -239,18 +373,11 @@ impl UserState {
 }
 
 impl ServerState {
+    /// Verifies a submission, returning the reply for the probe.
+    ///
+    /// Fails with [`CredentialError::BadDomain`] if `probe_cc` or `probe_asn`
+    /// are not well formed, see [`validate_probe_domain`].
     #[allow(clippy::too_many_arguments)]
-    #[instrument(skip(
-        self,
-        rng,
-        req,
-        probe_id,
-        probe_cc,
-        probe_asn,
-        measurement_hash,
-        age_range,
-        measurement_count_range
-    ))]
     pub fn handle_submit(
         &self,
         rng: &mut (impl RngCore + CryptoRng),
@@ -261,8 +388,51 @@ impl ServerState {
         measurement_hash: &MeasurementHash,
         age_range: std::ops::Range<u32>,
         measurement_count_range: std::ops::Range<u32>,
-    ) -> Result<submit::Reply, CMZError> {
+    ) -> Result<submit::Reply, CredentialError> {
+        let (reply, _) = self.handle_submit_with_outcome(
+            rng,
+            req,
+            probe_id,
+            probe_cc,
+            probe_asn,
+            measurement_hash,
+            age_range,
+            measurement_count_range,
+        )?;
+        Ok(reply)
+    }
+
+    /// Like [`ServerState::handle_submit`], also returning the
+    /// [`SubmitOutcome`] describing what was verified.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(
+        name = "handle_submit",
+        skip(
+            self,
+            rng,
+            req,
+            probe_id,
+            probe_cc,
+            probe_asn,
+            measurement_hash,
+            age_range,
+            measurement_count_range
+        )
+    )]
+    pub fn handle_submit_with_outcome(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        req: SubmitRequest,
+        probe_id: &[u8; 32],
+        probe_cc: &str,
+        probe_asn: &str,
+        measurement_hash: &MeasurementHash,
+        age_range: std::ops::Range<u32>,
+        measurement_count_range: std::ops::Range<u32>,
+    ) -> Result<(submit::Reply, SubmitOutcome), CredentialError> {
         trace!("Server handling submit request");
+        validate_probe_domain(probe_cc, probe_asn)?;
+
         let SubmitRequest {
             core_request: recvreq,
             nym_point,
@@ -276,7 +446,7 @@ impl ServerState {
         // a malicious probe should already have computed the probe ID from the
         // (malicious) group element.
         if &digest_point(nym_point) != probe_id {
-            return Err(CMZError::IssProofFailed);
+            return Err(CredentialError::CMZError(CMZError::IssProofFailed));
         }
 
         let params = submit::Params {
@@ -312,11 +482,21 @@ impl ServerState {
         ) {
             Ok((response, (_old_cred, _new_cred))) => {
                 debug!("Submit request verified successfully");
-                Ok(response)
+                let outcome = SubmitOutcome {
+                    probe_id: *probe_id,
+                    probe_cc: probe_cc.to_owned(),
+                    probe_asn: probe_asn.to_owned(),
+                    measurement_hash: *measurement_hash,
+                    age_range,
+                    measurement_count_range,
+                    session_id,
+                    key_id: self.key_id(),
+                };
+                Ok((response, outcome))
             }
             Err(e) => {
-                debug!("Submit request verification failed");
-                Err(e)
+                debug!(error = %e, "Submit request verification failed");
+                Err(CredentialError::CMZError(e))
             }
         }
     }
@@ -325,6 +505,7 @@ impl ServerState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::ErrorCode;
     use crate::{Scalar, ServerState, UserState};
 
     #[test]
@@ -356,6 +537,138 @@ mod tests {
         assert_eq!(inclusive_upper_bound(&(0..1)), 0);
     }
 
+    #[test]
+    fn test_submit_policy_ranges() {
+        assert_eq!(
+            SubmitPolicy::default().ranges(2_460_000),
+            Ok((2_459_970..2_460_001, 0..u32::MAX))
+        );
+        let policy = SubmitPolicy::new(10, 2, Some(5)).unwrap();
+        assert_eq!(policy.ranges(10), Ok((0..11, 2..5)));
+        assert_eq!(
+            policy.ranges(3),
+            Err(RangeError::AgeOutOfDays {
+                max_age_days: 10,
+                today: 3
+            })
+        );
+        assert_eq!(
+            SubmitPolicy::new(10, 5, Some(5)),
+            Err(RangeError::Empty {
+                what: "measurement count",
+                start: 5,
+                end: 5
+            })
+        );
+        let empty = SubmitPolicy {
+            max_measurement_count: Some(1),
+            ..policy
+        };
+        assert!(empty.ranges(10).is_err());
+    }
+
+    #[test]
+    fn test_check_submit_ranges() {
+        assert!(check_submit_ranges(&(10..11), &(0..1)).is_ok());
+        assert_eq!(
+            check_submit_ranges(&(10..10), &(0..1)),
+            Err(RangeError::Empty {
+                what: "age",
+                start: 10,
+                end: 10
+            })
+        );
+        let err =
+            check_submit_ranges(&(10..11), &std::ops::Range { start: 5, end: 2 }).unwrap_err();
+        assert_eq!(err.to_string(), "measurement count range [5, 2) is empty");
+    }
+
+    #[test]
+    fn test_validate_probe_domain() {
+        assert!(validate_probe_domain("US", "AS1234").is_ok());
+        assert!(validate_probe_domain("US", "AS0").is_ok());
+        for (cc, asn) in [
+            ("us", "AS1234"),
+            ("USA", "AS1234"),
+            ("US", "1234"),
+            ("US", "AS"),
+            ("US", "AS01234"),
+            ("US", "AS12345678901"),
+        ] {
+            let err = validate_probe_domain(cc, asn).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::BadDomain, "{cc}/{asn}");
+        }
+    }
+
+    #[test]
+    fn test_submit_request_error_codes() {
+        let rng = &mut rand::thread_rng();
+        let server_state = ServerState::new(rng);
+        let mut user_state = UserState::new(server_state.public_parameters());
+        let today = ServerState::today();
+        let age_range = (today - 30)..(today + 1);
+        let measurement_hash = submit_measurement_hash(b"measurement:US:AS1234");
+        let submit = |user_state: &UserState, cc: &str, age_range, count_range| {
+            user_state
+                .submit_request(
+                    &mut rand::thread_rng(),
+                    cc.into(),
+                    "AS1234".into(),
+                    &measurement_hash,
+                    age_range,
+                    count_range,
+                )
+                .map(|_| ())
+                .unwrap_err()
+                .code()
+        };
+
+        assert_eq!(
+            submit(&user_state, "US", age_range.clone(), 0..100),
+            ErrorCode::MissingCredential
+        );
+
+        let (reg_request, reg_client_state) = user_state.request(rng).unwrap();
+        let reg_response = server_state.open_registration(rng, reg_request).unwrap();
+        user_state
+            .handle_response(reg_client_state, reg_response)
+            .unwrap();
+
+        assert_eq!(
+            submit(&user_state, "us", age_range.clone(), 0..100),
+            ErrorCode::BadDomain
+        );
+        assert_eq!(
+            submit(&user_state, "US", age_range.clone(), 0..0),
+            ErrorCode::CountExceeded
+        );
+        assert_eq!(
+            submit(&user_state, "US", age_range, 1..100),
+            ErrorCode::NotYetEligible
+        );
+        assert_eq!(
+            submit(&user_state, "US", (today + 1)..(today + 30), 0..100),
+            ErrorCode::CredentialExpired
+        );
+        assert_eq!(
+            submit(&user_state, "US", (today - 30)..today, 0..100),
+            ErrorCode::NotYetEligible
+        );
+
+        let err = user_state
+            .submit_request(
+                rng,
+                "US".into(),
+                "AS1234".into(),
+                &measurement_hash,
+                (today - 30)..(today - 2),
+                0..100,
+            )
+            .map(|_| ())
+            .unwrap_err();
+        assert!(matches!(err, CredentialError::TimeThresholdNotMet(2)));
+    }
+
     #[test]
     fn test_submit_request() {
         let rng = &mut rand::thread_rng();
@@ -369,7 +682,7 @@ mod tests {
         assert!(result.is_ok(), "Registration request should succeed");
         let (reg_request, reg_client_state) = result.unwrap();
 
-        let server_response = server_state.open_registration(reg_request);
+        let server_response = server_state.open_registration(rng, reg_request);
         assert!(
             server_response.is_ok(),
             "Server should process registration request successfully"
@@ -411,7 +724,7 @@ mod tests {
 
         // Verify the request is valid
         assert!(
-            !request.as_bytes().is_empty(),
+            !request.to_payload().is_empty(),
             "Request should have content"
         );
 
@@ -419,7 +732,7 @@ mod tests {
         assert_ne!(&nym, &[0u8; 32], "NYM should not be all zeros");
 
         // Test server handling of submit request
-        let server_result = server_state.handle_submit(
+        let server_result = server_state.handle_submit_with_outcome(
             rng,
             request,
             &nym,
@@ -433,7 +746,15 @@ mod tests {
             server_result.is_ok(),
             "Server should handle submit request successfully"
         );
-        let response = server_result.unwrap();
+        let (response, outcome) = server_result.unwrap();
+        assert_eq!(outcome.probe_id, nym);
+        assert_eq!(outcome.probe_cc, probe_cc);
+        assert_eq!(outcome.probe_asn, probe_asn);
+        assert_eq!(outcome.measurement_hash, measurement_hash);
+        assert_eq!(outcome.age_range, (today - 30)..(today + 1));
+        assert_eq!(outcome.measurement_count_range, 0..100);
+        assert_eq!(outcome.session_id, submit_session_id(&measurement_hash));
+        assert_eq!(outcome.key_id, server_state.key_id());
 
         // Test user handling of server response
         let handle_result = user_state.handle_submit_response(client_state, response);
@@ -462,7 +783,7 @@ mod tests {
         let mut user_state = UserState::new(server_state.public_parameters());
 
         let (reg_request, reg_client_state) = user_state.request(rng).unwrap();
-        let reg_response = server_state.open_registration(reg_request).unwrap();
+        let reg_response = server_state.open_registration(rng, reg_request).unwrap();
         user_state
             .handle_response(reg_client_state, reg_response)
             .unwrap();
@@ -518,4 +839,47 @@ mod tests {
             "Server should accept the submit request with the original measurement session ID"
         );
     }
+
+    #[test]
+    fn test_handle_submit_rejects_bad_domain() {
+        let rng = &mut rand::thread_rng();
+
+        let server_state = ServerState::new(rng);
+        let mut user_state = UserState::new(server_state.public_parameters());
+
+        let (reg_request, reg_client_state) = user_state.request(rng).unwrap();
+        let reg_response = server_state.open_registration(rng, reg_request).unwrap();
+        user_state
+            .handle_response(reg_client_state, reg_response)
+            .unwrap();
+
+        let today = ServerState::today();
+        let age_range = (today - 30)..(today + 1);
+        let measurement_hash = submit_measurement_hash(b"measurement:US:AS1234");
+        let ((request, _client_state), nym) = user_state
+            .submit_request(
+                rng,
+                "US".into(),
+                "AS1234".into(),
+                &measurement_hash,
+                age_range.clone(),
+                0..100,
+            )
+            .unwrap();
+
+        // The server checks the domain itself, before the proof
+        let err = server_state
+            .handle_submit(
+                rng,
+                request,
+                &nym,
+                "us",
+                "AS1234",
+                &measurement_hash,
+                age_range,
+                0..100,
+            )
+            .unwrap_err();
+        assert!(matches!(err, CredentialError::BadDomain(_)));
+    }
 }
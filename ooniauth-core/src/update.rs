@@ -26,10 +26,7 @@ impl UserState {
         let old = self
             .credential
             .as_ref()
-            .ok_or(CredentialError::InvalidField(
-                String::from("credential"),
-                String::from("No credential available"),
-            ))?;
+            .ok_or(CredentialError::MissingCredential)?;
 
         let mut new = UserAuthCredential::using_pubkey(&self.pp);
         new.nym_id = old.nym_id;
@@ -103,7 +100,9 @@ mod tests {
         let mut user_state = UserState::new(old_server_state.public_parameters());
 
         let (reg_request, reg_client_state) = user_state.request(rng).unwrap();
-        let reg_reply = old_server_state.open_registration(reg_request).unwrap();
+        let reg_reply = old_server_state
+            .open_registration(rng, reg_request)
+            .unwrap();
         user_state
             .handle_response(reg_client_state, reg_reply)
             .unwrap();
@@ -0,0 +1,519 @@
+/* A framed, self-describing envelope for every protocol message exchanged
+ * between probes and the OONI Authority (OA).
+ *
+ * Layout (multi-byte integers are big-endian):
+ *
+ * | offset | size | field                                   |
+ * |--------|------|-----------------------------------------|
+ * | 0      | 4    | magic, `b"OOAU"`                        |
+ * | 4      | 1    | version, selects the payload encoding   |
+ * | 5      | 1    | message type, see [`MessageType`]       |
+ * | 6      | 8    | key ID of the public parameters in use  |
+ * | 14     | 4    | payload length `n`                      |
+ * | 18     | n    | payload                                 |
+ *
+ * The version byte selects how the payload is encoded, see [`Encoding`]:
+ * version 1 carries `bincode`, version 2 the canonical encoding of
+ * [`crate::canonical`].
+ *
+ * Decoding is strict: the magic, version and message type must be known,
+ * and the declared payload length must match the remaining bytes exactly.
+ *
+ * Messages produced before the envelope existed are the bare `bincode`
+ * payload. Nothing tells them apart from a framed message, since a payload
+ * may start with the magic, so decoders that need to keep talking to those
+ * peers are told which framing to expect with [`DecodeMode::Legacy`].
+*/
+
+use super::{PublicParameters, ServerState, UserState};
+use crate::canonical::Canonical;
+use crate::errors::WireError;
+use crate::registration::open_registration;
+use crate::submit::{submit, SubmitRequest};
+use crate::update::update;
+use bincode::Options;
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: [u8; 4] = *b"OOAU";
+pub const HEADER_LEN: usize = 18;
+
+const KEY_ID_SALT: &[u8] = b"ooni.org/userauth/v1/kid";
+
+/// Short fingerprint of a set of public parameters, see [`key_id`].
+pub type KeyId = [u8; 8];
+
+/// Kind of message carried by an [`Envelope`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    RegistrationRequest = 1,
+    RegistrationReply = 2,
+    SubmitRequest = 3,
+    SubmitReply = 4,
+    UpdateRequest = 5,
+    UpdateReply = 6,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = WireError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(MessageType::RegistrationRequest),
+            2 => Ok(MessageType::RegistrationReply),
+            3 => Ok(MessageType::SubmitRequest),
+            4 => Ok(MessageType::SubmitReply),
+            5 => Ok(MessageType::UpdateRequest),
+            6 => Ok(MessageType::UpdateReply),
+            other => Err(WireError::UnknownMessageType(other)),
+        }
+    }
+}
+
+/// Payload encoding, carried in the version byte of an [`Envelope`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// `bincode` 1.x serialization of the Rust structs.
+    #[default]
+    Bincode = 1,
+    /// The language-neutral encoding of [`crate::canonical`].
+    Canonical = 2,
+}
+
+impl TryFrom<u8> for Encoding {
+    type Error = WireError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Encoding::Bincode),
+            2 => Ok(Encoding::Canonical),
+            other => Err(WireError::UnsupportedVersion(other)),
+        }
+    }
+}
+
+/// How a message was (or should be) put on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Wrapped in an [`Envelope`] with the given payload encoding.
+    Envelope(Encoding),
+    /// Bare `bincode` payload, as sent by clients predating the envelope.
+    Legacy,
+}
+
+/// Which framing a decoder expects, see [`ServerState::decode_request_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeMode {
+    /// Only messages framed in an [`Envelope`].
+    #[default]
+    Envelope,
+    /// Only bare `bincode` payloads, from peers predating the envelope.
+    Legacy,
+}
+
+/// Compute the key ID identifying a set of public parameters.
+///
+/// The key ID is the first 8 bytes of a salted SHA-256 over the canonical
+/// encoding of the parameters. It lets the receiver notice early that a
+/// message was produced under parameters it does not hold.
+pub fn key_id(pp: &PublicParameters) -> KeyId {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_ID_SALT);
+    hasher.update(pp.to_canonical());
+    let digest = hasher.finalize();
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&digest[..8]);
+    out
+}
+
+/// A decoded envelope, before its payload has been interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub encoding: Encoding,
+    pub message_type: MessageType,
+    pub key_id: KeyId,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(
+        encoding: Encoding,
+        message_type: MessageType,
+        key_id: KeyId,
+        payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            encoding,
+            message_type,
+            key_id,
+            payload,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let len: u32 = self
+            .payload
+            .len()
+            .try_into()
+            .expect("payload does not fit in an envelope");
+        let mut out = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        out.extend_from_slice(&MAGIC);
+        out.push(self.encoding as u8);
+        out.push(self.message_type as u8);
+        out.extend_from_slice(&self.key_id);
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+}
+
+impl TryFrom<&[u8]> for Envelope {
+    type Error = WireError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() < HEADER_LEN {
+            return Err(WireError::Truncated(bytes.len()));
+        }
+        if bytes[..4] != MAGIC {
+            return Err(WireError::BadMagic);
+        }
+        let encoding = Encoding::try_from(bytes[4])?;
+        let message_type = MessageType::try_from(bytes[5])?;
+        let key_id: KeyId = bytes[6..14].try_into().unwrap();
+        let declared = u32::from_be_bytes(bytes[14..18].try_into().unwrap()) as usize;
+        let payload = &bytes[HEADER_LEN..];
+        if declared != payload.len() {
+            return Err(WireError::LengthMismatch {
+                declared,
+                actual: payload.len(),
+            });
+        }
+        Ok(Self {
+            encoding,
+            message_type,
+            key_id,
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+/// A protocol message that can be framed in an [`Envelope`].
+pub trait WireMessage: Canonical {
+    const MESSAGE_TYPE: MessageType;
+
+    /// Encode the bare `bincode` payload, as sent before the envelope existed.
+    fn to_payload(&self) -> Vec<u8>;
+
+    /// Decode a bare `bincode` payload, rejecting trailing bytes.
+    fn from_payload(payload: &[u8]) -> Result<Self, WireError>;
+
+    /// Frame this message in a `bincode` envelope tagged with `key_id`.
+    fn to_wire(&self, key_id: KeyId) -> Vec<u8> {
+        self.to_wire_with(key_id, Encoding::Bincode)
+    }
+
+    /// Frame this message in an envelope tagged with `key_id`, encoding the
+    /// payload with `encoding`.
+    fn to_wire_with(&self, key_id: KeyId, encoding: Encoding) -> Vec<u8> {
+        let payload = match encoding {
+            Encoding::Bincode => self.to_payload(),
+            Encoding::Canonical => self.to_canonical(),
+        };
+        Envelope::new(encoding, Self::MESSAGE_TYPE, key_id, payload).as_bytes()
+    }
+
+    /// Decode the payload of an envelope holding this type of message.
+    fn from_envelope(envelope: &Envelope) -> Result<Self, WireError> {
+        if envelope.message_type != Self::MESSAGE_TYPE {
+            return Err(WireError::UnexpectedMessageType {
+                expected: Self::MESSAGE_TYPE as u8,
+                found: envelope.message_type as u8,
+            });
+        }
+        match envelope.encoding {
+            Encoding::Bincode => Self::from_payload(&envelope.payload),
+            Encoding::Canonical => Self::from_canonical(&envelope.payload),
+        }
+    }
+
+    /// Strictly decode a framed message, returning the key ID it was tagged with.
+    fn from_wire(bytes: &[u8]) -> Result<(KeyId, Self), WireError> {
+        let envelope = Envelope::try_from(bytes)?;
+        Ok((envelope.key_id, Self::from_envelope(&envelope)?))
+    }
+
+    /// Decode a message with the given framing.
+    ///
+    /// The key ID is `None` for legacy payloads.
+    fn from_wire_as(bytes: &[u8], mode: DecodeMode) -> Result<(Option<KeyId>, Self), WireError> {
+        match mode {
+            DecodeMode::Envelope => {
+                let (key_id, message) = Self::from_wire(bytes)?;
+                Ok((Some(key_id), message))
+            }
+            DecodeMode::Legacy => Ok((None, Self::from_payload(bytes)?)),
+        }
+    }
+}
+
+/// The `bincode` configuration used for payloads: identical to
+/// `bincode::serialize`, but refusing trailing bytes when decoding.
+fn payload_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+}
+
+macro_rules! impl_wire_message {
+    ($ty:ty, $message_type:expr) => {
+        impl WireMessage for $ty {
+            const MESSAGE_TYPE: MessageType = $message_type;
+
+            fn to_payload(&self) -> Vec<u8> {
+                payload_options()
+                    .serialize(self)
+                    .expect("failed to serialize message payload")
+            }
+
+            fn from_payload(payload: &[u8]) -> Result<Self, WireError> {
+                payload_options()
+                    .deserialize(payload)
+                    .map_err(|e| WireError::Payload(e.to_string()))
+            }
+        }
+    };
+}
+
+impl_wire_message!(open_registration::Request, MessageType::RegistrationRequest);
+impl_wire_message!(open_registration::Reply, MessageType::RegistrationReply);
+impl_wire_message!(SubmitRequest, MessageType::SubmitRequest);
+impl_wire_message!(submit::Reply, MessageType::SubmitReply);
+impl_wire_message!(update::Request, MessageType::UpdateRequest);
+impl_wire_message!(update::Reply, MessageType::UpdateReply);
+
+impl ServerState {
+    /// Key ID of this server's public parameters.
+    pub fn key_id(&self) -> KeyId {
+        key_id(&self.pp)
+    }
+
+    /// Decode a request sent by a client, framed in an envelope tagged with
+    /// this server's key ID.
+    ///
+    /// The returned [`Framing`] should be passed to
+    /// [`ServerState::encode_reply`], so that the client can read the answer.
+    pub fn decode_request<T: WireMessage>(&self, bytes: &[u8]) -> Result<(T, Framing), WireError> {
+        self.decode_request_as(bytes, DecodeMode::Envelope)
+    }
+
+    /// Like [`ServerState::decode_request`], with the given framing.
+    /// [`DecodeMode::Legacy`] accepts the unframed requests of clients
+    /// predating the envelope, and only those.
+    pub fn decode_request_as<T: WireMessage>(
+        &self,
+        bytes: &[u8],
+        mode: DecodeMode,
+    ) -> Result<(T, Framing), WireError> {
+        if mode == DecodeMode::Legacy {
+            return Ok((T::from_payload(bytes)?, Framing::Legacy));
+        }
+        let envelope = Envelope::try_from(bytes)?;
+        let expected = self.key_id();
+        if envelope.key_id != expected {
+            return Err(WireError::KeyMismatch {
+                expected,
+                found: envelope.key_id,
+            });
+        }
+        Ok((
+            T::from_envelope(&envelope)?,
+            Framing::Envelope(envelope.encoding),
+        ))
+    }
+
+    /// Encode a reply using the same framing as the request it answers.
+    pub fn encode_reply<T: WireMessage>(&self, reply: &T, framing: Framing) -> Vec<u8> {
+        match framing {
+            Framing::Envelope(encoding) => reply.to_wire_with(self.key_id(), encoding),
+            Framing::Legacy => reply.to_payload(),
+        }
+    }
+}
+
+impl UserState {
+    /// Key ID of the public parameters this client currently uses.
+    pub fn key_id(&self) -> KeyId {
+        key_id(&self.pp)
+    }
+
+    /// Frame a request for the server holding this client's public parameters.
+    pub fn encode_request<T: WireMessage>(&self, request: &T) -> Vec<u8> {
+        request.to_wire(self.key_id())
+    }
+
+    /// Like [`UserState::encode_request`], with the given payload encoding.
+    pub fn encode_request_with<T: WireMessage>(&self, request: &T, encoding: Encoding) -> Vec<u8> {
+        request.to_wire_with(self.key_id(), encoding)
+    }
+
+    /// Decode a reply sent by the server, framed in an envelope tagged with
+    /// this client's key ID.
+    pub fn decode_reply<T: WireMessage>(&self, bytes: &[u8]) -> Result<T, WireError> {
+        self.decode_reply_as(bytes, DecodeMode::Envelope)
+    }
+
+    /// Like [`UserState::decode_reply`], with the given framing.
+    pub fn decode_reply_as<T: WireMessage>(
+        &self,
+        bytes: &[u8],
+        mode: DecodeMode,
+    ) -> Result<T, WireError> {
+        match T::from_wire_as(bytes, mode)? {
+            (Some(found), _) if found != self.key_id() => Err(WireError::KeyMismatch {
+                expected: self.key_id(),
+                found,
+            }),
+            (_, message) => Ok(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration_request() -> (ServerState, UserState, open_registration::Request) {
+        let rng = &mut rand::thread_rng();
+        let server = ServerState::new(rng);
+        let user = UserState::new(server.public_parameters());
+        let (request, _state) = user.request(rng).unwrap();
+        (server, user, request)
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let (server, user, request) = registration_request();
+        let bytes = user.encode_request(&request);
+
+        assert_eq!(&bytes[..4], &MAGIC);
+        assert_eq!(bytes[4], Encoding::Bincode as u8);
+        assert_eq!(bytes[5], MessageType::RegistrationRequest as u8);
+        assert_eq!(&bytes[6..14], &server.key_id());
+
+        let (decoded, framing) = server
+            .decode_request::<open_registration::Request>(&bytes)
+            .unwrap();
+        assert_eq!(framing, Framing::Envelope(Encoding::Bincode));
+        assert_eq!(decoded.as_bytes(), request.as_bytes());
+    }
+
+    #[test]
+    fn test_canonical_envelope() {
+        let (server, user, request) = registration_request();
+        let bytes = user.encode_request_with(&request, Encoding::Canonical);
+        assert_eq!(bytes[4], Encoding::Canonical as u8);
+        assert_eq!(&bytes[HEADER_LEN..], &request.to_canonical()[..]);
+
+        let (decoded, framing) = server
+            .decode_request::<open_registration::Request>(&bytes)
+            .unwrap();
+        assert_eq!(framing, Framing::Envelope(Encoding::Canonical));
+        assert_eq!(decoded.as_bytes(), request.as_bytes());
+
+        let reply = server
+            .open_registration(&mut rand::thread_rng(), decoded)
+            .unwrap();
+        let reply_bytes = server.encode_reply(&reply, framing);
+        assert_eq!(reply_bytes[4], Encoding::Canonical as u8);
+        assert!(user
+            .decode_reply::<open_registration::Reply>(&reply_bytes)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_legacy_payload_accepted() {
+        let (server, user, request) = registration_request();
+        let (decoded, framing) = server
+            .decode_request_as::<open_registration::Request>(
+                &request.to_payload(),
+                DecodeMode::Legacy,
+            )
+            .unwrap();
+        assert_eq!(framing, Framing::Legacy);
+        assert_eq!(decoded.as_bytes(), request.as_bytes());
+
+        // The framing is never guessed from the content
+        assert!(matches!(
+            server.decode_request::<open_registration::Request>(&request.to_payload()),
+            Err(WireError::BadMagic)
+        ));
+        assert!(matches!(
+            server.decode_request_as::<open_registration::Request>(
+                &user.encode_request(&request),
+                DecodeMode::Legacy
+            ),
+            Err(WireError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_id_is_canonical() {
+        let (server, _user, _request) = registration_request();
+        let mut hasher = Sha256::new();
+        hasher.update(KEY_ID_SALT);
+        hasher.update(server.public_parameters_ref().to_canonical());
+        assert_eq!(server.key_id()[..], hasher.finalize()[..8]);
+    }
+
+    #[test]
+    fn test_strict_decoding() {
+        let (server, user, request) = registration_request();
+        let bytes = user.encode_request(&request);
+
+        assert_eq!(
+            open_registration::Request::from_wire(&bytes[..10]).unwrap_err(),
+            WireError::Truncated(10)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            open_registration::Request::from_wire(&trailing),
+            Err(WireError::LengthMismatch { .. })
+        ));
+
+        let mut version = bytes.clone();
+        version[4] = 0xff;
+        assert_eq!(
+            open_registration::Request::from_wire(&version).unwrap_err(),
+            WireError::UnsupportedVersion(0xff)
+        );
+
+        assert!(matches!(
+            update::Request::from_wire(&bytes),
+            Err(WireError::UnexpectedMessageType { .. })
+        ));
+
+        let mut legacy_trailing = request.to_payload();
+        legacy_trailing.push(0);
+        assert!(matches!(
+            server.decode_request_as::<open_registration::Request>(
+                &legacy_trailing,
+                DecodeMode::Legacy
+            ),
+            Err(WireError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_mismatch_rejected() {
+        let (_server, user, request) = registration_request();
+        let other_server = ServerState::new(&mut rand::thread_rng());
+        let bytes = user.encode_request(&request);
+        assert!(matches!(
+            other_server.decode_request::<open_registration::Request>(&bytes),
+            Err(WireError::KeyMismatch { .. })
+        ));
+    }
+}
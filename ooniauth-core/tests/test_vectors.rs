@@ -0,0 +1,169 @@
+//! Replay the committed known-answer test vectors.
+//!
+//! Regenerating every transcript from its seed must reproduce the committed
+//! bytes exactly, and the committed messages must still be accepted on their
+//! own, independently of the RNG.
+
+mod vectors;
+
+use cmz::CMZCredential;
+use ooniauth_core::canonical::{decode_credential, Canonical};
+use ooniauth_core::registration::{open_registration, UserAuthCredential};
+use ooniauth_core::submit::{submit, submit_session_id, SubmitRequest};
+use ooniauth_core::update::update;
+use ooniauth_core::wire::WireMessage;
+use ooniauth_core::{PublicParameters, SecretKey, ServerState};
+use std::path::PathBuf;
+use vectors::{decode, decode_seed, RegistrationVector, SubmitVector, UpdateVector};
+
+fn load<T: serde::de::DeserializeOwned>(name: &str) -> Vec<T> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-vectors")
+        .join(name);
+    let json = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("could not read {}: {e}", path.display()));
+    serde_json::from_str(&json).unwrap_or_else(|e| panic!("invalid {}: {e}", path.display()))
+}
+
+fn server(public_parameters: &str, secret_key: &str) -> ServerState {
+    ServerState::from_creds(
+        decode::<SecretKey>(secret_key),
+        decode::<PublicParameters>(public_parameters),
+    )
+}
+
+fn message_bytes(value: &str) -> Vec<u8> {
+    hex::decode(value).expect("message is not hex")
+}
+
+fn assert_credential_verifies(credential: &str, server: &ServerState) {
+    decode::<UserAuthCredential>(credential)
+        .verify_MAC(server.secret_key_ref())
+        .expect("credential does not verify under the vector's key");
+}
+
+fn assert_canonical_credential(canonical: &str, credential: &str, server: &ServerState) {
+    let decoded =
+        decode_credential(&message_bytes(canonical), server.public_parameters_ref()).unwrap();
+    decoded.verify_MAC(server.secret_key_ref()).unwrap();
+    let expected = decode::<UserAuthCredential>(credential);
+    assert_eq!(decoded.nym_id, expected.nym_id);
+    assert_eq!(decoded.age, expected.age);
+    assert_eq!(decoded.measurement_count, expected.measurement_count);
+}
+
+#[test]
+fn test_registration_vectors() {
+    let vectors = load::<RegistrationVector>("registration.json");
+    assert!(!vectors.is_empty());
+    for vector in vectors {
+        let seed = decode_seed(&vector.seed);
+        assert_eq!(vectors::registration(seed, vector.today), vector);
+
+        let server = server(&vector.public_parameters, &vector.secret_key);
+        assert_eq!(hex::encode(server.key_id()), vector.key_id);
+        let request =
+            open_registration::Request::try_from(&message_bytes(&vector.request)[..]).unwrap();
+        assert!(server
+            .open_registration_at(&mut rand::thread_rng(), request, vector.today)
+            .is_ok());
+        let reply = open_registration::Reply::try_from(&message_bytes(&vector.reply)[..]).unwrap();
+        assert_credential_verifies(&vector.credential, &server);
+
+        let canonical_request =
+            open_registration::Request::from_canonical(&message_bytes(&vector.canonical_request))
+                .unwrap();
+        assert_eq!(hex::encode(canonical_request.as_bytes()), vector.request);
+        let canonical_reply =
+            open_registration::Reply::from_canonical(&message_bytes(&vector.canonical_reply))
+                .unwrap();
+        assert_eq!(canonical_reply.as_bytes(), reply.as_bytes());
+        assert_canonical_credential(&vector.canonical_credential, &vector.credential, &server);
+    }
+}
+
+#[test]
+fn test_submit_vectors() {
+    let vectors = load::<SubmitVector>("submit.json");
+    assert!(!vectors.is_empty());
+    for vector in vectors {
+        let seed = decode_seed(&vector.seed);
+        assert_eq!(vectors::submit(seed, vector.today), vector);
+
+        let server = server(&vector.public_parameters, &vector.secret_key);
+        assert_eq!(hex::encode(server.key_id()), vector.key_id);
+        let request: SubmitRequest = bincode::deserialize(&message_bytes(&vector.request)).unwrap();
+        let probe_id: [u8; 32] = message_bytes(&vector.probe_id).try_into().unwrap();
+        let measurement_hash: [u8; 32] =
+            message_bytes(&vector.measurement_hash).try_into().unwrap();
+        let (age_start, age_end) = vector.age_range;
+        let (count_start, count_end) = vector.measurement_count_range;
+        let (_, outcome) = server
+            .handle_submit_with_outcome(
+                &mut rand::thread_rng(),
+                request,
+                &probe_id,
+                &vector.probe_cc,
+                &vector.probe_asn,
+                &measurement_hash,
+                age_start..age_end,
+                count_start..count_end,
+            )
+            .unwrap();
+        assert_eq!(outcome.probe_id, probe_id);
+        assert_eq!(outcome.session_id, submit_session_id(&measurement_hash));
+        assert_eq!(outcome.age_range, age_start..age_end);
+        assert_eq!(outcome.key_id, server.key_id());
+        assert_credential_verifies(&vector.credential, &server);
+        assert_credential_verifies(&vector.new_credential, &server);
+
+        let canonical_request =
+            SubmitRequest::from_canonical(&message_bytes(&vector.canonical_request)).unwrap();
+        assert_eq!(hex::encode(canonical_request.to_payload()), vector.request);
+        let canonical_reply =
+            submit::Reply::from_canonical(&message_bytes(&vector.canonical_reply)).unwrap();
+        assert_eq!(hex::encode(canonical_reply.as_bytes()), vector.reply);
+        assert_canonical_credential(
+            &vector.canonical_new_credential,
+            &vector.new_credential,
+            &server,
+        );
+    }
+}
+
+#[test]
+fn test_update_vectors() {
+    let vectors = load::<UpdateVector>("update.json");
+    assert!(!vectors.is_empty());
+    for vector in vectors {
+        let seed = decode_seed(&vector.seed);
+        assert_eq!(vectors::update(seed, vector.today), vector);
+
+        let old_server = server(&vector.old_public_parameters, &vector.old_secret_key);
+        let new_server = server(&vector.public_parameters, &vector.secret_key);
+        assert_eq!(hex::encode(new_server.key_id()), vector.key_id);
+        let request = update::Request::try_from(&message_bytes(&vector.request)[..]).unwrap();
+        assert!(new_server
+            .handle_update(
+                &mut rand::thread_rng(),
+                request,
+                old_server.secret_key_ref(),
+                old_server.public_parameters_ref(),
+            )
+            .is_ok());
+        assert_credential_verifies(&vector.credential, &old_server);
+        assert_credential_verifies(&vector.new_credential, &new_server);
+
+        let canonical_request =
+            update::Request::from_canonical(&message_bytes(&vector.canonical_request)).unwrap();
+        assert_eq!(hex::encode(canonical_request.as_bytes()), vector.request);
+        let canonical_reply =
+            update::Reply::from_canonical(&message_bytes(&vector.canonical_reply)).unwrap();
+        assert_eq!(hex::encode(canonical_reply.as_bytes()), vector.reply);
+        assert_canonical_credential(
+            &vector.canonical_new_credential,
+            &vector.new_credential,
+            &new_server,
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Deterministic protocol transcripts used as known-answer test vectors.
+//!
+//! Shared by `examples/gen_test_vectors.rs`, which writes them to
+//! `test-vectors/`, and `tests/test_vectors.rs`, which replays them.
+//!
+//! Each transcript is driven by a single ChaCha20 RNG seeded with `seed` and
+//! consumed in protocol order: server key generation, then (for submit and
+//! update) the registration round trip, then the request and the reply of the
+//! protocol under test. Keys, messages and credentials are hex-encoded
+//! `bincode`, exactly as they go over the wire; the `canonical_*` fields hold
+//! the same values in the encoding specified in `ENCODING.md`, and `key_id`
+//! is the key ID of the public parameters that must verify the messages.
+
+// Each includer uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use ooniauth_core::canonical::{encode_credential, Canonical};
+use ooniauth_core::registration::UserAuthCredential;
+use ooniauth_core::submit::submit_measurement_hash;
+use ooniauth_core::wire::WireMessage;
+use ooniauth_core::{ServerState, UserState};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+/// Julian day used as "today" for every transcript (2025-01-01).
+pub const VECTOR_DAY: u32 = 2_460_677;
+
+/// Seeds of the committed vectors, one transcript per seed and protocol.
+pub const SEEDS: [[u8; 32]; 2] = [[0u8; 32], [0x42u8; 32]];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegistrationVector {
+    pub seed: String,
+    pub today: u32,
+    pub public_parameters: String,
+    pub secret_key: String,
+    pub key_id: String,
+    pub request: String,
+    pub reply: String,
+    pub credential: String,
+    pub canonical_request: String,
+    pub canonical_reply: String,
+    pub canonical_credential: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubmitVector {
+    pub seed: String,
+    pub today: u32,
+    pub public_parameters: String,
+    pub secret_key: String,
+    pub key_id: String,
+    pub credential: String,
+    pub probe_cc: String,
+    pub probe_asn: String,
+    pub measurement_hash: String,
+    pub age_range: (u32, u32),
+    pub measurement_count_range: (u32, u32),
+    pub request: String,
+    pub probe_id: String,
+    pub reply: String,
+    pub new_credential: String,
+    pub canonical_request: String,
+    pub canonical_reply: String,
+    pub canonical_new_credential: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UpdateVector {
+    pub seed: String,
+    pub today: u32,
+    pub old_public_parameters: String,
+    pub old_secret_key: String,
+    pub public_parameters: String,
+    pub secret_key: String,
+    pub key_id: String,
+    pub credential: String,
+    pub request: String,
+    pub reply: String,
+    pub new_credential: String,
+    pub canonical_request: String,
+    pub canonical_reply: String,
+    pub canonical_new_credential: String,
+}
+
+pub fn encode<T: Serialize>(value: &T) -> String {
+    hex::encode(bincode::serialize(value).expect("failed to serialize vector field"))
+}
+
+pub fn decode<T: serde::de::DeserializeOwned>(value: &str) -> T {
+    let bytes = hex::decode(value).expect("vector field is not hex");
+    bincode::deserialize(&bytes).expect("vector field is not valid bincode")
+}
+
+pub fn decode_seed(value: &str) -> [u8; 32] {
+    hex::decode(value)
+        .expect("seed is not hex")
+        .try_into()
+        .expect("seed must be 32 bytes")
+}
+
+fn credential(user: &UserState) -> &UserAuthCredential {
+    user.get_credential().expect("user has no credential")
+}
+
+fn register(rng: &mut ChaCha20Rng, server: &ServerState, today: u32) -> UserState {
+    let mut user = UserState::new(server.public_parameters());
+    let (request, state) = user.request(rng).unwrap();
+    let reply = server.open_registration_at(rng, request, today).unwrap();
+    user.handle_response_at(state, reply, today).unwrap();
+    user
+}
+
+pub fn registration(seed: [u8; 32], today: u32) -> RegistrationVector {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let server = ServerState::new(&mut rng);
+    let mut user = UserState::new(server.public_parameters());
+
+    let (request, state) = user.request(&mut rng).unwrap();
+    let request_bytes = request.as_bytes();
+    let canonical_request = request.to_canonical();
+    let reply = server
+        .open_registration_at(&mut rng, request, today)
+        .unwrap();
+    let reply_bytes = reply.as_bytes();
+    let canonical_reply = reply.to_canonical();
+    user.handle_response_at(state, reply, today).unwrap();
+
+    RegistrationVector {
+        seed: hex::encode(seed),
+        today,
+        public_parameters: encode(server.public_parameters_ref()),
+        secret_key: encode(server.secret_key_ref()),
+        key_id: hex::encode(server.key_id()),
+        request: hex::encode(request_bytes),
+        reply: hex::encode(reply_bytes),
+        credential: encode(credential(&user)),
+        canonical_request: hex::encode(canonical_request),
+        canonical_reply: hex::encode(canonical_reply),
+        canonical_credential: hex::encode(encode_credential(credential(&user))),
+    }
+}
+
+pub fn submit(seed: [u8; 32], today: u32) -> SubmitVector {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let server = ServerState::new(&mut rng);
+    let mut user = register(&mut rng, &server, today);
+    let old_credential = encode(credential(&user));
+
+    let (probe_cc, probe_asn) = ("IT", "AS30722");
+    let measurement_hash = submit_measurement_hash(b"measurement:IT:AS30722");
+    let age_range = (today - 30)..(today + 1);
+    let measurement_count_range = 0..100;
+
+    let ((request, state), probe_id) = user
+        .submit_request(
+            &mut rng,
+            probe_cc.into(),
+            probe_asn.into(),
+            &measurement_hash,
+            age_range.clone(),
+            measurement_count_range.clone(),
+        )
+        .unwrap();
+    let request_bytes = request.to_payload();
+    let canonical_request = request.to_canonical();
+    let reply = server
+        .handle_submit(
+            &mut rng,
+            request,
+            &probe_id,
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            age_range.clone(),
+            measurement_count_range.clone(),
+        )
+        .unwrap();
+    let reply_bytes = reply.as_bytes();
+    let canonical_reply = reply.to_canonical();
+    user.handle_submit_response(state, reply).unwrap();
+
+    SubmitVector {
+        seed: hex::encode(seed),
+        today,
+        public_parameters: encode(server.public_parameters_ref()),
+        secret_key: encode(server.secret_key_ref()),
+        key_id: hex::encode(server.key_id()),
+        credential: old_credential,
+        probe_cc: probe_cc.into(),
+        probe_asn: probe_asn.into(),
+        measurement_hash: hex::encode(measurement_hash),
+        age_range: (age_range.start, age_range.end),
+        measurement_count_range: (measurement_count_range.start, measurement_count_range.end),
+        request: hex::encode(request_bytes),
+        probe_id: hex::encode(probe_id),
+        reply: hex::encode(reply_bytes),
+        new_credential: encode(credential(&user)),
+        canonical_request: hex::encode(canonical_request),
+        canonical_reply: hex::encode(canonical_reply),
+        canonical_new_credential: hex::encode(encode_credential(credential(&user))),
+    }
+}
+
+pub fn update(seed: [u8; 32], today: u32) -> UpdateVector {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let old_server = ServerState::new(&mut rng);
+    let mut user = register(&mut rng, &old_server, today);
+    let old_credential = encode(credential(&user));
+
+    let new_server = ServerState::new(&mut rng);
+    user.pp = new_server.public_parameters();
+    let (request, state) = user.update_request(&mut rng).unwrap();
+    let request_bytes = request.as_bytes();
+    let canonical_request = request.to_canonical();
+    let reply = new_server
+        .handle_update(
+            &mut rng,
+            request,
+            old_server.secret_key_ref(),
+            old_server.public_parameters_ref(),
+        )
+        .unwrap();
+    let reply_bytes = reply.as_bytes();
+    let canonical_reply = reply.to_canonical();
+    user.handle_update_response(state, reply).unwrap();
+
+    UpdateVector {
+        seed: hex::encode(seed),
+        today,
+        old_public_parameters: encode(old_server.public_parameters_ref()),
+        old_secret_key: encode(old_server.secret_key_ref()),
+        public_parameters: encode(new_server.public_parameters_ref()),
+        secret_key: encode(new_server.secret_key_ref()),
+        key_id: hex::encode(new_server.key_id()),
+        credential: old_credential,
+        request: hex::encode(request_bytes),
+        reply: hex::encode(reply_bytes),
+        new_credential: encode(credential(&user)),
+        canonical_request: hex::encode(canonical_request),
+        canonical_reply: hex::encode(canonical_reply),
+        canonical_new_credential: hex::encode(encode_credential(credential(&user))),
+    }
+}
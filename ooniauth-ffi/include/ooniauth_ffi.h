@@ -1,15 +1,576 @@
 #ifndef OONIAUTH_FFI_H
 #define OONIAUTH_FFI_H
 
+/* Generated by cbindgen from ooniauth-ffi/src, do not edit. Run `OONIAUTH_UPDATE_HEADER=1 cargo test -p ooniauth-ffi --test c_api` to update it. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Version of the requests and responses. Requests for other versions are
+ * rejected with `invalid_argument`
+ */
+#define OONIAUTH_JSON_VERSION 1
+
+/**
+ * Status codes returned by the C API. Values from 1 to 63 mirror
+ * [`ooniauth_core::errors::ErrorCode`] and keep its numbering, higher ones are
+ * specific to the C API.
+ */
+typedef enum {
+  OONIAUTH_STATUS_OK = 0,
+  OONIAUTH_STATUS_CREDENTIAL_EXPIRED = 1,
+  OONIAUTH_STATUS_NOT_YET_ELIGIBLE = 2,
+  OONIAUTH_STATUS_COUNT_EXCEEDED = 3,
+  OONIAUTH_STATUS_BAD_DOMAIN = 4,
+  OONIAUTH_STATUS_UNKNOWN_PUBLIC_PARAMETERS = 5,
+  OONIAUTH_STATUS_MALFORMED_MESSAGE = 6,
+  OONIAUTH_STATUS_PROOF_REJECTED = 7,
+  OONIAUTH_STATUS_INVALID_FIELD = 8,
+  OONIAUTH_STATUS_CREDENTIAL_MISMATCH = 9,
+  OONIAUTH_STATUS_MISSING_CREDENTIAL = 10,
+  OONIAUTH_STATUS_INTERNAL = 11,
+  /**
+   * A NULL pointer, a string that is not UTF-8 or an empty range was passed
+   */
+  OONIAUTH_STATUS_INVALID_ARGUMENT = 64,
+  /**
+   * The library panicked. The handles passed to the call may be left in an
+   * inconsistent state and should be freed
+   */
+  OONIAUTH_STATUS_PANIC = 65,
+} OoniauthStatus;
+
+/**
+ * Severity of a log record passed to an `OoniauthLogCallback`, and the values
+ * accepted by `ooniauth_set_log_level`
+ */
+typedef enum {
+  OONIAUTH_LOG_LEVEL_ERROR = 1,
+  OONIAUTH_LOG_LEVEL_WARN = 2,
+  OONIAUTH_LOG_LEVEL_INFO = 3,
+  OONIAUTH_LOG_LEVEL_DEBUG = 4,
+  OONIAUTH_LOG_LEVEL_TRACE = 5,
+} OoniauthLogLevel;
+
+/**
+ * Pending registration, from `ooniauth_user_registration_request`
+ */
+typedef struct OoniauthRegistrationState OoniauthRegistrationState;
+
+/**
+ * Opaque server state, holding the issuer keys
+ */
+typedef struct OoniauthServerState OoniauthServerState;
+
+/**
+ * Pending submission, from `ooniauth_user_submit_request`
+ */
+typedef struct OoniauthSubmitState OoniauthSubmitState;
+
+/**
+ * Pending credential update, from `ooniauth_user_update_request`
+ */
+typedef struct OoniauthUpdateState OoniauthUpdateState;
+
+/**
+ * Opaque client state, holding the server public parameters and the credential
+ */
+typedef struct OoniauthUserState OoniauthUserState;
+
+/**
+ * A byte buffer allocated by the library. Release it with `ooniauth_buffer_free`
+ */
+typedef struct {
+  uint8_t *data;
+  size_t len;
+} OoniauthBuffer;
+
+/**
+ * Receives the log records of the library. `target` is the Rust module the
+ * record comes from, e.g. `"ooniauth_core::submit"`. Both strings are only
+ * valid for the duration of the call, copy them to keep them.
+ *
+ * It is called on the threads that call into the library, and must not call
+ * into the library itself.
+ */
+typedef void (*OoniauthLogCallback)(OoniauthLogLevel level,
+                                    const char *target,
+                                    const char *message,
+                                    void *user_data);
+
+/**
+ * What the server verified when accepting a submission, to store next to the
+ * measurement. The domain is the `probe_cc` and `probe_asn` of the call.
+ */
+typedef struct {
+  uint8_t probe_id[32];
+  uint8_t measurement_hash[32];
+  /**
+   * The credential was registered in `[age_start, age_end)`
+   */
+  uint32_t age_start;
+  uint32_t age_end;
+  /**
+   * The credential had a measurement count in
+   * `[min_measurement_count, max_measurement_count)`
+   */
+  uint32_t min_measurement_count;
+  uint32_t max_measurement_count;
+  /**
+   * The proof session ID, derived from the measurement hash
+   */
+  uint8_t session_id[32];
+  /**
+   * The key ID of the keys that verified the credential
+   */
+  uint8_t key_id[8];
+} OoniauthSubmitOutcome;
+
+/**
+ * The half-open ranges a credential must fall in to submit a measurement, as
+ * passed to `ooniauth_user_submit_request` and `ooniauth_server_handle_submit`
+ */
+typedef struct {
+  /**
+   * Registration days, as Julian days
+   */
+  uint32_t age_start;
+  uint32_t age_end;
+  /**
+   * Measurements submitted with the credential so far
+   */
+  uint32_t min_measurement_count;
+  uint32_t max_measurement_count;
+} OoniauthSubmitRanges;
+
 #ifdef __cplusplus
 extern "C" {
-#endif
+#endif // __cplusplus
 
+/**
+ * Runs the demo flow and returns its log, or NULL if it panicked
+ */
 char *ooniauth_run_basic_usage(void);
+
+/**
+ * # Safety
+ * Caller must pass the pointer returned by `ooniauth_run_basic_usage`.
+ * The pointer must be valid, non-null, and freed exactly once.
+ */
 void ooniauth_string_free(char *ptr);
 
+/**
+ * Returns the stable name of a status code, e.g. `"credential_expired"`, or NULL for
+ * unknown codes. The returned string is static and must not be freed.
+ */
+const char *ooniauth_status_name(uint32_t status);
+
+/**
+ * Returns the message of the last failed call made on this thread, or NULL if
+ * it succeeded. Calls returning an `OoniauthStatus` set or clear it, the other
+ * functions only set it when they panic.
+ *
+ * The string belongs to the library and stays valid until the next call into
+ * the library on this thread. Copy it to keep it.
+ */
+const char *ooniauth_last_error(void);
+
+/**
+ * Hashes a measurement for `ooniauth_user_submit_request` and
+ * `ooniauth_server_handle_submit`, writing 32 bytes to `out`
+ *
+ * # Safety
+ * `measurement` must be valid for reads of `measurement_len` bytes and `out`
+ * for writes of 32 bytes.
+ */
+OoniauthStatus ooniauth_submit_measurement_hash(const uint8_t *measurement,
+                                                size_t measurement_len,
+                                                uint8_t *out);
+
+/**
+ * Releases a buffer returned by the library. Empty buffers are ignored.
+ *
+ * # Safety
+ * `buffer` must come from the library, unmodified, and be freed only once.
+ */
+void ooniauth_buffer_free(OoniauthBuffer buffer);
+
+/**
+ * Handles a request of the JSON API and returns its response, to free with
+ * `ooniauth_string_free`
+ *
+ * A request is an object with the `version` of the API, a `command` and its
+ * `params`, e.g. `{"version": 1, "command": "user_new", "params":
+ * {"public_parameters": "..."}}`. The response holds the `version` and either
+ * a `result` object or an `error` with the `code` and `message` of the
+ * failure, the codes being the names of `ooniauth_status_name`. Binary values
+ * are base64 strings, states included: calls return the updated states and
+ * keep nothing. See `ooniauth-ffi/README.md` for the commands.
+ *
+ * Returns NULL only if the response could not be allocated.
+ *
+ * # Safety
+ * `request` must be a NUL-terminated string.
+ */
+char *ooniauth_call(const char *request);
+
+/**
+ * Forwards the logs of the library to `callback`, replacing the previous one,
+ * or stops forwarding them if it is NULL. `user_data` is passed back to every
+ * call of `callback`.
+ *
+ * Spans log their duration when they close, e.g. `handle_submit took 4.211ms`,
+ * and the events inside spans are prefixed with their names, e.g.
+ * `handle_submit: Submit request verified successfully`. Only records at the
+ * level set by `ooniauth_set_log_level`, `OONIAUTH_LOG_LEVEL_INFO` by default,
+ * or more severe are forwarded.
+ *
+ * The previous callback is not called anymore once this function returns: it
+ * waits for the calls in flight, so its `user_data` can be freed right after.
+ *
+ * Returns `OONIAUTH_STATUS_INTERNAL` if another `tracing` subscriber is
+ * already installed in the process.
+ *
+ * # Safety
+ * `callback` must be safe to call from any thread with `user_data` until it is
+ * replaced, and must not unwind. It must not call into the library, which
+ * could deadlock: in particular it can't replace itself.
+ */
+OoniauthStatus ooniauth_set_log_callback(OoniauthLogCallback callback, void *user_data);
+
+/**
+ * Sets the lowest severity forwarded to the log callback, one of the
+ * `OoniauthLogLevel` values
+ */
+OoniauthStatus ooniauth_set_log_level(uint32_t level);
+
+/**
+ * Creates a server state with fresh keys
+ *
+ * # Safety
+ * `out` must be valid for writes. Free the handle with `ooniauth_server_free`.
+ */
+OoniauthStatus ooniauth_server_new(OoniauthServerState **out);
+
+/**
+ * Restores a server state from the keys returned by
+ * `ooniauth_server_public_parameters` and `ooniauth_server_secret_key`
+ *
+ * # Safety
+ * The byte arguments must be valid for reads of their length, and `out` valid
+ * for writes. Free the handle with `ooniauth_server_free`.
+ */
+OoniauthStatus ooniauth_server_from_creds(const uint8_t *public_parameters,
+                                          size_t public_parameters_len,
+                                          const uint8_t *secret_key,
+                                          size_t secret_key_len,
+                                          OoniauthServerState **out);
+
+/**
+ * Releases a server state. NULL is ignored
+ *
+ * # Safety
+ * `server` must come from the library and be freed only once.
+ */
+void ooniauth_server_free(OoniauthServerState *server);
+
+/**
+ * The public parameters of the server, to hand out to clients
+ *
+ * # Safety
+ * `server` must be a valid handle and `out` valid for writes.
+ */
+OoniauthStatus ooniauth_server_public_parameters(const OoniauthServerState *server,
+                                                 OoniauthBuffer *out);
+
+/**
+ * The secret key of the server, to store along with the public parameters
+ *
+ * # Safety
+ * `server` must be a valid handle and `out` valid for writes.
+ */
+OoniauthStatus ooniauth_server_secret_key(const OoniauthServerState *server, OoniauthBuffer *out);
+
+/**
+ * Handles a registration request, writing the response to `response`
+ *
+ * # Safety
+ * `server` must be a valid handle, `request` valid for reads of `request_len`
+ * bytes and `response` valid for writes.
+ */
+OoniauthStatus ooniauth_server_handle_registration(const OoniauthServerState *server,
+                                                   const uint8_t *request,
+                                                   size_t request_len,
+                                                   OoniauthBuffer *response);
+
+/**
+ * Handles a submit request, writing the response to `response`
+ *
+ * `nym` and `measurement_hash` are 32 bytes long. The credential must have
+ * been registered in `[age_start, age_end)` and hold a measurement count in
+ * `[min_measurement_count, max_measurement_count)`, with the same bounds the
+ * client used.
+ *
+ * # Safety
+ * `server` must be a valid handle, `nym` and `measurement_hash` valid for
+ * reads of 32 bytes, `request` for reads of `request_len` bytes, `probe_cc`
+ * and `probe_asn` NUL-terminated strings and `response` valid for writes.
+ */
+OoniauthStatus ooniauth_server_handle_submit(const OoniauthServerState *server,
+                                             const uint8_t *nym,
+                                             const uint8_t *request,
+                                             size_t request_len,
+                                             const char *probe_cc,
+                                             const char *probe_asn,
+                                             const uint8_t *measurement_hash,
+                                             uint32_t age_start,
+                                             uint32_t age_end,
+                                             uint32_t min_measurement_count,
+                                             uint32_t max_measurement_count,
+                                             OoniauthBuffer *response);
+
+/**
+ * Same as `ooniauth_server_handle_submit`, also writing what was verified to
+ * `outcome`
+ *
+ * # Safety
+ * As for `ooniauth_server_handle_submit`, and `outcome` must be valid for
+ * writes.
+ */
+OoniauthStatus ooniauth_server_handle_submit_with_outcome(const OoniauthServerState *server,
+                                                          const uint8_t *nym,
+                                                          const uint8_t *request,
+                                                          size_t request_len,
+                                                          const char *probe_cc,
+                                                          const char *probe_asn,
+                                                          const uint8_t *measurement_hash,
+                                                          uint32_t age_start,
+                                                          uint32_t age_end,
+                                                          uint32_t min_measurement_count,
+                                                          uint32_t max_measurement_count,
+                                                          OoniauthBuffer *response,
+                                                          OoniauthSubmitOutcome *outcome);
+
+/**
+ * Handles a credential update request, moving a credential issued under the
+ * old keys to the keys of `server`
+ *
+ * # Safety
+ * `server` must be a valid handle, the byte arguments valid for reads of
+ * their length and `response` valid for writes.
+ */
+OoniauthStatus ooniauth_server_handle_update(const OoniauthServerState *server,
+                                             const uint8_t *request,
+                                             size_t request_len,
+                                             const uint8_t *old_public_parameters,
+                                             size_t old_public_parameters_len,
+                                             const uint8_t *old_secret_key,
+                                             size_t old_secret_key_len,
+                                             OoniauthBuffer *response);
+
+/**
+ * Today as a Julian day, the unit of the age ranges
+ */
+uint32_t ooniauth_today(void);
+
+/**
+ * Writes to `out` the ranges a submit policy accepts on `today`: credentials
+ * registered at most `max_age_days` ago, with at least `min_measurement_count`
+ * and fewer than `*max_measurement_count` measurements (no upper bound when
+ * it is NULL). This is the `SubmitPolicy` of the other bindings.
+ *
+ * Fails with `OONIAUTH_STATUS_INVALID_ARGUMENT` when no credential can
+ * satisfy the policy.
+ *
+ * # Safety
+ * `max_measurement_count` must be NULL or valid for reads, and `out` valid for
+ * writes.
+ */
+OoniauthStatus ooniauth_submit_policy_ranges(uint32_t max_age_days,
+                                             uint32_t min_measurement_count,
+                                             const uint32_t *max_measurement_count,
+                                             uint32_t today,
+                                             OoniauthSubmitRanges *out);
+
+/**
+ * Creates a client state without a credential for the given public parameters
+ *
+ * # Safety
+ * `public_parameters` must be valid for reads of `public_parameters_len` bytes
+ * and `out` valid for writes. Free the handle with `ooniauth_user_free`.
+ */
+OoniauthStatus ooniauth_user_new(const uint8_t *public_parameters,
+                                 size_t public_parameters_len,
+                                 OoniauthUserState **out);
+
+/**
+ * Releases a client state. NULL is ignored
+ *
+ * # Safety
+ * `user` must come from the library and be freed only once.
+ */
+void ooniauth_user_free(OoniauthUserState *user);
+
+/**
+ * Exports the whole client state, to be restored with `ooniauth_user_from_bytes`
+ *
+ * # Safety
+ * `user` must be a valid handle and `out` valid for writes.
+ */
+OoniauthStatus ooniauth_user_to_bytes(const OoniauthUserState *user, OoniauthBuffer *out);
+
+/**
+ * # Safety
+ * `data` must be valid for reads of `len` bytes and `out` valid for writes.
+ * Free the handle with `ooniauth_user_free`.
+ */
+OoniauthStatus ooniauth_user_from_bytes(const uint8_t *data, size_t len, OoniauthUserState **out);
+
+/**
+ * Exports the credential. Fails with `MissingCredential` before registration
+ *
+ * # Safety
+ * `user` must be a valid handle and `out` valid for writes.
+ */
+OoniauthStatus ooniauth_user_get_credential(const OoniauthUserState *user, OoniauthBuffer *out);
+
+/**
+ * Imports a credential exported by `ooniauth_user_get_credential`, replacing
+ * the current one
+ *
+ * # Safety
+ * `user` must be a valid handle and `data` valid for reads of `len` bytes.
+ */
+OoniauthStatus ooniauth_user_set_credential(OoniauthUserState *user,
+                                            const uint8_t *data,
+                                            size_t len);
+
+/**
+ * Replaces the public parameters, after the server rotated its keys. Follow
+ * with a credential update
+ *
+ * # Safety
+ * `user` must be a valid handle and `public_parameters` valid for reads of
+ * `public_parameters_len` bytes.
+ */
+OoniauthStatus ooniauth_user_set_public_parameters(OoniauthUserState *user,
+                                                   const uint8_t *public_parameters,
+                                                   size_t public_parameters_len);
+
+/**
+ * Makes a registration request. Send `request` to the server, and pass
+ * `pending` along with its response to `ooniauth_user_handle_registration_response`
+ *
+ * # Safety
+ * `user` must be a valid handle, `request` and `pending` valid for writes.
+ */
+OoniauthStatus ooniauth_user_registration_request(const OoniauthUserState *user,
+                                                  OoniauthBuffer *request,
+                                                  OoniauthRegistrationState **pending);
+
+/**
+ * Stores the credential issued by the server. Releases `pending` on success, it
+ * is left for `ooniauth_registration_state_free` or another response on error
+ *
+ * # Safety
+ * `user` and `pending` must be valid handles, and `response` valid for reads
+ * of `response_len` bytes.
+ */
+OoniauthStatus ooniauth_user_handle_registration_response(OoniauthUserState *user,
+                                                          OoniauthRegistrationState *pending,
+                                                          const uint8_t *response,
+                                                          size_t response_len);
+
+/**
+ * Releases a registration that will not be completed. NULL is ignored
+ *
+ * # Safety
+ * `pending` must come from the library and not have been released.
+ */
+void ooniauth_registration_state_free(OoniauthRegistrationState *pending);
+
+/**
+ * Makes a submit request for a measurement. Send `nym` (32 bytes) and
+ * `request` to the server, and pass `pending` along with its response to
+ * `ooniauth_user_handle_submit_response`
+ *
+ * The ranges are half-open and must match the ones of the server.
+ *
+ * # Safety
+ * `user` must be a valid handle, `probe_cc` and `probe_asn` NUL-terminated
+ * strings, `measurement_hash` valid for reads of 32 bytes, `nym` for writes of
+ * 32 bytes and `request` and `pending` valid for writes.
+ */
+OoniauthStatus ooniauth_user_submit_request(const OoniauthUserState *user,
+                                            const char *probe_cc,
+                                            const char *probe_asn,
+                                            const uint8_t *measurement_hash,
+                                            uint32_t age_start,
+                                            uint32_t age_end,
+                                            uint32_t min_measurement_count,
+                                            uint32_t max_measurement_count,
+                                            uint8_t *nym,
+                                            OoniauthBuffer *request,
+                                            OoniauthSubmitState **pending);
+
+/**
+ * Replaces the credential with the one issued by the server in response to a
+ * submission. Releases `pending` on success, it is left for
+ * `ooniauth_submit_state_free` or another response on error
+ *
+ * # Safety
+ * `user` and `pending` must be valid handles, and `response` valid for reads
+ * of `response_len` bytes.
+ */
+OoniauthStatus ooniauth_user_handle_submit_response(OoniauthUserState *user,
+                                                    OoniauthSubmitState *pending,
+                                                    const uint8_t *response,
+                                                    size_t response_len);
+
+/**
+ * Releases a submission that will not be completed. NULL is ignored
+ *
+ * # Safety
+ * `pending` must come from the library and not have been released.
+ */
+void ooniauth_submit_state_free(OoniauthSubmitState *pending);
+
+/**
+ * Makes a request to move the credential to the current public parameters.
+ * Send `request` to the server, and pass `pending` along with its response
+ * to `ooniauth_user_handle_update_response`
+ *
+ * # Safety
+ * `user` must be a valid handle, `request` and `pending` valid for writes.
+ */
+OoniauthStatus ooniauth_user_update_request(const OoniauthUserState *user,
+                                            OoniauthBuffer *request,
+                                            OoniauthUpdateState **pending);
+
+/**
+ * Stores the updated credential. Releases `pending` on success, it is left for
+ * `ooniauth_update_state_free` or another response on error
+ *
+ * # Safety
+ * `user` and `pending` must be valid handles, and `response` valid for reads
+ * of `response_len` bytes.
+ */
+OoniauthStatus ooniauth_user_handle_update_response(OoniauthUserState *user,
+                                                    OoniauthUpdateState *pending,
+                                                    const uint8_t *response,
+                                                    size_t response_len);
+
+/**
+ * Releases a credential update that will not be completed. NULL is ignored
+ *
+ * # Safety
+ * `pending` must come from the library and not have been released.
+ */
+void ooniauth_update_state_free(OoniauthUpdateState *pending);
+
 #ifdef __cplusplus
-} // extern "C"
-#endif
+}  // extern "C"
+#endif  // __cplusplus
 
-#endif // OONIAUTH_FFI_H
+#endif  /* OONIAUTH_FFI_H */
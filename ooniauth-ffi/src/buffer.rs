@@ -0,0 +1,111 @@
+//! Byte buffers passed across the C API
+use std::ffi::{c_char, CStr};
+
+use crate::{catch_panic, FfiError};
+
+/// A byte buffer allocated by the library. Release it with `ooniauth_buffer_free`
+#[repr(C)]
+#[derive(Debug)]
+pub struct OoniauthBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl OoniauthBuffer {
+    /// An empty buffer, which needs no freeing
+    pub const EMPTY: Self = Self {
+        data: std::ptr::null_mut(),
+        len: 0,
+    };
+
+    pub(crate) fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        Self {
+            data: bytes as *mut u8,
+            len: bytes.len(),
+        }
+    }
+}
+
+/// Releases a buffer returned by the library. Empty buffers are ignored.
+///
+/// # Safety
+/// `buffer` must come from the library, unmodified, and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_buffer_free(buffer: OoniauthBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+    catch_panic((), || {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )))
+    })
+}
+
+/// Borrow `len` bytes from `data`. `data` may be NULL when `len` is 0
+pub(crate) unsafe fn bytes_arg<'a>(
+    data: *const u8,
+    len: usize,
+    name: &str,
+) -> Result<&'a [u8], FfiError> {
+    if data.is_null() {
+        return match len {
+            0 => Ok(&[]),
+            _ => Err(FfiError::null_argument(name)),
+        };
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+/// Copy a 32 byte array, such as a nym or a measurement hash, from `data`
+pub(crate) unsafe fn array_32_arg(data: *const u8, name: &str) -> Result<[u8; 32], FfiError> {
+    if data.is_null() {
+        return Err(FfiError::null_argument(name));
+    }
+    Ok(*(data as *const [u8; 32]))
+}
+
+/// Borrow a NUL-terminated UTF-8 string
+pub(crate) unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::null_argument(name));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| FfiError::invalid_argument(format!("{name} is not valid UTF-8")))
+}
+
+/// Store `value` in the output argument `out`
+pub(crate) unsafe fn write_out<T>(out: *mut T, value: T, name: &str) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(FfiError::null_argument(name));
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Fail before doing any work if an output argument is NULL
+pub(crate) fn check_out<T>(out: *mut T, name: &str) -> Result<(), FfiError> {
+    match out.is_null() {
+        true => Err(FfiError::null_argument(name)),
+        false => Ok(()),
+    }
+}
+
+/// Borrow the object behind a handle
+pub(crate) unsafe fn handle_ref<'a, T>(handle: *const T, name: &str) -> Result<&'a T, FfiError> {
+    handle.as_ref().ok_or_else(|| FfiError::null_argument(name))
+}
+
+pub(crate) unsafe fn handle_mut<'a, T>(handle: *mut T, name: &str) -> Result<&'a mut T, FfiError> {
+    handle.as_mut().ok_or_else(|| FfiError::null_argument(name))
+}
+
+/// Take back ownership of a handle returned as a `Box`. NULL handles are ignored
+pub(crate) unsafe fn free_handle<T>(handle: *mut T) {
+    if !handle.is_null() {
+        catch_panic((), || drop(Box::from_raw(handle)))
+    }
+}
@@ -0,0 +1,704 @@
+//! JSON message API, a single entry point for embedders that would rather
+//! exchange messages than call the typed functions, see `ooniauth_call`
+//!
+//! The calls are stateless: client and server states go in and out of every
+//! call as base64 strings, in the bincode form of the `_bytes` functions and of
+//! the Python bindings.
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use base64::prelude::*;
+use ooniauth_core::bindings;
+use ooniauth_core::errors::CredentialError;
+use ooniauth_core::registration::{open_registration, UserAuthCredential};
+use ooniauth_core::submit::{submit, submit_measurement_hash, SubmitPolicy, SubmitRequest};
+use ooniauth_core::update::update;
+use ooniauth_core::{PublicParameters, ServerState, UserState};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+use crate::buffer::str_arg;
+use crate::{set_last_error, submit_ranges, FfiError};
+
+/// Version of the requests and responses. Requests for other versions are
+/// rejected with `invalid_argument`
+pub const OONIAUTH_JSON_VERSION: u32 = 1;
+
+/// Binary values cross the JSON API as standard base64 strings, with padding
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Base64(Vec<u8>);
+
+impl Serialize for Base64 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(string)
+            .map(Base64)
+            .map_err(|e| D::Error::custom(format!("invalid base64: {e}")))
+    }
+}
+
+impl Base64 {
+    /// Decode the bincode value held in the string
+    fn decode<T: serde::de::DeserializeOwned>(&self) -> Result<T, FfiError> {
+        Ok(bindings::from_bytes(&self.0)?)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Self {
+        Self(bindings::to_bytes(value))
+    }
+
+    /// A 32 byte value, such as a nym or a measurement hash
+    fn array_32(&self, name: &str) -> Result<[u8; 32], FfiError> {
+        self.0.as_slice().try_into().map_err(|_| {
+            FfiError::invalid_argument(format!(
+                "{name} is {} bytes long, expected 32",
+                self.0.len()
+            ))
+        })
+    }
+}
+
+/// The commands, with their parameters. Ranges are half-open `[start, end]`
+/// arrays
+#[derive(Deserialize)]
+#[serde(tag = "command", content = "params", rename_all = "snake_case")]
+enum Command {
+    Today,
+    SubmitMeasurementHash {
+        measurement: Base64,
+    },
+    SubmitPolicyRanges {
+        max_age_days: u32,
+        #[serde(default)]
+        min_measurement_count: u32,
+        #[serde(default)]
+        max_measurement_count: Option<u32>,
+        #[serde(default)]
+        today: Option<u32>,
+    },
+    ServerNew,
+    ServerPublicParameters {
+        server: Base64,
+    },
+    ServerHandleRegistration {
+        server: Base64,
+        request: Base64,
+    },
+    ServerHandleSubmit {
+        server: Base64,
+        nym: Base64,
+        request: Base64,
+        probe_cc: String,
+        probe_asn: String,
+        measurement_hash: Base64,
+        age_range: (u32, u32),
+        measurement_count_range: (u32, u32),
+    },
+    ServerHandleUpdate {
+        server: Base64,
+        old_server: Base64,
+        request: Base64,
+    },
+    UserNew {
+        public_parameters: Base64,
+    },
+    UserSetPublicParameters {
+        user: Base64,
+        public_parameters: Base64,
+    },
+    UserCredential {
+        user: Base64,
+    },
+    UserSetCredential {
+        user: Base64,
+        credential: Base64,
+    },
+    UserCredentialInfo {
+        user: Base64,
+    },
+    UserRegistrationRequest {
+        user: Base64,
+    },
+    UserHandleRegistrationResponse {
+        user: Base64,
+        pending: Base64,
+        response: Base64,
+        /// Checked against the date of the credential, defaults to today
+        #[serde(default)]
+        today: Option<u32>,
+    },
+    UserSubmitRequest {
+        user: Base64,
+        probe_cc: String,
+        probe_asn: String,
+        measurement_hash: Base64,
+        age_range: (u32, u32),
+        measurement_count_range: (u32, u32),
+    },
+    UserHandleSubmitResponse {
+        user: Base64,
+        pending: Base64,
+        response: Base64,
+    },
+    UserUpdateRequest {
+        user: Base64,
+    },
+    UserHandleUpdateResponse {
+        user: Base64,
+        pending: Base64,
+        response: Base64,
+    },
+}
+
+impl Command {
+    fn run(self) -> Result<Value, FfiError> {
+        let mut rng = rand::thread_rng();
+        let result = match self {
+            Command::Today => json!({ "today": ServerState::today() }),
+            Command::SubmitMeasurementHash { measurement } => {
+                json!({ "hash": Base64(submit_measurement_hash(&measurement.0).to_vec()) })
+            }
+            Command::SubmitPolicyRanges {
+                max_age_days,
+                min_measurement_count,
+                max_measurement_count,
+                today,
+            } => {
+                let policy =
+                    SubmitPolicy::new(max_age_days, min_measurement_count, max_measurement_count)?;
+                let (age, count) = policy.ranges(today.unwrap_or_else(ServerState::today))?;
+                json!({
+                    "age_range": [age.start, age.end],
+                    "measurement_count_range": [count.start, count.end],
+                })
+            }
+            Command::ServerNew => {
+                let server = ServerState::new(&mut rng);
+                json!({
+                    "server": Base64::encode(&server),
+                    "public_parameters": Base64::encode(server.public_parameters_ref()),
+                })
+            }
+            Command::ServerPublicParameters { server } => {
+                let server: ServerState = server.decode()?;
+                json!({ "public_parameters": Base64::encode(server.public_parameters_ref()) })
+            }
+            Command::ServerHandleRegistration { server, request } => {
+                let server: ServerState = server.decode()?;
+                let (request, framing) =
+                    server.decode_request::<open_registration::Request>(&request.0)?;
+                let reply = server.open_registration(&mut rng, request)?;
+                json!({ "response": Base64(server.encode_reply(&reply, framing)) })
+            }
+            Command::ServerHandleSubmit {
+                server,
+                nym,
+                request,
+                probe_cc,
+                probe_asn,
+                measurement_hash,
+                age_range,
+                measurement_count_range,
+            } => {
+                let server: ServerState = server.decode()?;
+                let nym = nym.array_32("nym")?;
+                let measurement_hash = measurement_hash.array_32("measurement_hash")?;
+                let (age_range, measurement_count_range) = submit_ranges(
+                    age_range.0,
+                    age_range.1,
+                    measurement_count_range.0,
+                    measurement_count_range.1,
+                )?;
+                let (request, framing) = server.decode_request::<SubmitRequest>(&request.0)?;
+                let (reply, outcome) = server.handle_submit_with_outcome(
+                    &mut rng,
+                    request,
+                    &nym,
+                    &probe_cc,
+                    &probe_asn,
+                    &measurement_hash,
+                    age_range,
+                    measurement_count_range,
+                )?;
+                json!({
+                    "response": Base64(server.encode_reply(&reply, framing)),
+                    "outcome": {
+                        "probe_id": Base64(outcome.probe_id.to_vec()),
+                        "measurement_hash": Base64(outcome.measurement_hash.to_vec()),
+                        "age_range": [outcome.age_range.start, outcome.age_range.end],
+                        "measurement_count_range": [
+                            outcome.measurement_count_range.start,
+                            outcome.measurement_count_range.end,
+                        ],
+                        "session_id": Base64(outcome.session_id.to_vec()),
+                        "key_id": Base64(outcome.key_id.to_vec()),
+                    },
+                })
+            }
+            Command::ServerHandleUpdate {
+                server,
+                old_server,
+                request,
+            } => {
+                let server: ServerState = server.decode()?;
+                let old_server: ServerState = old_server.decode()?;
+                let (request, framing) = server.decode_request::<update::Request>(&request.0)?;
+                let reply = server.handle_update(
+                    &mut rng,
+                    request,
+                    old_server.secret_key_ref(),
+                    old_server.public_parameters_ref(),
+                )?;
+                json!({ "response": Base64(server.encode_reply(&reply, framing)) })
+            }
+            Command::UserNew { public_parameters } => {
+                let pp: PublicParameters = public_parameters.decode()?;
+                json!({ "user": Base64::encode(&UserState::new(pp)) })
+            }
+            Command::UserSetPublicParameters {
+                user,
+                public_parameters,
+            } => {
+                let mut user: UserState = user.decode()?;
+                user.pp = public_parameters.decode()?;
+                json!({ "user": Base64::encode(&user) })
+            }
+            Command::UserCredential { user } => {
+                let user: UserState = user.decode()?;
+                let credential = user
+                    .get_credential()
+                    .ok_or(CredentialError::MissingCredential)?;
+                json!({ "credential": Base64::encode(credential) })
+            }
+            Command::UserSetCredential { user, credential } => {
+                let mut user: UserState = user.decode()?;
+                let credential: UserAuthCredential = credential.decode()?;
+                user.set_credential(credential);
+                json!({ "user": Base64::encode(&user) })
+            }
+            Command::UserCredentialInfo { user } => {
+                let user: UserState = user.decode()?;
+                let info = user.credential_info()?.map(|info| {
+                    json!({
+                        "age": info.age,
+                        "measurement_count": info.measurement_count,
+                        "key_id": Base64(info.key_id.to_vec()),
+                    })
+                });
+                json!({ "info": info })
+            }
+            Command::UserRegistrationRequest { user } => {
+                let user: UserState = user.decode()?;
+                let (request, state) = user.request(&mut rng)?;
+                json!({
+                    "request": Base64(user.encode_request(&request)),
+                    "pending": Base64::encode(&state),
+                })
+            }
+            Command::UserHandleRegistrationResponse {
+                user,
+                pending,
+                response,
+                today,
+            } => {
+                let mut user: UserState = user.decode()?;
+                let state: open_registration::ClientState = pending.decode()?;
+                let reply = user.decode_reply::<open_registration::Reply>(&response.0)?;
+                let today = today.unwrap_or_else(ServerState::today);
+                user.handle_response_at(state, reply, today)?;
+                json!({ "user": Base64::encode(&user) })
+            }
+            Command::UserSubmitRequest {
+                user,
+                probe_cc,
+                probe_asn,
+                measurement_hash,
+                age_range,
+                measurement_count_range,
+            } => {
+                let user: UserState = user.decode()?;
+                let measurement_hash = measurement_hash.array_32("measurement_hash")?;
+                let (age_range, measurement_count_range) = submit_ranges(
+                    age_range.0,
+                    age_range.1,
+                    measurement_count_range.0,
+                    measurement_count_range.1,
+                )?;
+                let ((request, state), nym) = user.submit_request(
+                    &mut rng,
+                    probe_cc,
+                    probe_asn,
+                    &measurement_hash,
+                    age_range,
+                    measurement_count_range,
+                )?;
+                json!({
+                    "nym": Base64(nym.to_vec()),
+                    "request": Base64(user.encode_request(&request)),
+                    "pending": Base64::encode(&state),
+                })
+            }
+            Command::UserHandleSubmitResponse {
+                user,
+                pending,
+                response,
+            } => {
+                let mut user: UserState = user.decode()?;
+                let state: submit::ClientState = pending.decode()?;
+                let reply = user.decode_reply::<submit::Reply>(&response.0)?;
+                user.handle_submit_response(state, reply)?;
+                json!({ "user": Base64::encode(&user) })
+            }
+            Command::UserUpdateRequest { user } => {
+                let user: UserState = user.decode()?;
+                let (request, state) = user.update_request(&mut rng)?;
+                json!({
+                    "request": Base64(user.encode_request(&request)),
+                    "pending": Base64::encode(&state),
+                })
+            }
+            Command::UserHandleUpdateResponse {
+                user,
+                pending,
+                response,
+            } => {
+                let mut user: UserState = user.decode()?;
+                let state: update::ClientState = pending.decode()?;
+                let reply = user.decode_reply::<update::Reply>(&response.0)?;
+                user.handle_update_response(state, reply)?;
+                json!({ "user": Base64::encode(&user) })
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// Parses a request, checking its version before its command
+fn parse(request: &str) -> Result<Command, FfiError> {
+    let invalid =
+        |e: serde_json::Error| FfiError::invalid_argument(format!("invalid request: {e}"));
+    let request: Value = serde_json::from_str(request).map_err(invalid)?;
+    match request.get("version").and_then(Value::as_u64) {
+        Some(version) if version == u64::from(OONIAUTH_JSON_VERSION) => {}
+        Some(version) => {
+            return Err(FfiError::invalid_argument(format!(
+                "unsupported version {version}, expected {OONIAUTH_JSON_VERSION}"
+            )))
+        }
+        None => return Err(FfiError::invalid_argument("version is missing")),
+    }
+    serde_json::from_value(request).map_err(invalid)
+}
+
+/// Handles a JSON request, returning the JSON response
+pub(crate) fn call(request: &str) -> String {
+    let result = catch_unwind(AssertUnwindSafe(|| parse(request)?.run()))
+        .unwrap_or_else(|e| Err(FfiError::panic(e)));
+    let response = match result {
+        Ok(result) => json!({ "version": OONIAUTH_JSON_VERSION, "result": result }),
+        Err(err) => json!({
+            "version": OONIAUTH_JSON_VERSION,
+            "error": {
+                "code": err.status.name().to_str().expect("status names are ASCII"),
+                "message": err.message,
+            },
+        }),
+    };
+    response.to_string()
+}
+
+/// Handles a request of the JSON API and returns its response, to free with
+/// `ooniauth_string_free`
+///
+/// A request is an object with the `version` of the API, a `command` and its
+/// `params`, e.g. `{"version": 1, "command": "user_new", "params":
+/// {"public_parameters": "..."}}`. The response holds the `version` and either
+/// a `result` object or an `error` with the `code` and `message` of the
+/// failure, the codes being the names of `ooniauth_status_name`. Binary values
+/// are base64 strings, states included: calls return the updated states and
+/// keep nothing. See `ooniauth-ffi/README.md` for the commands.
+///
+/// Returns NULL only if the response could not be allocated.
+///
+/// # Safety
+/// `request` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_call(request: *const c_char) -> *mut c_char {
+    let response = match str_arg(request, "request") {
+        Ok(request) => call(request),
+        Err(err) => json!({
+            "version": OONIAUTH_JSON_VERSION,
+            "error": { "code": "invalid_argument", "message": err.message },
+        })
+        .to_string(),
+    };
+    match CString::new(response) {
+        Ok(response) => response.into_raw(),
+        Err(_) => {
+            // serde_json escapes NUL bytes, so this is unreachable
+            set_last_error(Some("response contained a NUL byte".into()));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &str, params: Value) -> Result<Value, (String, String)> {
+        let request = json!({ "version": 1, "command": command, "params": params });
+        let response: Value = serde_json::from_str(&call(&request.to_string())).unwrap();
+        assert_eq!(response["version"], 1);
+        match response.get("error") {
+            Some(error) => Err((
+                error["code"].as_str().unwrap().to_owned(),
+                error["message"].as_str().unwrap().to_owned(),
+            )),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    fn code(result: Result<Value, (String, String)>) -> String {
+        result.expect_err("expected an error").0
+    }
+
+    #[test]
+    fn test_register_submit_update() {
+        let server = run("server_new", Value::Null).unwrap();
+        let user = run(
+            "user_new",
+            json!({ "public_parameters": server["public_parameters"] }),
+        )
+        .unwrap()["user"]
+            .clone();
+        assert_eq!(
+            run("user_credential_info", json!({ "user": user })).unwrap()["info"],
+            Value::Null
+        );
+
+        let registration = run("user_registration_request", json!({ "user": user })).unwrap();
+        let response = run(
+            "server_handle_registration",
+            json!({ "server": server["server"], "request": registration["request"] }),
+        )
+        .unwrap();
+        let user = run(
+            "user_handle_registration_response",
+            json!({
+                "user": user,
+                "pending": registration["pending"],
+                "response": response["response"],
+            }),
+        )
+        .unwrap()["user"]
+            .clone();
+
+        let today = run("today", Value::Null).unwrap()["today"]
+            .as_u64()
+            .unwrap();
+        let hash = run(
+            "submit_measurement_hash",
+            json!({ "measurement": BASE64_STANDARD.encode("measurement") }),
+        )
+        .unwrap()["hash"]
+            .clone();
+        let ranges = run(
+            "submit_policy_ranges",
+            json!({ "max_age_days": 30, "max_measurement_count": 100, "today": today }),
+        )
+        .unwrap();
+        assert_eq!(
+            ranges,
+            json!({
+                "age_range": [today - 30, today + 1],
+                "measurement_count_range": [0, 100],
+            })
+        );
+        let submission = run(
+            "user_submit_request",
+            json!({
+                "user": user,
+                "probe_cc": "VE",
+                "probe_asn": "AS1234",
+                "measurement_hash": hash,
+                "age_range": ranges["age_range"],
+                "measurement_count_range": ranges["measurement_count_range"],
+            }),
+        )
+        .unwrap();
+        let server_submit = |probe_cc: &str| {
+            run(
+                "server_handle_submit",
+                json!({
+                    "server": server["server"],
+                    "nym": submission["nym"],
+                    "request": submission["request"],
+                    "probe_cc": probe_cc,
+                    "probe_asn": "AS1234",
+                    "measurement_hash": hash,
+                    "age_range": ranges["age_range"],
+                    "measurement_count_range": ranges["measurement_count_range"],
+                }),
+            )
+        };
+        assert_eq!(code(server_submit("IT")), "proof_rejected");
+        let response = server_submit("VE").unwrap();
+        let outcome = &response["outcome"];
+        assert_eq!(outcome["probe_id"], submission["nym"]);
+        assert_eq!(outcome["measurement_hash"], hash);
+        assert_eq!(outcome["age_range"], ranges["age_range"]);
+        assert_eq!(
+            outcome["measurement_count_range"],
+            ranges["measurement_count_range"]
+        );
+        let user = run(
+            "user_handle_submit_response",
+            json!({
+                "user": user,
+                "pending": submission["pending"],
+                "response": response["response"],
+            }),
+        )
+        .unwrap()["user"]
+            .clone();
+        let info = run("user_credential_info", json!({ "user": user })).unwrap()["info"].clone();
+        assert_eq!(info["age"], today);
+        assert_eq!(info["measurement_count"], 1);
+
+        // Key rotation
+        let new_server = run("server_new", Value::Null).unwrap();
+        let user = run(
+            "user_set_public_parameters",
+            json!({ "user": user, "public_parameters": new_server["public_parameters"] }),
+        )
+        .unwrap()["user"]
+            .clone();
+        let update = run("user_update_request", json!({ "user": user })).unwrap();
+        let response = run(
+            "server_handle_update",
+            json!({
+                "server": new_server["server"],
+                "old_server": server["server"],
+                "request": update["request"],
+            }),
+        )
+        .unwrap();
+        let user = run(
+            "user_handle_update_response",
+            json!({
+                "user": user,
+                "pending": update["pending"],
+                "response": response["response"],
+            }),
+        )
+        .unwrap()["user"]
+            .clone();
+        let updated = run("user_credential_info", json!({ "user": user })).unwrap()["info"].clone();
+        assert_ne!(updated["key_id"], info["key_id"]);
+        assert_eq!(updated["measurement_count"], 1);
+
+        // Credential export and import
+        let credential =
+            run("user_credential", json!({ "user": user })).unwrap()["credential"].clone();
+        let other = run(
+            "user_new",
+            json!({ "public_parameters": new_server["public_parameters"] }),
+        )
+        .unwrap()["user"]
+            .clone();
+        let other = run(
+            "user_set_credential",
+            json!({ "user": other, "credential": credential }),
+        )
+        .unwrap()["user"]
+            .clone();
+        assert_eq!(
+            run("user_credential_info", json!({ "user": other })).unwrap()["info"],
+            updated
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let server = run("server_new", Value::Null).unwrap();
+        let user = run(
+            "user_new",
+            json!({ "public_parameters": server["public_parameters"] }),
+        )
+        .unwrap()["user"]
+            .clone();
+        assert_eq!(
+            code(run("user_credential", json!({ "user": user }))),
+            "missing_credential"
+        );
+        assert_eq!(
+            code(run("user_new", json!({ "public_parameters": "AAAA" }))),
+            "malformed_message"
+        );
+        assert_eq!(
+            code(run(
+                "user_new",
+                json!({ "public_parameters": "not base64" })
+            )),
+            "invalid_argument"
+        );
+        assert_eq!(code(run("user_new", json!({}))), "invalid_argument");
+        assert_eq!(
+            code(run("no_such_command", Value::Null)),
+            "invalid_argument"
+        );
+        let (code, message) = run(
+            "server_handle_submit",
+            json!({
+                "server": server["server"],
+                "nym": "AAAA",
+                "request": "",
+                "probe_cc": "VE",
+                "probe_asn": "AS1234",
+                "measurement_hash": "AAAA",
+                "age_range": [0, 1],
+                "measurement_count_range": [0, 1],
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(code, "invalid_argument");
+        assert_eq!(message, "nym is 3 bytes long, expected 32");
+
+        let response: Value =
+            serde_json::from_str(&call(r#"{"version": 2, "command": "today"}"#)).unwrap();
+        assert_eq!(response["version"], 1);
+        assert_eq!(response["error"]["code"], "invalid_argument");
+        assert_eq!(
+            response["error"]["message"],
+            "unsupported version 2, expected 1"
+        );
+        let response: Value = serde_json::from_str(&call("[")).unwrap();
+        assert_eq!(response["error"]["code"], "invalid_argument");
+    }
+
+    #[test]
+    fn test_ooniauth_call() {
+        unsafe {
+            let response = ooniauth_call(c"{\"version\": 1, \"command\": \"today\"}".as_ptr());
+            let json: Value =
+                serde_json::from_slice(std::ffi::CStr::from_ptr(response).to_bytes()).unwrap();
+            assert_eq!(json["result"]["today"], ServerState::today());
+            crate::ooniauth_string_free(response);
+
+            let response = ooniauth_call(std::ptr::null());
+            let json: Value =
+                serde_json::from_slice(std::ffi::CStr::from_ptr(response).to_bytes()).unwrap();
+            assert_eq!(json["error"]["message"], "request is NULL");
+            crate::ooniauth_string_free(response);
+        }
+    }
+}
@@ -1,29 +1,223 @@
-use std::ffi::{c_char, CString};
-use std::sync::Once;
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ops::Range;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::time::Instant;
 
+use cmz::CMZError;
+use ooniauth_core::errors::{CredentialError, ErrorCode, RangeError, WireError};
 use ooniauth_core::registration::UserAuthCredential;
-use ooniauth_core::submit::submit_measurement_hash;
+use ooniauth_core::submit::{check_submit_ranges, submit_measurement_hash};
+use ooniauth_core::wire::{Encoding, Framing};
 use ooniauth_core::{scalar_u32, ServerState, UserState};
 use tracing_forest::util::LevelFilter;
 use tracing_forest::ForestLayer;
 use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
-static TRACING_INIT: Once = Once::new();
+mod buffer;
+mod json;
+mod logging;
+mod server;
+mod user;
+
+pub use buffer::*;
+pub use json::*;
+pub use logging::*;
+pub use server::*;
+pub use user::*;
+
+thread_local! {
+    /// Message of the last failed call on this thread, see `ooniauth_last_error`
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Status codes returned by the C API. Values from 1 to 63 mirror
+/// [`ooniauth_core::errors::ErrorCode`] and keep its numbering, higher ones are
+/// specific to the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OoniauthStatus {
+    Ok = 0,
+    CredentialExpired = 1,
+    NotYetEligible = 2,
+    CountExceeded = 3,
+    BadDomain = 4,
+    UnknownPublicParameters = 5,
+    MalformedMessage = 6,
+    ProofRejected = 7,
+    InvalidField = 8,
+    CredentialMismatch = 9,
+    MissingCredential = 10,
+    Internal = 11,
+    /// A NULL pointer, a string that is not UTF-8 or an empty range was passed
+    InvalidArgument = 64,
+    /// The library panicked. The handles passed to the call may be left in an
+    /// inconsistent state and should be freed
+    Panic = 65,
+}
+
+impl From<ErrorCode> for OoniauthStatus {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::CredentialExpired => OoniauthStatus::CredentialExpired,
+            ErrorCode::NotYetEligible => OoniauthStatus::NotYetEligible,
+            ErrorCode::CountExceeded => OoniauthStatus::CountExceeded,
+            ErrorCode::BadDomain => OoniauthStatus::BadDomain,
+            ErrorCode::UnknownPublicParameters => OoniauthStatus::UnknownPublicParameters,
+            ErrorCode::MalformedMessage => OoniauthStatus::MalformedMessage,
+            ErrorCode::ProofRejected => OoniauthStatus::ProofRejected,
+            ErrorCode::InvalidField => OoniauthStatus::InvalidField,
+            ErrorCode::CredentialMismatch => OoniauthStatus::CredentialMismatch,
+            ErrorCode::MissingCredential => OoniauthStatus::MissingCredential,
+            ErrorCode::Internal => OoniauthStatus::Internal,
+        }
+    }
+}
+
+impl OoniauthStatus {
+    fn name(self) -> &'static CStr {
+        match self {
+            OoniauthStatus::Ok => c"ok",
+            OoniauthStatus::CredentialExpired => c"credential_expired",
+            OoniauthStatus::NotYetEligible => c"not_yet_eligible",
+            OoniauthStatus::CountExceeded => c"count_exceeded",
+            OoniauthStatus::BadDomain => c"bad_domain",
+            OoniauthStatus::UnknownPublicParameters => c"unknown_public_parameters",
+            OoniauthStatus::MalformedMessage => c"malformed_message",
+            OoniauthStatus::ProofRejected => c"proof_rejected",
+            OoniauthStatus::InvalidField => c"invalid_field",
+            OoniauthStatus::CredentialMismatch => c"credential_mismatch",
+            OoniauthStatus::MissingCredential => c"missing_credential",
+            OoniauthStatus::Internal => c"internal",
+            OoniauthStatus::InvalidArgument => c"invalid_argument",
+            OoniauthStatus::Panic => c"panic",
+        }
+    }
+}
+
+/// Why an exported function failed: its status and the message returned by
+/// `ooniauth_last_error`
+#[derive(Debug)]
+struct FfiError {
+    status: OoniauthStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn new(status: OoniauthStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(OoniauthStatus::InvalidArgument, message)
+    }
+
+    fn null_argument(name: &str) -> Self {
+        Self::invalid_argument(format!("{name} is NULL"))
+    }
 
-fn init_tracing() {
-    TRACING_INIT.call_once(|| {
-        let env_filter = EnvFilter::builder()
-            .with_default_directive(LevelFilter::INFO.into())
-            .from_env_lossy();
+    fn panic(payload: Box<dyn Any + Send>) -> Self {
+        let reason = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => (*message).to_owned(),
+                Err(_) => "unknown reason".to_owned(),
+            },
+        };
+        Self::new(OoniauthStatus::Panic, format!("panic: {reason}"))
+    }
+}
+
+impl From<CredentialError> for FfiError {
+    fn from(err: CredentialError) -> Self {
+        Self::new(err.code().into(), err.to_string())
+    }
+}
 
-        Registry::default()
-            .with(env_filter)
-            .with(ForestLayer::default())
-            .init();
-    });
+impl From<WireError> for FfiError {
+    fn from(err: WireError) -> Self {
+        Self::new(err.code().into(), err.to_string())
+    }
+}
+
+impl From<RangeError> for FfiError {
+    fn from(err: RangeError) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+
+impl From<CMZError> for FfiError {
+    fn from(err: CMZError) -> Self {
+        Self::new(ErrorCode::from(&err).into(), format!("{err:?}"))
+    }
+}
+
+fn set_last_error(message: Option<String>) {
+    let message = message
+        .map(|message| CString::new(message.replace('\0', " ")).expect("NUL bytes were replaced"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Runs the body of an exported function returning a status. Panics are caught
+/// here, since unwinding into C is undefined behaviour, and the message of any
+/// failure is kept for `ooniauth_last_error`
+fn ffi_call(f: impl FnOnce() -> Result<(), FfiError>) -> OoniauthStatus {
+    // The handles touched by a panicking call are reported as unusable through
+    // `OoniauthStatus::Panic`, so there is no unwind safety to preserve
+    let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| Err(FfiError::panic(e)));
+    match result {
+        Ok(()) => {
+            set_last_error(None);
+            OoniauthStatus::Ok
+        }
+        Err(err) => {
+            set_last_error(Some(err.message));
+            err.status
+        }
+    }
+}
+
+/// Same as [`ffi_call`] for the exported functions that can't return a status:
+/// on panic, `default` is returned instead
+fn catch_panic<T>(default: T, f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| {
+        set_last_error(Some(FfiError::panic(e).message));
+        default
+    })
+}
+
+/// Check the half-open submission ranges given to the submit functions
+fn submit_ranges(
+    age_start: u32,
+    age_end: u32,
+    min_measurement_count: u32,
+    max_measurement_count: u32,
+) -> Result<(Range<u32>, Range<u32>), FfiError> {
+    let ranges = (
+        age_start..age_end,
+        min_measurement_count..max_measurement_count,
+    );
+    check_submit_ranges(&ranges.0, &ranges.1)?;
+    Ok(ranges)
+}
+
+/// Prints the traces of the demo to stdout, unless they go to the log callback
+fn with_demo_tracing<T>(f: impl FnOnce() -> T) -> T {
+    if logging::has_callback() {
+        return f();
+    }
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    let subscriber = Registry::default()
+        .with(env_filter)
+        .with(ForestLayer::default());
+    tracing::subscriber::with_default(subscriber, f)
 }
 
 fn push_line(log: &mut String, line: &str) {
@@ -64,7 +258,6 @@ fn log_credential(log: &mut String, label: &str, cred: &UserAuthCredential) -> R
 }
 
 fn run_basic_usage_demo() -> Result<String, String> {
-    init_tracing();
     let mut log = String::new();
     push_line(&mut log, "=== Anonymous Credential Example ===");
     push_line(&mut log, "");
@@ -107,7 +300,7 @@ fn run_basic_usage_demo() -> Result<String, String> {
         ),
     );
 
-    let request_bytes = reg_request.as_bytes();
+    let request_bytes = user.encode_request(&reg_request);
     push_line(
         &mut log,
         &format!("   Request size: {} bytes", request_bytes.len()),
@@ -119,7 +312,7 @@ fn run_basic_usage_demo() -> Result<String, String> {
 
     let now = Instant::now();
     let reg_response = server
-        .open_registration(reg_request)
+        .open_registration(&mut rng, reg_request)
         .map_err(|e| format!("registration response failed: {e:?}"))?;
     push_line(
         &mut log,
@@ -129,7 +322,7 @@ fn run_basic_usage_demo() -> Result<String, String> {
         ),
     );
 
-    let response_bytes = reg_response.as_bytes();
+    let response_bytes = server.encode_reply(&reg_response, Framing::Envelope(Encoding::Bincode));
     push_line(
         &mut log,
         &format!("   Response size: {} bytes", response_bytes.len()),
@@ -182,7 +375,7 @@ fn run_basic_usage_demo() -> Result<String, String> {
     );
     push_line(&mut log, "   Domain-specific pseudonym computed");
     push_line(&mut log, &format!("   NYM (hex): {}", hex::encode(nym)));
-    let submit_request_bytes = submit_request.as_bytes();
+    let submit_request_bytes = user.encode_request(&submit_request);
     push_line(
         &mut log,
         &format!("   Request size: {} bytes", submit_request_bytes.len()),
@@ -208,7 +401,8 @@ fn run_basic_usage_demo() -> Result<String, String> {
             now.elapsed().as_millis()
         ),
     );
-    let submit_response_bytes = submit_response.as_bytes();
+    let submit_response_bytes =
+        server.encode_reply(&submit_response, Framing::Envelope(Encoding::Bincode));
     push_line(
         &mut log,
         &format!("   Response size: {} bytes", submit_response_bytes.len()),
@@ -304,16 +498,19 @@ fn run_basic_usage_demo() -> Result<String, String> {
     Ok(log)
 }
 
+/// Runs the demo flow and returns its log, or NULL if it panicked
 #[no_mangle]
 pub extern "C" fn ooniauth_run_basic_usage() -> *mut c_char {
-    let output = match run_basic_usage_demo() {
-        Ok(log) => log,
-        Err(err) => format!("error: {err}"),
-    };
-
-    CString::new(output)
-        .unwrap_or_else(|_| CString::new("error: output contained nul byte").unwrap())
-        .into_raw()
+    catch_panic(std::ptr::null_mut(), || {
+        let output = match with_demo_tracing(run_basic_usage_demo) {
+            Ok(log) => log,
+            Err(err) => format!("error: {err}"),
+        };
+
+        CString::new(output)
+            .unwrap_or_else(|_| CString::new("error: output contained nul byte").unwrap())
+            .into_raw()
+    })
 }
 
 /// # Safety
@@ -324,5 +521,219 @@ pub unsafe extern "C" fn ooniauth_string_free(ptr: *mut c_char) {
     if ptr.is_null() {
         return;
     }
-    drop(CString::from_raw(ptr));
+    catch_panic((), || drop(CString::from_raw(ptr)))
+}
+
+/// Returns the stable name of a status code, e.g. `"credential_expired"`, or NULL for
+/// unknown codes. The returned string is static and must not be freed.
+#[no_mangle]
+pub extern "C" fn ooniauth_status_name(status: u32) -> *const c_char {
+    let status = match status {
+        0 => OoniauthStatus::Ok,
+        64 => OoniauthStatus::InvalidArgument,
+        65 => OoniauthStatus::Panic,
+        code => match ErrorCode::try_from(code) {
+            Ok(code) => OoniauthStatus::from(code),
+            Err(_) => return std::ptr::null(),
+        },
+    };
+    status.name().as_ptr()
+}
+
+/// Returns the message of the last failed call made on this thread, or NULL if
+/// it succeeded. Calls returning an `OoniauthStatus` set or clear it, the other
+/// functions only set it when they panic.
+///
+/// The string belongs to the library and stays valid until the next call into
+/// the library on this thread. Copy it to keep it.
+#[no_mangle]
+pub extern "C" fn ooniauth_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+/// Hashes a measurement for `ooniauth_user_submit_request` and
+/// `ooniauth_server_handle_submit`, writing 32 bytes to `out`
+///
+/// # Safety
+/// `measurement` must be valid for reads of `measurement_len` bytes and `out`
+/// for writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_submit_measurement_hash(
+    measurement: *const u8,
+    measurement_len: usize,
+    out: *mut u8,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let measurement = buffer::bytes_arg(measurement, measurement_len, "measurement")?;
+        buffer::write_out(
+            out as *mut [u8; 32],
+            submit_measurement_hash(measurement),
+            "out",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ooniauth_core::bindings;
+
+    fn last_error() -> Option<String> {
+        let ptr = ooniauth_last_error();
+        (!ptr.is_null()).then(|| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    #[test]
+    fn test_ffi_call_catches_panics() {
+        assert_eq!(ffi_call(|| panic!("boom")), OoniauthStatus::Panic);
+        assert_eq!(last_error().as_deref(), Some("panic: boom"));
+
+        assert_eq!(ffi_call(|| Ok(())), OoniauthStatus::Ok);
+        assert_eq!(last_error(), None);
+
+        let value = 3;
+        assert_eq!(catch_panic(0, || panic!("value {value}")), 0);
+        assert_eq!(last_error().as_deref(), Some("panic: value 3"));
+        assert_eq!(
+            unsafe { CStr::from_ptr(ooniauth_status_name(65)) },
+            c"panic"
+        );
+    }
+
+    #[test]
+    fn test_errors_set_last_error() {
+        let mut buffer = OoniauthBuffer::EMPTY;
+        let status = unsafe { ooniauth_server_public_parameters(std::ptr::null(), &mut buffer) };
+        assert_eq!(status, OoniauthStatus::InvalidArgument);
+        assert_eq!(last_error().as_deref(), Some("server is NULL"));
+
+        let mut server = std::ptr::null_mut();
+        let status =
+            unsafe { ooniauth_server_from_creds(b"x".as_ptr(), 1, [].as_ptr(), 0, &mut server) };
+        assert_eq!(status, OoniauthStatus::MalformedMessage);
+        assert!(last_error().unwrap().starts_with("could not decode"));
+    }
+
+    #[test]
+    fn test_submit_policy_ranges() {
+        let mut ranges = OoniauthSubmitRanges {
+            age_start: 0,
+            age_end: 0,
+            min_measurement_count: 0,
+            max_measurement_count: 0,
+        };
+        let status =
+            unsafe { ooniauth_submit_policy_ranges(30, 2, std::ptr::null(), 100, &mut ranges) };
+        assert_eq!(status, OoniauthStatus::Ok);
+        assert_eq!((ranges.age_start, ranges.age_end), (70, 101));
+        assert_eq!(
+            (ranges.min_measurement_count, ranges.max_measurement_count),
+            (2, u32::MAX)
+        );
+
+        let status = unsafe { ooniauth_submit_policy_ranges(30, 2, &10, 100, &mut ranges) };
+        assert_eq!(status, OoniauthStatus::Ok);
+        assert_eq!(ranges.max_measurement_count, 10);
+
+        let status = unsafe { ooniauth_submit_policy_ranges(30, 2, &2, 100, &mut ranges) };
+        assert_eq!(status, OoniauthStatus::InvalidArgument);
+        let status =
+            unsafe { ooniauth_submit_policy_ranges(200, 0, std::ptr::null(), 100, &mut ranges) };
+        assert_eq!(status, OoniauthStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_panic_in_core_is_caught() {
+        unsafe {
+            let mut server = std::ptr::null_mut();
+            assert_eq!(ooniauth_server_new(&mut server), OoniauthStatus::Ok);
+            let mut pp = OoniauthBuffer::EMPTY;
+            assert_eq!(
+                ooniauth_server_public_parameters(server, &mut pp),
+                OoniauthStatus::Ok
+            );
+            let mut user = std::ptr::null_mut();
+            assert_eq!(
+                ooniauth_user_new(pp.data, pp.len, &mut user),
+                OoniauthStatus::Ok
+            );
+
+            let mut request = OoniauthBuffer::EMPTY;
+            let mut pending = std::ptr::null_mut();
+            assert_eq!(
+                ooniauth_user_registration_request(user, &mut request, &mut pending),
+                OoniauthStatus::Ok
+            );
+            let mut response = OoniauthBuffer::EMPTY;
+            assert_eq!(
+                ooniauth_server_handle_registration(
+                    server,
+                    request.data,
+                    request.len,
+                    &mut response
+                ),
+                OoniauthStatus::Ok
+            );
+            assert_eq!(
+                ooniauth_user_handle_registration_response(
+                    user,
+                    pending,
+                    response.data,
+                    response.len
+                ),
+                OoniauthStatus::Ok
+            );
+
+            // A credential without its nym_id makes the core unwrap a `None`
+            let mut credential = OoniauthBuffer::EMPTY;
+            assert_eq!(
+                ooniauth_user_get_credential(user, &mut credential),
+                OoniauthStatus::Ok
+            );
+            let mut cred: UserAuthCredential =
+                bindings::from_bytes(std::slice::from_raw_parts(credential.data, credential.len))
+                    .unwrap();
+            cred.nym_id = None;
+            let bytes = bindings::to_bytes(&cred);
+            assert_eq!(
+                ooniauth_user_set_credential(user, bytes.as_ptr(), bytes.len()),
+                OoniauthStatus::Ok
+            );
+
+            let hash = [0u8; 32];
+            let mut nym = [0u8; 32];
+            let mut submit_request = OoniauthBuffer::EMPTY;
+            let mut submit_pending = std::ptr::null_mut();
+            let today = ooniauth_today();
+            let status = ooniauth_user_submit_request(
+                user,
+                c"VE".as_ptr(),
+                c"AS1234".as_ptr(),
+                hash.as_ptr(),
+                today - 30,
+                today + 1,
+                0,
+                100,
+                nym.as_mut_ptr(),
+                &mut submit_request,
+                &mut submit_pending,
+            );
+            assert_eq!(status, OoniauthStatus::Panic);
+            assert!(last_error().unwrap().starts_with("panic: "));
+            assert!(submit_pending.is_null());
+
+            for buffer in [pp, request, response, credential] {
+                ooniauth_buffer_free(buffer);
+            }
+            ooniauth_user_free(user);
+            ooniauth_server_free(server);
+        }
+    }
 }
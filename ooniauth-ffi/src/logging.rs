@@ -0,0 +1,318 @@
+//! Opt-in forwarding of the `tracing` events and spans of the Rust code to a
+//! callback of the host application, see `ooniauth_set_log_callback`
+use std::ffi::{c_char, c_void, CString};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use ooniauth_core::logging::ForwardingLayer;
+use tracing::{Level, Metadata};
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::{ffi_call, FfiError, OoniauthStatus};
+
+/// Severity of a log record passed to an `OoniauthLogCallback`, and the values
+/// accepted by `ooniauth_set_log_level`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OoniauthLogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl From<&Level> for OoniauthLogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => OoniauthLogLevel::Error,
+            Level::WARN => OoniauthLogLevel::Warn,
+            Level::INFO => OoniauthLogLevel::Info,
+            Level::DEBUG => OoniauthLogLevel::Debug,
+            Level::TRACE => OoniauthLogLevel::Trace,
+        }
+    }
+}
+
+/// Receives the log records of the library. `target` is the Rust module the
+/// record comes from, e.g. `"ooniauth_core::submit"`. Both strings are only
+/// valid for the duration of the call, copy them to keep them.
+///
+/// It is called on the threads that call into the library, and must not call
+/// into the library itself.
+pub type OoniauthLogCallback = Option<
+    unsafe extern "C" fn(
+        level: OoniauthLogLevel,
+        target: *const c_char,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ),
+>;
+
+#[derive(Clone, Copy)]
+struct Callback {
+    function: unsafe extern "C" fn(OoniauthLogLevel, *const c_char, *const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// SAFETY: the caller of `ooniauth_set_log_callback` guarantees that the
+// callback and its `user_data` can be used from any thread
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+/// The registered callback. `None` while logging is disabled
+static CALLBACK: RwLock<Option<Callback>> = RwLock::new(None);
+
+/// Lowest severity forwarded, as an `OoniauthLogLevel`
+static LEVEL: AtomicU32 = AtomicU32::new(OoniauthLogLevel::Info as u32);
+
+/// Whether our subscriber could be installed as the global default
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+fn callback() -> Option<Callback> {
+    CALLBACK.read().ok().and_then(|callback| *callback)
+}
+
+/// Whether a callback is registered, in which case the demo logs to it too
+pub(crate) fn has_callback() -> bool {
+    callback().is_some()
+}
+
+fn is_enabled(metadata: &Metadata<'_>) -> bool {
+    OoniauthLogLevel::from(metadata.level()) as u32 <= LEVEL.load(Ordering::Relaxed)
+        && has_callback()
+}
+
+fn emit(metadata: &Metadata<'_>, message: String) {
+    let c_string = |s: &str| CString::new(s.replace('\0', " ")).expect("NUL bytes were replaced");
+    let target = c_string(metadata.target());
+    let message = c_string(&message);
+    // The read lock is held during the call, so that clearing or replacing the
+    // callback waits for the calls in flight and `user_data` can be freed
+    // right after
+    let Ok(current) = CALLBACK.read() else { return };
+    // The callback may have been cleared since `enabled` was asked
+    let Some(callback) = *current else { return };
+    // SAFETY: guaranteed by the caller of `ooniauth_set_log_callback`
+    unsafe {
+        (callback.function)(
+            metadata.level().into(),
+            target.as_ptr(),
+            message.as_ptr(),
+            callback.user_data,
+        )
+    }
+}
+
+/// Forwards the logs of the library to `callback`, replacing the previous one,
+/// or stops forwarding them if it is NULL. `user_data` is passed back to every
+/// call of `callback`.
+///
+/// Spans log their duration when they close, e.g. `handle_submit took 4.211ms`,
+/// and the events inside spans are prefixed with their names, e.g.
+/// `handle_submit: Submit request verified successfully`. Only records at the
+/// level set by `ooniauth_set_log_level`, `OONIAUTH_LOG_LEVEL_INFO` by default,
+/// or more severe are forwarded.
+///
+/// The previous callback is not called anymore once this function returns: it
+/// waits for the calls in flight, so its `user_data` can be freed right after.
+///
+/// Returns `OONIAUTH_STATUS_INTERNAL` if another `tracing` subscriber is
+/// already installed in the process.
+///
+/// # Safety
+/// `callback` must be safe to call from any thread with `user_data` until it is
+/// replaced, and must not unwind. It must not call into the library, which
+/// could deadlock: in particular it can't replace itself.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_set_log_callback(
+    callback: OoniauthLogCallback,
+    user_data: *mut c_void,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let callback = callback.map(|function| Callback {
+            function,
+            user_data,
+        });
+        if callback.is_some() {
+            // The subscriber is installed on first use and stays, clearing the
+            // callback only disables it
+            let installed = *INSTALLED.get_or_init(|| {
+                let subscriber =
+                    tracing_subscriber::registry().with(ForwardingLayer::new(is_enabled, emit));
+                tracing::subscriber::set_global_default(subscriber).is_ok()
+            });
+            if !installed {
+                return Err(FfiError::new(
+                    OoniauthStatus::Internal,
+                    "another tracing subscriber is already installed",
+                ));
+            }
+        }
+        if let Ok(mut current) = CALLBACK.write() {
+            *current = callback;
+        }
+        tracing::callsite::rebuild_interest_cache();
+        Ok(())
+    })
+}
+
+/// Sets the lowest severity forwarded to the log callback, one of the
+/// `OoniauthLogLevel` values
+#[no_mangle]
+pub extern "C" fn ooniauth_set_log_level(level: u32) -> OoniauthStatus {
+    ffi_call(|| {
+        let range = OoniauthLogLevel::Error as u32..=OoniauthLogLevel::Trace as u32;
+        if !range.contains(&level) {
+            return Err(FfiError::invalid_argument(format!(
+                "unknown log level {level}"
+            )));
+        }
+        LEVEL.store(level, Ordering::Relaxed);
+        tracing::callsite::rebuild_interest_cache();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use ooniauth_core::ServerState;
+
+    use super::*;
+
+    type Records = Mutex<Vec<(OoniauthLogLevel, String, String)>>;
+
+    /// The callback is global, so the tests setting it run one at a time
+    static CALLBACK_TESTS: Mutex<()> = Mutex::new(());
+
+    unsafe extern "C" fn record(
+        level: OoniauthLogLevel,
+        target: *const c_char,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ) {
+        let records = &*(user_data as *const Records);
+        let string = |s| CStr::from_ptr(s).to_string_lossy().into_owned();
+        records
+            .lock()
+            .unwrap()
+            .push((level, string(target), string(message)));
+    }
+
+    #[test]
+    fn test_forwards_to_callback() {
+        let _serial = CALLBACK_TESTS.lock().unwrap();
+        let records = Records::default();
+        let user_data = &records as *const Records as *mut c_void;
+        assert_eq!(ooniauth_set_log_level(0), OoniauthStatus::InvalidArgument);
+        assert_eq!(ooniauth_set_log_level(6), OoniauthStatus::InvalidArgument);
+        assert_eq!(
+            ooniauth_set_log_level(OoniauthLogLevel::Debug as u32),
+            OoniauthStatus::Ok
+        );
+        assert_eq!(
+            unsafe { ooniauth_set_log_callback(Some(record), user_data) },
+            OoniauthStatus::Ok
+        );
+
+        let rng = &mut rand::thread_rng();
+        let server = ServerState::new(rng);
+        let mut user = ooniauth_core::UserState::new(server.public_parameters());
+        let (request, state) = user.request(rng).unwrap();
+        let reply = server.open_registration(rng, request).unwrap();
+        user.handle_response(state, reply).unwrap();
+        let today = ServerState::today();
+        let hash = [0; 32];
+        let ((request, _), nym) = user
+            .submit_request(
+                rng,
+                "VE".into(),
+                "AS1234".into(),
+                &hash,
+                today - 30..today + 1,
+                0..u32::MAX,
+            )
+            .unwrap();
+        server
+            .handle_submit(
+                rng,
+                request,
+                &nym,
+                "VE",
+                "AS1234",
+                &hash,
+                today - 30..today + 1,
+                0..u32::MAX,
+            )
+            .unwrap();
+        assert_eq!(
+            unsafe { ooniauth_set_log_callback(None, std::ptr::null_mut()) },
+            OoniauthStatus::Ok
+        );
+        assert_eq!(
+            ooniauth_set_log_level(OoniauthLogLevel::Info as u32),
+            OoniauthStatus::Ok
+        );
+
+        let records = records.into_inner().unwrap();
+        let has = |level: OoniauthLogLevel, prefix: &str| {
+            records.iter().any(|(l, target, message)| {
+                *l == level && target == "ooniauth_core::submit" && message.starts_with(prefix)
+            })
+        };
+        assert!(has(
+            OoniauthLogLevel::Debug,
+            "handle_submit: Submit request verified successfully"
+        ));
+        assert!(has(OoniauthLogLevel::Info, "handle_submit took "));
+        assert!(has(OoniauthLogLevel::Info, "submit_request took "));
+        // Below the requested level
+        assert!(!records
+            .iter()
+            .any(|(level, _, _)| *level == OoniauthLogLevel::Trace));
+    }
+
+    static ENTERED: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" fn slow(
+        _level: OoniauthLogLevel,
+        _target: *const c_char,
+        _message: *const c_char,
+        user_data: *mut c_void,
+    ) {
+        let in_flight = &*(user_data as *const AtomicU32);
+        in_flight.fetch_add(1, Ordering::SeqCst);
+        ENTERED.store(true, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(100));
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_clearing_waits_for_calls_in_flight() {
+        let _serial = CALLBACK_TESTS.lock().unwrap();
+        let in_flight = AtomicU32::new(0);
+        let user_data = &in_flight as *const AtomicU32 as *mut c_void;
+        assert_eq!(
+            unsafe { ooniauth_set_log_callback(Some(slow), user_data) },
+            OoniauthStatus::Ok
+        );
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| tracing::info!("logged from another thread"));
+            while !ENTERED.load(Ordering::SeqCst) {
+                std::thread::yield_now();
+            }
+            assert_eq!(
+                unsafe { ooniauth_set_log_callback(None, std::ptr::null_mut()) },
+                OoniauthStatus::Ok
+            );
+            // `user_data` could be freed here
+            assert_eq!(in_flight.load(Ordering::SeqCst), 0);
+        });
+    }
+}
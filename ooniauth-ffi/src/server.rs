@@ -0,0 +1,389 @@
+//! Server side of the C API
+use std::ffi::c_char;
+
+use ooniauth_core::bindings;
+use ooniauth_core::registration::open_registration;
+use ooniauth_core::submit::{SubmitOutcome, SubmitPolicy, SubmitRequest};
+use ooniauth_core::update::update;
+use ooniauth_core::{PublicParameters, SecretKey, ServerState};
+
+use crate::buffer::{
+    array_32_arg, bytes_arg, check_out, free_handle, handle_ref, str_arg, write_out,
+};
+use crate::{ffi_call, submit_ranges, FfiError, OoniauthBuffer, OoniauthStatus};
+
+/// Opaque server state, holding the issuer keys
+pub struct OoniauthServerState(ServerState);
+
+/// Creates a server state with fresh keys
+///
+/// # Safety
+/// `out` must be valid for writes. Free the handle with `ooniauth_server_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_new(out: *mut *mut OoniauthServerState) -> OoniauthStatus {
+    ffi_call(|| {
+        let mut rng = rand::thread_rng();
+        let server = OoniauthServerState(ServerState::new(&mut rng));
+        write_out(out, Box::into_raw(Box::new(server)), "out")
+    })
+}
+
+/// Restores a server state from the keys returned by
+/// `ooniauth_server_public_parameters` and `ooniauth_server_secret_key`
+///
+/// # Safety
+/// The byte arguments must be valid for reads of their length, and `out` valid
+/// for writes. Free the handle with `ooniauth_server_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_from_creds(
+    public_parameters: *const u8,
+    public_parameters_len: usize,
+    secret_key: *const u8,
+    secret_key_len: usize,
+    out: *mut *mut OoniauthServerState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let pp: PublicParameters = bindings::from_bytes(bytes_arg(
+            public_parameters,
+            public_parameters_len,
+            "public_parameters",
+        )?)?;
+        let sk: SecretKey =
+            bindings::from_bytes(bytes_arg(secret_key, secret_key_len, "secret_key")?)?;
+        let server = OoniauthServerState(ServerState::from_creds(sk, pp));
+        write_out(out, Box::into_raw(Box::new(server)), "out")
+    })
+}
+
+/// Releases a server state. NULL is ignored
+///
+/// # Safety
+/// `server` must come from the library and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_free(server: *mut OoniauthServerState) {
+    free_handle(server);
+}
+
+/// The public parameters of the server, to hand out to clients
+///
+/// # Safety
+/// `server` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_public_parameters(
+    server: *const OoniauthServerState,
+    out: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let server = handle_ref(server, "server")?;
+        let bytes = bindings::to_bytes(server.0.public_parameters_ref());
+        write_out(out, OoniauthBuffer::from_vec(bytes), "out")
+    })
+}
+
+/// The secret key of the server, to store along with the public parameters
+///
+/// # Safety
+/// `server` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_secret_key(
+    server: *const OoniauthServerState,
+    out: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let server = handle_ref(server, "server")?;
+        let bytes = bindings::to_bytes(server.0.secret_key_ref());
+        write_out(out, OoniauthBuffer::from_vec(bytes), "out")
+    })
+}
+
+/// Handles a registration request, writing the response to `response`
+///
+/// # Safety
+/// `server` must be a valid handle, `request` valid for reads of `request_len`
+/// bytes and `response` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_handle_registration(
+    server: *const OoniauthServerState,
+    request: *const u8,
+    request_len: usize,
+    response: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let server = &handle_ref(server, "server")?.0;
+        let (request, framing) = server.decode_request::<open_registration::Request>(bytes_arg(
+            request,
+            request_len,
+            "request",
+        )?)?;
+        let mut rng = rand::thread_rng();
+        let reply = server.open_registration(&mut rng, request)?;
+        write_out(
+            response,
+            OoniauthBuffer::from_vec(server.encode_reply(&reply, framing)),
+            "response",
+        )
+    })
+}
+
+/// Handles a submit request, writing the response to `response`
+///
+/// `nym` and `measurement_hash` are 32 bytes long. The credential must have
+/// been registered in `[age_start, age_end)` and hold a measurement count in
+/// `[min_measurement_count, max_measurement_count)`, with the same bounds the
+/// client used.
+///
+/// # Safety
+/// `server` must be a valid handle, `nym` and `measurement_hash` valid for
+/// reads of 32 bytes, `request` for reads of `request_len` bytes, `probe_cc`
+/// and `probe_asn` NUL-terminated strings and `response` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_handle_submit(
+    server: *const OoniauthServerState,
+    nym: *const u8,
+    request: *const u8,
+    request_len: usize,
+    probe_cc: *const c_char,
+    probe_asn: *const c_char,
+    measurement_hash: *const u8,
+    age_start: u32,
+    age_end: u32,
+    min_measurement_count: u32,
+    max_measurement_count: u32,
+    response: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        check_out(response, "response")?;
+        let (reply, _) = handle_submit(
+            server,
+            nym,
+            request,
+            request_len,
+            probe_cc,
+            probe_asn,
+            measurement_hash,
+            (age_start, age_end),
+            (min_measurement_count, max_measurement_count),
+        )?;
+        write_out(response, OoniauthBuffer::from_vec(reply), "response")
+    })
+}
+
+/// What the server verified when accepting a submission, to store next to the
+/// measurement. The domain is the `probe_cc` and `probe_asn` of the call.
+#[repr(C)]
+#[derive(Debug)]
+pub struct OoniauthSubmitOutcome {
+    pub probe_id: [u8; 32],
+    pub measurement_hash: [u8; 32],
+    /// The credential was registered in `[age_start, age_end)`
+    pub age_start: u32,
+    pub age_end: u32,
+    /// The credential had a measurement count in
+    /// `[min_measurement_count, max_measurement_count)`
+    pub min_measurement_count: u32,
+    pub max_measurement_count: u32,
+    /// The proof session ID, derived from the measurement hash
+    pub session_id: [u8; 32],
+    /// The key ID of the keys that verified the credential
+    pub key_id: [u8; 8],
+}
+
+impl From<SubmitOutcome> for OoniauthSubmitOutcome {
+    fn from(outcome: SubmitOutcome) -> Self {
+        Self {
+            probe_id: outcome.probe_id,
+            measurement_hash: outcome.measurement_hash,
+            age_start: outcome.age_range.start,
+            age_end: outcome.age_range.end,
+            min_measurement_count: outcome.measurement_count_range.start,
+            max_measurement_count: outcome.measurement_count_range.end,
+            session_id: outcome.session_id,
+            key_id: outcome.key_id,
+        }
+    }
+}
+
+/// Same as `ooniauth_server_handle_submit`, also writing what was verified to
+/// `outcome`
+///
+/// # Safety
+/// As for `ooniauth_server_handle_submit`, and `outcome` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_handle_submit_with_outcome(
+    server: *const OoniauthServerState,
+    nym: *const u8,
+    request: *const u8,
+    request_len: usize,
+    probe_cc: *const c_char,
+    probe_asn: *const c_char,
+    measurement_hash: *const u8,
+    age_start: u32,
+    age_end: u32,
+    min_measurement_count: u32,
+    max_measurement_count: u32,
+    response: *mut OoniauthBuffer,
+    outcome: *mut OoniauthSubmitOutcome,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        check_out(response, "response")?;
+        check_out(outcome, "outcome")?;
+        let (reply, verified) = handle_submit(
+            server,
+            nym,
+            request,
+            request_len,
+            probe_cc,
+            probe_asn,
+            measurement_hash,
+            (age_start, age_end),
+            (min_measurement_count, max_measurement_count),
+        )?;
+        write_out(outcome, verified.into(), "outcome")?;
+        write_out(response, OoniauthBuffer::from_vec(reply), "response")
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn handle_submit(
+    server: *const OoniauthServerState,
+    nym: *const u8,
+    request: *const u8,
+    request_len: usize,
+    probe_cc: *const c_char,
+    probe_asn: *const c_char,
+    measurement_hash: *const u8,
+    (age_start, age_end): (u32, u32),
+    (min_measurement_count, max_measurement_count): (u32, u32),
+) -> Result<(Vec<u8>, SubmitOutcome), FfiError> {
+    let server = &handle_ref(server, "server")?.0;
+    let nym = array_32_arg(nym, "nym")?;
+    let measurement_hash = array_32_arg(measurement_hash, "measurement_hash")?;
+    let probe_cc = str_arg(probe_cc, "probe_cc")?;
+    let probe_asn = str_arg(probe_asn, "probe_asn")?;
+    let (age_range, measurement_count_range) = submit_ranges(
+        age_start,
+        age_end,
+        min_measurement_count,
+        max_measurement_count,
+    )?;
+
+    let (request, framing) =
+        server.decode_request::<SubmitRequest>(bytes_arg(request, request_len, "request")?)?;
+    let mut rng = rand::thread_rng();
+    let (reply, outcome) = server.handle_submit_with_outcome(
+        &mut rng,
+        request,
+        &nym,
+        probe_cc,
+        probe_asn,
+        &measurement_hash,
+        age_range,
+        measurement_count_range,
+    )?;
+    Ok((server.encode_reply(&reply, framing), outcome))
+}
+
+/// Handles a credential update request, moving a credential issued under the
+/// old keys to the keys of `server`
+///
+/// # Safety
+/// `server` must be a valid handle, the byte arguments valid for reads of
+/// their length and `response` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_server_handle_update(
+    server: *const OoniauthServerState,
+    request: *const u8,
+    request_len: usize,
+    old_public_parameters: *const u8,
+    old_public_parameters_len: usize,
+    old_secret_key: *const u8,
+    old_secret_key_len: usize,
+    response: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let server = &handle_ref(server, "server")?.0;
+        let old_pp: PublicParameters = bindings::from_bytes(bytes_arg(
+            old_public_parameters,
+            old_public_parameters_len,
+            "old_public_parameters",
+        )?)?;
+        let old_sk: SecretKey = bindings::from_bytes(bytes_arg(
+            old_secret_key,
+            old_secret_key_len,
+            "old_secret_key",
+        )?)?;
+
+        let (request, framing) = server.decode_request::<update::Request>(bytes_arg(
+            request,
+            request_len,
+            "request",
+        )?)?;
+        let mut rng = rand::thread_rng();
+        let reply = server.handle_update(&mut rng, request, &old_sk, &old_pp)?;
+        write_out(
+            response,
+            OoniauthBuffer::from_vec(server.encode_reply(&reply, framing)),
+            "response",
+        )
+    })
+}
+
+/// Today as a Julian day, the unit of the age ranges
+#[no_mangle]
+pub extern "C" fn ooniauth_today() -> u32 {
+    ServerState::today()
+}
+
+/// The half-open ranges a credential must fall in to submit a measurement, as
+/// passed to `ooniauth_user_submit_request` and `ooniauth_server_handle_submit`
+#[repr(C)]
+#[derive(Debug)]
+pub struct OoniauthSubmitRanges {
+    /// Registration days, as Julian days
+    pub age_start: u32,
+    pub age_end: u32,
+    /// Measurements submitted with the credential so far
+    pub min_measurement_count: u32,
+    pub max_measurement_count: u32,
+}
+
+/// Writes to `out` the ranges a submit policy accepts on `today`: credentials
+/// registered at most `max_age_days` ago, with at least `min_measurement_count`
+/// and fewer than `*max_measurement_count` measurements (no upper bound when
+/// it is NULL). This is the `SubmitPolicy` of the other bindings.
+///
+/// Fails with `OONIAUTH_STATUS_INVALID_ARGUMENT` when no credential can
+/// satisfy the policy.
+///
+/// # Safety
+/// `max_measurement_count` must be NULL or valid for reads, and `out` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_submit_policy_ranges(
+    max_age_days: u32,
+    min_measurement_count: u32,
+    max_measurement_count: *const u32,
+    today: u32,
+    out: *mut OoniauthSubmitRanges,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        check_out(out, "out")?;
+        let policy = SubmitPolicy::new(
+            max_age_days,
+            min_measurement_count,
+            max_measurement_count.as_ref().copied(),
+        )?;
+        let (age, count) = policy.ranges(today)?;
+        write_out(
+            out,
+            OoniauthSubmitRanges {
+                age_start: age.start,
+                age_end: age.end,
+                min_measurement_count: count.start,
+                max_measurement_count: count.end,
+            },
+            "out",
+        )
+    })
+}
@@ -0,0 +1,392 @@
+//! Client side of the C API
+//!
+//! Each request function returns the bytes to send along with a pending state
+//! handle, which the matching response function releases once the response is
+//! handled. A response that can't be handled leaves it pending, to be passed
+//! again with another response or released with its `_free` function. Several
+//! requests may be pending at once.
+use std::ffi::c_char;
+
+use ooniauth_core::bindings;
+use ooniauth_core::errors::CredentialError;
+use ooniauth_core::registration::{open_registration, UserAuthCredential};
+use ooniauth_core::submit::submit;
+use ooniauth_core::update::update;
+use ooniauth_core::{PublicParameters, UserState};
+
+use crate::buffer::{
+    array_32_arg, bytes_arg, check_out, free_handle, handle_mut, handle_ref, str_arg, write_out,
+};
+use crate::{ffi_call, submit_ranges, OoniauthBuffer, OoniauthStatus};
+
+/// Opaque client state, holding the server public parameters and the credential
+pub struct OoniauthUserState(UserState);
+
+/// Pending registration, from `ooniauth_user_registration_request`
+pub struct OoniauthRegistrationState(open_registration::ClientState);
+
+/// Pending submission, from `ooniauth_user_submit_request`
+pub struct OoniauthSubmitState(submit::ClientState);
+
+/// Pending credential update, from `ooniauth_user_update_request`
+pub struct OoniauthUpdateState(update::ClientState);
+
+/// Creates a client state without a credential for the given public parameters
+///
+/// # Safety
+/// `public_parameters` must be valid for reads of `public_parameters_len` bytes
+/// and `out` valid for writes. Free the handle with `ooniauth_user_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_new(
+    public_parameters: *const u8,
+    public_parameters_len: usize,
+    out: *mut *mut OoniauthUserState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let pp: PublicParameters = bindings::from_bytes(bytes_arg(
+            public_parameters,
+            public_parameters_len,
+            "public_parameters",
+        )?)?;
+        let user = OoniauthUserState(UserState::new(pp));
+        write_out(out, Box::into_raw(Box::new(user)), "out")
+    })
+}
+
+/// Releases a client state. NULL is ignored
+///
+/// # Safety
+/// `user` must come from the library and be freed only once.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_free(user: *mut OoniauthUserState) {
+    free_handle(user);
+}
+
+/// Exports the whole client state, to be restored with `ooniauth_user_from_bytes`
+///
+/// # Safety
+/// `user` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_to_bytes(
+    user: *const OoniauthUserState,
+    out: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = handle_ref(user, "user")?;
+        write_out(
+            out,
+            OoniauthBuffer::from_vec(bindings::to_bytes(&user.0)),
+            "out",
+        )
+    })
+}
+
+/// # Safety
+/// `data` must be valid for reads of `len` bytes and `out` valid for writes.
+/// Free the handle with `ooniauth_user_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut OoniauthUserState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = OoniauthUserState(bindings::from_bytes(bytes_arg(data, len, "data")?)?);
+        write_out(out, Box::into_raw(Box::new(user)), "out")
+    })
+}
+
+/// Exports the credential. Fails with `MissingCredential` before registration
+///
+/// # Safety
+/// `user` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_get_credential(
+    user: *const OoniauthUserState,
+    out: *mut OoniauthBuffer,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let credential = handle_ref(user, "user")?
+            .0
+            .get_credential()
+            .ok_or(CredentialError::MissingCredential)?;
+        write_out(
+            out,
+            OoniauthBuffer::from_vec(bindings::to_bytes(credential)),
+            "out",
+        )
+    })
+}
+
+/// Imports a credential exported by `ooniauth_user_get_credential`, replacing
+/// the current one
+///
+/// # Safety
+/// `user` must be a valid handle and `data` valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_set_credential(
+    user: *mut OoniauthUserState,
+    data: *const u8,
+    len: usize,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = handle_mut(user, "user")?;
+        let credential: UserAuthCredential = bindings::from_bytes(bytes_arg(data, len, "data")?)?;
+        user.0.set_credential(credential);
+        Ok(())
+    })
+}
+
+/// Replaces the public parameters, after the server rotated its keys. Follow
+/// with a credential update
+///
+/// # Safety
+/// `user` must be a valid handle and `public_parameters` valid for reads of
+/// `public_parameters_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_set_public_parameters(
+    user: *mut OoniauthUserState,
+    public_parameters: *const u8,
+    public_parameters_len: usize,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = handle_mut(user, "user")?;
+        user.0.pp = bindings::from_bytes(bytes_arg(
+            public_parameters,
+            public_parameters_len,
+            "public_parameters",
+        )?)?;
+        Ok(())
+    })
+}
+
+/// Makes a registration request. Send `request` to the server, and pass
+/// `pending` along with its response to `ooniauth_user_handle_registration_response`
+///
+/// # Safety
+/// `user` must be a valid handle, `request` and `pending` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_registration_request(
+    user: *const OoniauthUserState,
+    request: *mut OoniauthBuffer,
+    pending: *mut *mut OoniauthRegistrationState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = &handle_ref(user, "user")?.0;
+        check_out(request, "request")?;
+        check_out(pending, "pending")?;
+        let mut rng = rand::thread_rng();
+        let (req, state) = user.request(&mut rng)?;
+        write_out(
+            request,
+            OoniauthBuffer::from_vec(user.encode_request(&req)),
+            "request",
+        )?;
+        write_out(
+            pending,
+            Box::into_raw(Box::new(OoniauthRegistrationState(state))),
+            "pending",
+        )
+    })
+}
+
+/// Stores the credential issued by the server. Releases `pending` on success, it
+/// is left for `ooniauth_registration_state_free` or another response on error
+///
+/// # Safety
+/// `user` and `pending` must be valid handles, and `response` valid for reads
+/// of `response_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_handle_registration_response(
+    user: *mut OoniauthUserState,
+    pending: *mut OoniauthRegistrationState,
+    response: *const u8,
+    response_len: usize,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let state = handle_mut(pending, "pending")?.0.clone();
+        let user = &mut handle_mut(user, "user")?.0;
+        let reply = user.decode_reply::<open_registration::Reply>(bytes_arg(
+            response,
+            response_len,
+            "response",
+        )?)?;
+        user.handle_response(state, reply)?;
+        finish_pending(pending);
+        Ok(())
+    })
+}
+
+/// Releases a registration that will not be completed. NULL is ignored
+///
+/// # Safety
+/// `pending` must come from the library and not have been released.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_registration_state_free(pending: *mut OoniauthRegistrationState) {
+    free_handle(pending);
+}
+
+/// Makes a submit request for a measurement. Send `nym` (32 bytes) and
+/// `request` to the server, and pass `pending` along with its response to
+/// `ooniauth_user_handle_submit_response`
+///
+/// The ranges are half-open and must match the ones of the server.
+///
+/// # Safety
+/// `user` must be a valid handle, `probe_cc` and `probe_asn` NUL-terminated
+/// strings, `measurement_hash` valid for reads of 32 bytes, `nym` for writes of
+/// 32 bytes and `request` and `pending` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_submit_request(
+    user: *const OoniauthUserState,
+    probe_cc: *const c_char,
+    probe_asn: *const c_char,
+    measurement_hash: *const u8,
+    age_start: u32,
+    age_end: u32,
+    min_measurement_count: u32,
+    max_measurement_count: u32,
+    nym: *mut u8,
+    request: *mut OoniauthBuffer,
+    pending: *mut *mut OoniauthSubmitState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = &handle_ref(user, "user")?.0;
+        let probe_cc = str_arg(probe_cc, "probe_cc")?;
+        let probe_asn = str_arg(probe_asn, "probe_asn")?;
+        let measurement_hash = array_32_arg(measurement_hash, "measurement_hash")?;
+        check_out(nym, "nym")?;
+        check_out(request, "request")?;
+        check_out(pending, "pending")?;
+        let (age_range, measurement_count_range) = submit_ranges(
+            age_start,
+            age_end,
+            min_measurement_count,
+            max_measurement_count,
+        )?;
+
+        let mut rng = rand::thread_rng();
+        let ((req, state), nym_bytes) = user.submit_request(
+            &mut rng,
+            probe_cc.to_owned(),
+            probe_asn.to_owned(),
+            &measurement_hash,
+            age_range,
+            measurement_count_range,
+        )?;
+        write_out(nym as *mut [u8; 32], nym_bytes, "nym")?;
+        write_out(
+            request,
+            OoniauthBuffer::from_vec(user.encode_request(&req)),
+            "request",
+        )?;
+        write_out(
+            pending,
+            Box::into_raw(Box::new(OoniauthSubmitState(state))),
+            "pending",
+        )
+    })
+}
+
+/// Replaces the credential with the one issued by the server in response to a
+/// submission. Releases `pending` on success, it is left for
+/// `ooniauth_submit_state_free` or another response on error
+///
+/// # Safety
+/// `user` and `pending` must be valid handles, and `response` valid for reads
+/// of `response_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_handle_submit_response(
+    user: *mut OoniauthUserState,
+    pending: *mut OoniauthSubmitState,
+    response: *const u8,
+    response_len: usize,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let state = handle_mut(pending, "pending")?.0.clone();
+        let user = &mut handle_mut(user, "user")?.0;
+        let reply =
+            user.decode_reply::<submit::Reply>(bytes_arg(response, response_len, "response")?)?;
+        user.handle_submit_response(state, reply)?;
+        finish_pending(pending);
+        Ok(())
+    })
+}
+
+/// Releases a submission that will not be completed. NULL is ignored
+///
+/// # Safety
+/// `pending` must come from the library and not have been released.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_submit_state_free(pending: *mut OoniauthSubmitState) {
+    free_handle(pending);
+}
+
+/// Makes a request to move the credential to the current public parameters.
+/// Send `request` to the server, and pass `pending` along with its response
+/// to `ooniauth_user_handle_update_response`
+///
+/// # Safety
+/// `user` must be a valid handle, `request` and `pending` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_update_request(
+    user: *const OoniauthUserState,
+    request: *mut OoniauthBuffer,
+    pending: *mut *mut OoniauthUpdateState,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let user = &handle_ref(user, "user")?.0;
+        check_out(request, "request")?;
+        check_out(pending, "pending")?;
+        let mut rng = rand::thread_rng();
+        let (req, state) = user.update_request(&mut rng)?;
+        write_out(
+            request,
+            OoniauthBuffer::from_vec(user.encode_request(&req)),
+            "request",
+        )?;
+        write_out(
+            pending,
+            Box::into_raw(Box::new(OoniauthUpdateState(state))),
+            "pending",
+        )
+    })
+}
+
+/// Stores the updated credential. Releases `pending` on success, it is left for
+/// `ooniauth_update_state_free` or another response on error
+///
+/// # Safety
+/// `user` and `pending` must be valid handles, and `response` valid for reads
+/// of `response_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_user_handle_update_response(
+    user: *mut OoniauthUserState,
+    pending: *mut OoniauthUpdateState,
+    response: *const u8,
+    response_len: usize,
+) -> OoniauthStatus {
+    ffi_call(|| {
+        let state = handle_mut(pending, "pending")?.0.clone();
+        let user = &mut handle_mut(user, "user")?.0;
+        let reply =
+            user.decode_reply::<update::Reply>(bytes_arg(response, response_len, "response")?)?;
+        user.handle_update_response(state, reply)?;
+        finish_pending(pending);
+        Ok(())
+    })
+}
+
+/// Releases a credential update that will not be completed. NULL is ignored
+///
+/// # Safety
+/// `pending` must come from the library and not have been released.
+#[no_mangle]
+pub unsafe extern "C" fn ooniauth_update_state_free(pending: *mut OoniauthUpdateState) {
+    free_handle(pending);
+}
+
+/// Releases a pending state once its response was handled
+unsafe fn finish_pending<T>(pending: *mut T) {
+    drop(Box::from_raw(pending));
+}
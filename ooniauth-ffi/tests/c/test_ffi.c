@@ -0,0 +1,230 @@
+/* Drives both sides of the protocol through the C API. Built and run by
+ * tests/c_api.rs, exits non-zero on the first failed check. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "ooniauth_ffi.h"
+
+#define CHECK(cond)                                                    \
+    do {                                                               \
+        if (!(cond)) {                                                 \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,     \
+                    __LINE__, #cond);                                  \
+            exit(1);                                                   \
+        }                                                              \
+    } while (0)
+
+#define CHECK_STATUS(call, expected)                                   \
+    do {                                                               \
+        OoniauthStatus status_ = (call);                               \
+        if (status_ != (expected)) {                                   \
+            fprintf(stderr, "%s:%d: %s returned %s\n", __FILE__,       \
+                    __LINE__, #call, ooniauth_status_name(status_));   \
+            exit(1);                                                   \
+        }                                                              \
+    } while (0)
+
+#define CHECK_OK(call) CHECK_STATUS(call, OONIAUTH_STATUS_OK)
+
+static void registration(OoniauthServerState *server, OoniauthUserState *user)
+{
+    OoniauthBuffer request = {0}, response = {0};
+    OoniauthRegistrationState *pending = NULL;
+
+    CHECK_OK(ooniauth_user_registration_request(user, &request, &pending));
+    CHECK_OK(ooniauth_server_handle_registration(server, request.data,
+                                                 request.len, &response));
+    /* A bad response leaves the registration pending */
+    CHECK_STATUS(ooniauth_user_handle_registration_response(
+                     user, pending, (const uint8_t *)"garbage", 7),
+                 OONIAUTH_STATUS_MALFORMED_MESSAGE);
+    CHECK_OK(ooniauth_user_handle_registration_response(
+        user, pending, response.data, response.len));
+    ooniauth_buffer_free(request);
+    ooniauth_buffer_free(response);
+}
+
+static OoniauthStatus submit(OoniauthServerState *server,
+                             OoniauthUserState *user, const char *probe_cc,
+                             const char *server_probe_cc)
+{
+    const char *measurement = "{\"test_name\": \"web_connectivity\"}";
+    uint8_t hash[32], nym[32];
+    uint32_t today = ooniauth_today();
+    OoniauthBuffer request = {0}, response = {0};
+    OoniauthSubmitState *pending = NULL;
+    OoniauthSubmitOutcome outcome;
+    OoniauthStatus status;
+
+    CHECK_OK(ooniauth_submit_measurement_hash(
+        (const uint8_t *)measurement, strlen(measurement), hash));
+    status = ooniauth_user_submit_request(user, probe_cc, "AS1234", hash,
+                                          today - 30, today + 1, 0, 100, nym,
+                                          &request, &pending);
+    if (status != OONIAUTH_STATUS_OK)
+        return status;
+
+    status = ooniauth_server_handle_submit_with_outcome(
+        server, nym, request.data, request.len, server_probe_cc, "AS1234",
+        hash, today - 30, today + 1, 0, 100, &response, &outcome);
+    ooniauth_buffer_free(request);
+    if (status != OONIAUTH_STATUS_OK) {
+        ooniauth_submit_state_free(pending);
+        return status;
+    }
+    CHECK(memcmp(outcome.probe_id, nym, 32) == 0);
+    CHECK(memcmp(outcome.measurement_hash, hash, 32) == 0);
+    CHECK(outcome.age_start == today - 30 && outcome.age_end == today + 1);
+    CHECK(outcome.min_measurement_count == 0 &&
+          outcome.max_measurement_count == 100);
+
+    status = ooniauth_user_handle_submit_response(user, pending, response.data,
+                                                  response.len);
+    ooniauth_buffer_free(response);
+    if (status != OONIAUTH_STATUS_OK)
+        ooniauth_submit_state_free(pending);
+    return status;
+}
+
+struct log_records {
+    int submit_spans;
+    int below_level;
+};
+
+static void log_record(OoniauthLogLevel level, const char *target,
+                       const char *message, void *user_data)
+{
+    struct log_records *records = user_data;
+
+    if (level > OONIAUTH_LOG_LEVEL_INFO)
+        records->below_level++;
+    if (strcmp(target, "ooniauth_core::submit") == 0 &&
+        strncmp(message, "handle_submit took ", 19) == 0)
+        records->submit_spans++;
+}
+
+int main(void)
+{
+    struct log_records records = {0};
+    char *json = NULL;
+    OoniauthServerState *server = NULL, *rotated = NULL, *restored = NULL;
+    OoniauthUserState *user = NULL, *copy = NULL;
+    OoniauthBuffer pp = {0}, sk = {0}, new_pp = {0}, credential = {0};
+    OoniauthBuffer state = {0}, request = {0}, response = {0};
+    OoniauthUpdateState *update = NULL;
+
+    CHECK(strcmp(ooniauth_status_name(OONIAUTH_STATUS_BAD_DOMAIN),
+                 "bad_domain") == 0);
+    CHECK(strcmp(ooniauth_status_name(OONIAUTH_STATUS_INVALID_ARGUMENT),
+                 "invalid_argument") == 0);
+    CHECK(ooniauth_status_name(1000) == NULL);
+
+    CHECK_OK(ooniauth_server_new(&server));
+    CHECK_OK(ooniauth_server_public_parameters(server, &pp));
+    CHECK_OK(ooniauth_server_secret_key(server, &sk));
+    CHECK_OK(ooniauth_user_new(pp.data, pp.len, &user));
+
+    /* No credential yet */
+    CHECK_STATUS(ooniauth_user_get_credential(user, &credential),
+                 OONIAUTH_STATUS_MISSING_CREDENTIAL);
+    CHECK_STATUS(submit(server, user, "VE", "VE"),
+                 OONIAUTH_STATUS_MISSING_CREDENTIAL);
+
+    registration(server, user);
+    CHECK_OK(submit(server, user, "VE", "VE"));
+
+    /* The JSON API reports errors in the response */
+    json = ooniauth_call("{\"version\": 1, \"command\": \"user_credential\", "
+                         "\"params\": {\"user\": \"AAAA\"}}");
+    CHECK(json != NULL);
+    CHECK(strstr(json, "\"code\":\"malformed_message\"") != NULL);
+    ooniauth_string_free(json);
+
+    /* Log records go to the callback, down to the level set */
+    CHECK_STATUS(ooniauth_set_log_level(0), OONIAUTH_STATUS_INVALID_ARGUMENT);
+    CHECK_OK(ooniauth_set_log_level(OONIAUTH_LOG_LEVEL_INFO));
+    CHECK_OK(ooniauth_set_log_callback(log_record, &records));
+    CHECK_OK(submit(server, user, "VE", "VE"));
+    CHECK_OK(ooniauth_set_log_callback(NULL, NULL));
+    CHECK_OK(submit(server, user, "VE", "VE"));
+    CHECK(records.submit_spans == 1);
+    CHECK(records.below_level == 0);
+
+    /* A server restored from its keys accepts the credential */
+    CHECK_OK(ooniauth_server_from_creds(pp.data, pp.len, sk.data, sk.len,
+                                        &restored));
+    CHECK_OK(submit(restored, user, "VE", "VE"));
+
+    /* Errors come back as status codes */
+    CHECK_STATUS(submit(server, user, "VE", "ve"), OONIAUTH_STATUS_BAD_DOMAIN);
+    CHECK_STATUS(submit(server, user, "VE", "IT"),
+                 OONIAUTH_STATUS_PROOF_REJECTED);
+    CHECK_STATUS(ooniauth_server_handle_registration(
+                     server, (const uint8_t *)"garbage", 7, &response),
+                 OONIAUTH_STATUS_MALFORMED_MESSAGE);
+    CHECK_STATUS(ooniauth_user_new(NULL, 1, &copy),
+                 OONIAUTH_STATUS_INVALID_ARGUMENT);
+    CHECK_STATUS(ooniauth_server_new(NULL), OONIAUTH_STATUS_INVALID_ARGUMENT);
+    CHECK(strcmp(ooniauth_last_error(), "out is NULL") == 0);
+    CHECK_OK(ooniauth_server_public_parameters(server, &new_pp));
+    CHECK(ooniauth_last_error() == NULL);
+    ooniauth_buffer_free(new_pp);
+
+    /* Export and import the credential and the whole state */
+    CHECK_OK(ooniauth_user_get_credential(user, &credential));
+    CHECK_OK(ooniauth_user_to_bytes(user, &state));
+    CHECK_OK(ooniauth_user_from_bytes(state.data, state.len, &copy));
+    CHECK_OK(submit(server, copy, "IT", "IT"));
+    ooniauth_user_free(copy);
+    CHECK_OK(ooniauth_user_new(pp.data, pp.len, &copy));
+    CHECK_OK(ooniauth_user_set_credential(copy, credential.data,
+                                          credential.len));
+    CHECK_OK(submit(server, copy, "IT", "IT"));
+    ooniauth_user_free(copy);
+
+    /* A credential without its nym_id makes the core panic, which must come
+     * back as a status. bincode writes the Option of the first field as a
+     * tag byte followed by the 32 byte scalar, and None as a single 0. */
+    CHECK(credential.len > 33 && credential.data[0] == 1);
+    {
+        uint8_t *broken = malloc(credential.len - 32);
+        CHECK(broken != NULL);
+        broken[0] = 0;
+        memcpy(broken + 1, credential.data + 33, credential.len - 33);
+        CHECK_OK(ooniauth_user_new(pp.data, pp.len, &copy));
+        CHECK_OK(ooniauth_user_set_credential(copy, broken,
+                                              credential.len - 32));
+        free(broken);
+    }
+    CHECK_STATUS(submit(server, copy, "IT", "IT"), OONIAUTH_STATUS_PANIC);
+    CHECK(strncmp(ooniauth_last_error(), "panic: ", 7) == 0);
+    ooniauth_user_free(copy);
+
+    /* Key rotation: move the credential to the keys of a new server */
+    CHECK_OK(ooniauth_server_new(&rotated));
+    CHECK_OK(ooniauth_server_public_parameters(rotated, &new_pp));
+    CHECK_OK(ooniauth_user_set_public_parameters(user, new_pp.data,
+                                                 new_pp.len));
+    CHECK_OK(ooniauth_user_update_request(user, &request, &update));
+    CHECK_OK(ooniauth_server_handle_update(rotated, request.data, request.len,
+                                           pp.data, pp.len, sk.data, sk.len,
+                                           &response));
+    CHECK_OK(ooniauth_user_handle_update_response(user, update, response.data,
+                                                  response.len));
+    CHECK_OK(submit(rotated, user, "VE", "VE"));
+
+    ooniauth_buffer_free(pp);
+    ooniauth_buffer_free(sk);
+    ooniauth_buffer_free(new_pp);
+    ooniauth_buffer_free(credential);
+    ooniauth_buffer_free(state);
+    ooniauth_buffer_free(request);
+    ooniauth_buffer_free(response);
+    ooniauth_user_free(user);
+    ooniauth_server_free(server);
+    ooniauth_server_free(restored);
+    ooniauth_server_free(rotated);
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,53 @@
+//! Builds `tests/c/test_ffi.c` against the static library and runs it, and
+//! checks that the committed header is the one cbindgen generates
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_c_api() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Test binaries live in `<target>/<profile>/deps`, next to the library
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().unwrap().parent().unwrap();
+    let library = profile_dir.join("libooniauth_ffi.a");
+    assert!(library.exists(), "{} is missing", library.display());
+
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_ffi");
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(cc)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/test_ffi.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("a C compiler is needed, set CC to pick one");
+    assert!(status.success(), "compiling test_ffi.c failed");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "test_ffi failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
+
+#[test]
+fn test_header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/ooniauth_ffi.h"));
+    let header = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include/ooniauth_ffi.h");
+    if std::env::var_os("OONIAUTH_UPDATE_HEADER").is_some() {
+        std::fs::write(&header, generated).unwrap();
+    }
+    let committed = std::fs::read_to_string(&header).unwrap();
+    assert!(
+        committed == generated,
+        "{} is out of date, update it with `OONIAUTH_UPDATE_HEADER=1 cargo test -p ooniauth-ffi --test c_api`",
+        header.display()
+    );
+}
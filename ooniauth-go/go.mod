@@ -0,0 +1,3 @@
+module github.com/ooni/userauth/ooniauth-go
+
+go 1.21
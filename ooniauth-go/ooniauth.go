@@ -0,0 +1,186 @@
+// Package ooniauth wraps the C API of ooniauth-ffi for Go programs such as
+// OONI Probe.
+//
+// The static library must be built first, from the root of the repository:
+//
+//	cargo build -p ooniauth-ffi --release
+//
+// Keys, credentials and messages are the same bytes as with the `_bytes`
+// methods of the Python bindings, so a Go client can talk to a Python server.
+package ooniauth
+
+/*
+#cgo CFLAGS: -I${SRCDIR}/../ooniauth-ffi/include
+#cgo LDFLAGS: ${SRCDIR}/../target/release/libooniauth_ffi.a
+#cgo linux LDFLAGS: -lm -ldl -lrt -lpthread -lutil
+#include <stdlib.h>
+#include "ooniauth_ffi.h"
+*/
+import "C"
+
+import (
+	"fmt"
+	"runtime"
+	"unsafe"
+)
+
+// Code is the status of a failed call. The values are stable and shared with
+// the Rust, Python and C interfaces.
+type Code uint32
+
+const (
+	CodeCredentialExpired       Code = C.OONIAUTH_STATUS_CREDENTIAL_EXPIRED
+	CodeNotYetEligible          Code = C.OONIAUTH_STATUS_NOT_YET_ELIGIBLE
+	CodeCountExceeded           Code = C.OONIAUTH_STATUS_COUNT_EXCEEDED
+	CodeBadDomain               Code = C.OONIAUTH_STATUS_BAD_DOMAIN
+	CodeUnknownPublicParameters Code = C.OONIAUTH_STATUS_UNKNOWN_PUBLIC_PARAMETERS
+	CodeMalformedMessage        Code = C.OONIAUTH_STATUS_MALFORMED_MESSAGE
+	CodeProofRejected           Code = C.OONIAUTH_STATUS_PROOF_REJECTED
+	CodeInvalidField            Code = C.OONIAUTH_STATUS_INVALID_FIELD
+	CodeCredentialMismatch      Code = C.OONIAUTH_STATUS_CREDENTIAL_MISMATCH
+	CodeMissingCredential       Code = C.OONIAUTH_STATUS_MISSING_CREDENTIAL
+	CodeInternal                Code = C.OONIAUTH_STATUS_INTERNAL
+	CodeInvalidArgument         Code = C.OONIAUTH_STATUS_INVALID_ARGUMENT
+	CodePanic                   Code = C.OONIAUTH_STATUS_PANIC
+)
+
+// String returns the stable snake_case name of the code, e.g. "bad_domain"
+func (c Code) String() string {
+	name := C.ooniauth_status_name(C.uint32_t(c))
+	if name == nil {
+		return fmt.Sprintf("unknown_%d", uint32(c))
+	}
+	return C.GoString(name)
+}
+
+// Error is returned by every failed call. Match it with errors.Is against
+// the Err* values, which compare the code only:
+//
+//	if errors.Is(err, ooniauth.ErrCredentialExpired) { ... }
+type Error struct {
+	Code    Code
+	Message string
+}
+
+func (e *Error) Error() string {
+	if e.Message == "" {
+		return e.Code.String()
+	}
+	return fmt.Sprintf("%s: %s", e.Code, e.Message)
+}
+
+// Is reports whether target is an *Error with the same code
+func (e *Error) Is(target error) bool {
+	t, ok := target.(*Error)
+	return ok && t.Code == e.Code
+}
+
+var (
+	ErrCredentialExpired       = &Error{Code: CodeCredentialExpired}
+	ErrNotYetEligible          = &Error{Code: CodeNotYetEligible}
+	ErrCountExceeded           = &Error{Code: CodeCountExceeded}
+	ErrBadDomain               = &Error{Code: CodeBadDomain}
+	ErrUnknownPublicParameters = &Error{Code: CodeUnknownPublicParameters}
+	ErrMalformedMessage        = &Error{Code: CodeMalformedMessage}
+	ErrProofRejected           = &Error{Code: CodeProofRejected}
+	ErrInvalidField            = &Error{Code: CodeInvalidField}
+	ErrCredentialMismatch      = &Error{Code: CodeCredentialMismatch}
+	ErrMissingCredential       = &Error{Code: CodeMissingCredential}
+	ErrInternal                = &Error{Code: CodeInternal}
+	ErrInvalidArgument         = &Error{Code: CodeInvalidArgument}
+	ErrPanic                   = &Error{Code: CodePanic}
+)
+
+// call runs one C function and turns its status into an error. The message
+// is kept per OS thread by the library, so the goroutine must not move to
+// another thread between the call and reading it.
+func call(f func() C.OoniauthStatus) error {
+	runtime.LockOSThread()
+	defer runtime.UnlockOSThread()
+	status := f()
+	if status == C.OONIAUTH_STATUS_OK {
+		return nil
+	}
+	return &Error{Code: Code(status), Message: C.GoString(C.ooniauth_last_error())}
+}
+
+// cBytes points C at the contents of b, for the duration of a call
+func cBytes(b []byte) (*C.uint8_t, C.size_t) {
+	if len(b) == 0 {
+		return nil, 0
+	}
+	return (*C.uint8_t)(unsafe.Pointer(&b[0])), C.size_t(len(b))
+}
+
+// goBytes copies a buffer returned by the library and frees it
+func goBytes(buf C.OoniauthBuffer) []byte {
+	defer C.ooniauth_buffer_free(buf)
+	if buf.data == nil {
+		return []byte{}
+	}
+	return C.GoBytes(unsafe.Pointer(buf.data), C.int(buf.len))
+}
+
+// Today returns today as a Julian day, the unit of the age ranges
+func Today() uint32 {
+	return uint32(C.ooniauth_today())
+}
+
+// MeasurementHash hashes a measurement for NewSubmitRequest and HandleSubmit
+func MeasurementHash(measurement []byte) ([32]byte, error) {
+	var hash [32]byte
+	data, size := cBytes(measurement)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_submit_measurement_hash(data, size, (*C.uint8_t)(unsafe.Pointer(&hash[0])))
+	})
+	return hash, err
+}
+
+// SubmitRanges are the half-open ranges a credential must fall in to submit
+// a measurement. The client and the server must use the same ones.
+type SubmitRanges struct {
+	// Registration day of the credential, as Julian days
+	AgeStart, AgeEnd uint32
+	// Measurements submitted with the credential so far
+	MinMeasurementCount, MaxMeasurementCount uint32
+}
+
+// SubmitPolicy are the limits a server puts on submissions, the same as
+// SubmitPolicy in the other bindings
+type SubmitPolicy struct {
+	// Credentials registered more than MaxAgeDays ago are refused
+	MaxAgeDays uint32
+	// Accepted measurement counts: at least MinMeasurementCount and fewer
+	// than *MaxMeasurementCount, with no upper bound when it is nil
+	MinMeasurementCount uint32
+	MaxMeasurementCount *uint32
+}
+
+// DefaultSubmitPolicy accepts credentials up to 30 days old, with any count
+var DefaultSubmitPolicy = SubmitPolicy{MaxAgeDays: 30}
+
+// Ranges returns the ranges accepted on the given Julian day, see Today. It
+// fails with CodeInvalidArgument when no credential can satisfy the policy.
+func (p SubmitPolicy) Ranges(today uint32) (SubmitRanges, error) {
+	var maxCount *C.uint32_t
+	if p.MaxMeasurementCount != nil {
+		value := C.uint32_t(*p.MaxMeasurementCount)
+		maxCount = &value
+	}
+	var out C.OoniauthSubmitRanges
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_submit_policy_ranges(
+			C.uint32_t(p.MaxAgeDays), C.uint32_t(p.MinMeasurementCount), maxCount,
+			C.uint32_t(today), &out,
+		)
+	})
+	if err != nil {
+		return SubmitRanges{}, err
+	}
+	return SubmitRanges{
+		AgeStart:            uint32(out.age_start),
+		AgeEnd:              uint32(out.age_end),
+		MinMeasurementCount: uint32(out.min_measurement_count),
+		MaxMeasurementCount: uint32(out.max_measurement_count),
+	}, nil
+}
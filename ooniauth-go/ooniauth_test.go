@@ -0,0 +1,232 @@
+package ooniauth
+
+import (
+	"bytes"
+	"errors"
+	"testing"
+)
+
+func mustServer(t *testing.T) (*ServerState, []byte) {
+	t.Helper()
+	server, err := NewServerState()
+	if err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(server.Close)
+	pp, err := server.PublicParameters()
+	if err != nil {
+		t.Fatal(err)
+	}
+	return server, pp
+}
+
+func register(t *testing.T, server *ServerState, user *UserState) {
+	t.Helper()
+	req, err := user.NewRegistrationRequest()
+	if err != nil {
+		t.Fatal(err)
+	}
+	resp, err := server.HandleRegistration(req.Request)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := user.HandleRegistrationResponse(req, resp); err != nil {
+		t.Fatal(err)
+	}
+}
+
+func submit(server *ServerState, user *UserState, probeCC, probeASN string, measurement []byte) error {
+	hash, err := MeasurementHash(measurement)
+	if err != nil {
+		return err
+	}
+	ranges, err := DefaultSubmitPolicy.Ranges(Today())
+	if err != nil {
+		return err
+	}
+	req, err := user.NewSubmitRequest(probeCC, probeASN, hash, ranges)
+	if err != nil {
+		return err
+	}
+	resp, err := server.HandleSubmit(req.Nym, req.Request, probeCC, probeASN, hash, ranges)
+	if err != nil {
+		req.Close()
+		return err
+	}
+	return user.HandleSubmitResponse(req, resp)
+}
+
+func TestRegisterSubmitUpdate(t *testing.T) {
+	server, pp := mustServer(t)
+	sk, err := server.SecretKey()
+	if err != nil {
+		t.Fatal(err)
+	}
+	user, err := NewUserState(pp)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer user.Close()
+
+	register(t, server, user)
+	for i := 0; i < 3; i++ {
+		if err := submit(server, user, "IT", "AS1234", []byte("measurement")); err != nil {
+			t.Fatalf("submit %d: %v", i, err)
+		}
+	}
+
+	// The server rotates its keys, the client moves its credential over
+	rotated, newPP := mustServer(t)
+	if err := user.SetPublicParameters(newPP); err != nil {
+		t.Fatal(err)
+	}
+	req, err := user.NewUpdateRequest()
+	if err != nil {
+		t.Fatal(err)
+	}
+	resp, err := rotated.HandleUpdate(req.Request, pp, sk)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := user.HandleUpdateResponse(req, resp); err != nil {
+		t.Fatal(err)
+	}
+	if err := submit(rotated, user, "IT", "AS1234", []byte("measurement")); err != nil {
+		t.Fatal(err)
+	}
+
+	// The old keys no longer match the credential
+	if err := submit(server, user, "IT", "AS1234", []byte("measurement")); err == nil {
+		t.Fatal("submit with the old keys should fail")
+	}
+}
+
+func TestPersistence(t *testing.T) {
+	server, pp := mustServer(t)
+	sk, err := server.SecretKey()
+	if err != nil {
+		t.Fatal(err)
+	}
+	user, err := NewUserState(pp)
+	if err != nil {
+		t.Fatal(err)
+	}
+	register(t, server, user)
+
+	saved, err := user.MarshalBinary()
+	if err != nil {
+		t.Fatal(err)
+	}
+	credential, err := user.Credential()
+	if err != nil {
+		t.Fatal(err)
+	}
+	user.Close()
+
+	restored, err := UnmarshalUserState(saved)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer restored.Close()
+	restoredServer, err := ServerStateFromCreds(pp, sk)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer restoredServer.Close()
+	if err := submit(restoredServer, restored, "VE", "AS8048", []byte("measurement")); err != nil {
+		t.Fatal(err)
+	}
+
+	imported, err := NewUserState(pp)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer imported.Close()
+	if err := imported.SetCredential(credential); err != nil {
+		t.Fatal(err)
+	}
+	got, err := imported.Credential()
+	if err != nil {
+		t.Fatal(err)
+	}
+	if !bytes.Equal(got, credential) {
+		t.Fatal("imported credential differs")
+	}
+}
+
+func TestErrors(t *testing.T) {
+	server, pp := mustServer(t)
+	user, err := NewUserState(pp)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer user.Close()
+
+	if _, err := user.Credential(); !errors.Is(err, ErrMissingCredential) {
+		t.Fatalf("expected ErrMissingCredential, got %v", err)
+	}
+	err = submit(server, user, "IT", "AS1234", nil)
+	if !errors.Is(err, ErrMissingCredential) {
+		t.Fatalf("expected ErrMissingCredential, got %v", err)
+	}
+
+	register(t, server, user)
+	err = submit(server, user, "it", "AS1234", nil)
+	var e *Error
+	if !errors.As(err, &e) || e.Code != CodeBadDomain || e.Message == "" {
+		t.Fatalf("expected a bad_domain error with a message, got %v", err)
+	}
+	if e.Code.String() != "bad_domain" {
+		t.Fatalf("unexpected code name %q", e.Code)
+	}
+
+	if _, err := server.HandleRegistration([]byte("garbage")); !errors.Is(err, ErrMalformedMessage) {
+		t.Fatalf("expected ErrMalformedMessage, got %v", err)
+	}
+
+	// A bad response leaves the request pending, and a request is consumed
+	// by the response it handles
+	req, err := user.NewRegistrationRequest()
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := user.HandleRegistrationResponse(req, []byte("garbage")); !errors.Is(err, ErrMalformedMessage) {
+		t.Fatalf("expected ErrMalformedMessage, got %v", err)
+	}
+	resp, err := server.HandleRegistration(req.Request)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if err := user.HandleRegistrationResponse(req, resp); err != nil {
+		t.Fatal(err)
+	}
+	if err := user.HandleRegistrationResponse(req, resp); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("expected ErrInvalidArgument, got %v", err)
+	}
+}
+
+func TestSubmitPolicy(t *testing.T) {
+	ranges, err := DefaultSubmitPolicy.Ranges(100)
+	if err != nil {
+		t.Fatal(err)
+	}
+	want := SubmitRanges{AgeStart: 70, AgeEnd: 101, MaxMeasurementCount: ^uint32(0)}
+	if ranges != want {
+		t.Fatalf("expected %+v, got %+v", want, ranges)
+	}
+
+	maxCount := uint32(10)
+	ranges, err = SubmitPolicy{MaxAgeDays: 30, MaxMeasurementCount: &maxCount}.Ranges(100)
+	if err != nil || ranges.MaxMeasurementCount != 10 {
+		t.Fatalf("expected a max count of 10, got %+v, %v", ranges, err)
+	}
+
+	// Policies no credential can satisfy are refused, as in the other bindings
+	maxCount = 0
+	if _, err := (SubmitPolicy{MaxAgeDays: 30, MaxMeasurementCount: &maxCount}).Ranges(100); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("expected ErrInvalidArgument, got %v", err)
+	}
+	if _, err := (SubmitPolicy{MaxAgeDays: 200}).Ranges(100); !errors.Is(err, ErrInvalidArgument) {
+		t.Fatalf("expected ErrInvalidArgument, got %v", err)
+	}
+}
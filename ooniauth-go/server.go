@@ -0,0 +1,155 @@
+package ooniauth
+
+/*
+#include <stdlib.h>
+#include "ooniauth_ffi.h"
+*/
+import "C"
+
+import (
+	"runtime"
+	"sync"
+	"unsafe"
+)
+
+// ServerState holds the issuer keys and verifies the requests of clients.
+// It is safe for concurrent use, and requests are handled in parallel.
+type ServerState struct {
+	mu  sync.RWMutex
+	ptr *C.OoniauthServerState
+}
+
+func newServerState(ptr *C.OoniauthServerState) *ServerState {
+	s := &ServerState{ptr: ptr}
+	runtime.SetFinalizer(s, (*ServerState).Close)
+	return s
+}
+
+// NewServerState creates a server with fresh keys
+func NewServerState() (*ServerState, error) {
+	var ptr *C.OoniauthServerState
+	if err := call(func() C.OoniauthStatus { return C.ooniauth_server_new(&ptr) }); err != nil {
+		return nil, err
+	}
+	return newServerState(ptr), nil
+}
+
+// ServerStateFromCreds restores a server from the keys returned by
+// PublicParameters and SecretKey
+func ServerStateFromCreds(publicParameters, secretKey []byte) (*ServerState, error) {
+	var ptr *C.OoniauthServerState
+	pp, ppLen := cBytes(publicParameters)
+	sk, skLen := cBytes(secretKey)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_server_from_creds(pp, ppLen, sk, skLen, &ptr)
+	})
+	if err != nil {
+		return nil, err
+	}
+	return newServerState(ptr), nil
+}
+
+// Close releases the keys. It is called by the garbage collector otherwise,
+// and the state must not be used afterwards.
+func (s *ServerState) Close() {
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if s.ptr != nil {
+		C.ooniauth_server_free(s.ptr)
+		s.ptr = nil
+	}
+}
+
+// PublicParameters returns the public parameters to hand out to clients
+func (s *ServerState) PublicParameters() ([]byte, error) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	var buf C.OoniauthBuffer
+	err := call(func() C.OoniauthStatus { return C.ooniauth_server_public_parameters(s.ptr, &buf) })
+	if err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// SecretKey returns the secret key, to store along with the public parameters
+func (s *ServerState) SecretKey() ([]byte, error) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	var buf C.OoniauthBuffer
+	err := call(func() C.OoniauthStatus { return C.ooniauth_server_secret_key(s.ptr, &buf) })
+	if err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// HandleRegistration issues a credential and returns the response for the
+// client
+func (s *ServerState) HandleRegistration(request []byte) ([]byte, error) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	var buf C.OoniauthBuffer
+	req, reqLen := cBytes(request)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_server_handle_registration(s.ptr, req, reqLen, &buf)
+	})
+	if err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// HandleSubmit verifies a submission and returns the response holding the
+// updated credential. probeCC and probeASN must be well formed, e.g. "IT"
+// and "AS1234", or ErrBadDomain is returned.
+func (s *ServerState) HandleSubmit(
+	nym [32]byte,
+	request []byte,
+	probeCC, probeASN string,
+	measurementHash [32]byte,
+	ranges SubmitRanges,
+) ([]byte, error) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	var buf C.OoniauthBuffer
+	req, reqLen := cBytes(request)
+	cc := C.CString(probeCC)
+	defer C.free(unsafe.Pointer(cc))
+	asn := C.CString(probeASN)
+	defer C.free(unsafe.Pointer(asn))
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_server_handle_submit(
+			s.ptr,
+			(*C.uint8_t)(unsafe.Pointer(&nym[0])),
+			req, reqLen,
+			cc, asn,
+			(*C.uint8_t)(unsafe.Pointer(&measurementHash[0])),
+			C.uint32_t(ranges.AgeStart), C.uint32_t(ranges.AgeEnd),
+			C.uint32_t(ranges.MinMeasurementCount), C.uint32_t(ranges.MaxMeasurementCount),
+			&buf,
+		)
+	})
+	if err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// HandleUpdate moves a credential issued under the old keys to the keys of
+// this server, after a key rotation
+func (s *ServerState) HandleUpdate(request, oldPublicParameters, oldSecretKey []byte) ([]byte, error) {
+	s.mu.RLock()
+	defer s.mu.RUnlock()
+	var buf C.OoniauthBuffer
+	req, reqLen := cBytes(request)
+	pp, ppLen := cBytes(oldPublicParameters)
+	sk, skLen := cBytes(oldSecretKey)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_server_handle_update(s.ptr, req, reqLen, pp, ppLen, sk, skLen, &buf)
+	})
+	if err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
@@ -0,0 +1,280 @@
+package ooniauth
+
+/*
+#include <stdlib.h>
+#include "ooniauth_ffi.h"
+*/
+import "C"
+
+import (
+	"runtime"
+	"sync"
+	"unsafe"
+)
+
+// UserState is the client side: the server public parameters and the
+// credential. It is safe for concurrent use.
+//
+// Each New*Request method returns a request holding the bytes to send to the
+// server, which is then passed back with the response to the matching
+// Handle*Response method. Several requests may be pending at once.
+type UserState struct {
+	mu  sync.Mutex
+	ptr *C.OoniauthUserState
+}
+
+func newUserState(ptr *C.OoniauthUserState) *UserState {
+	u := &UserState{ptr: ptr}
+	runtime.SetFinalizer(u, (*UserState).Close)
+	return u
+}
+
+// NewUserState creates a client without a credential for the given public
+// parameters
+func NewUserState(publicParameters []byte) (*UserState, error) {
+	var ptr *C.OoniauthUserState
+	pp, ppLen := cBytes(publicParameters)
+	err := call(func() C.OoniauthStatus { return C.ooniauth_user_new(pp, ppLen, &ptr) })
+	if err != nil {
+		return nil, err
+	}
+	return newUserState(ptr), nil
+}
+
+// UnmarshalUserState restores a client saved with MarshalBinary
+func UnmarshalUserState(data []byte) (*UserState, error) {
+	var ptr *C.OoniauthUserState
+	d, dLen := cBytes(data)
+	err := call(func() C.OoniauthStatus { return C.ooniauth_user_from_bytes(d, dLen, &ptr) })
+	if err != nil {
+		return nil, err
+	}
+	return newUserState(ptr), nil
+}
+
+// Close releases the state. It is called by the garbage collector otherwise,
+// and the state must not be used afterwards.
+func (u *UserState) Close() {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	if u.ptr != nil {
+		C.ooniauth_user_free(u.ptr)
+		u.ptr = nil
+	}
+}
+
+// MarshalBinary saves the whole state, credential included
+func (u *UserState) MarshalBinary() ([]byte, error) {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	var buf C.OoniauthBuffer
+	if err := call(func() C.OoniauthStatus { return C.ooniauth_user_to_bytes(u.ptr, &buf) }); err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// Credential exports the credential, or returns ErrMissingCredential before
+// registration
+func (u *UserState) Credential() ([]byte, error) {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	var buf C.OoniauthBuffer
+	if err := call(func() C.OoniauthStatus { return C.ooniauth_user_get_credential(u.ptr, &buf) }); err != nil {
+		return nil, err
+	}
+	return goBytes(buf), nil
+}
+
+// SetCredential imports a credential exported with Credential
+func (u *UserState) SetCredential(credential []byte) error {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	cred, credLen := cBytes(credential)
+	return call(func() C.OoniauthStatus { return C.ooniauth_user_set_credential(u.ptr, cred, credLen) })
+}
+
+// SetPublicParameters switches to new public parameters after the server
+// rotated its keys. Follow with an update of the credential.
+func (u *UserState) SetPublicParameters(publicParameters []byte) error {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	pp, ppLen := cBytes(publicParameters)
+	return call(func() C.OoniauthStatus { return C.ooniauth_user_set_public_parameters(u.ptr, pp, ppLen) })
+}
+
+// RegistrationRequest is a pending registration
+type RegistrationRequest struct {
+	// Request is sent to the server
+	Request []byte
+	pending *C.OoniauthRegistrationState
+}
+
+// Close releases a registration that will not be completed
+func (r *RegistrationRequest) Close() {
+	if r.pending != nil {
+		C.ooniauth_registration_state_free(r.pending)
+		r.pending = nil
+	}
+}
+
+// NewRegistrationRequest starts a registration
+func (u *UserState) NewRegistrationRequest() (*RegistrationRequest, error) {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	var buf C.OoniauthBuffer
+	var pending *C.OoniauthRegistrationState
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_registration_request(u.ptr, &buf, &pending)
+	})
+	if err != nil {
+		return nil, err
+	}
+	r := &RegistrationRequest{Request: goBytes(buf), pending: pending}
+	runtime.SetFinalizer(r, (*RegistrationRequest).Close)
+	return r, nil
+}
+
+// HandleRegistrationResponse stores the credential issued by the server.
+// The request can't be used again once handled. When the response is
+// rejected it stays pending, for another response or Close.
+func (u *UserState) HandleRegistrationResponse(r *RegistrationRequest, response []byte) error {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	if r.pending == nil {
+		return &Error{Code: CodeInvalidArgument, Message: "registration request already used"}
+	}
+	resp, respLen := cBytes(response)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_handle_registration_response(u.ptr, r.pending, resp, respLen)
+	})
+	if err == nil {
+		r.pending = nil
+	}
+	return err
+}
+
+// SubmitRequest is a pending submission
+type SubmitRequest struct {
+	// Nym is the pseudonym of the client for the probe network, sent to the
+	// server along with Request
+	Nym     [32]byte
+	Request []byte
+	pending *C.OoniauthSubmitState
+}
+
+// Close releases a submission that will not be completed
+func (r *SubmitRequest) Close() {
+	if r.pending != nil {
+		C.ooniauth_submit_state_free(r.pending)
+		r.pending = nil
+	}
+}
+
+// NewSubmitRequest proves that the credential falls in ranges, to submit the
+// measurement hashed as measurementHash, see MeasurementHash
+func (u *UserState) NewSubmitRequest(
+	probeCC, probeASN string,
+	measurementHash [32]byte,
+	ranges SubmitRanges,
+) (*SubmitRequest, error) {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	cc := C.CString(probeCC)
+	defer C.free(unsafe.Pointer(cc))
+	asn := C.CString(probeASN)
+	defer C.free(unsafe.Pointer(asn))
+
+	var buf C.OoniauthBuffer
+	var nym [32]byte
+	var pending *C.OoniauthSubmitState
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_submit_request(
+			u.ptr,
+			cc, asn,
+			(*C.uint8_t)(unsafe.Pointer(&measurementHash[0])),
+			C.uint32_t(ranges.AgeStart), C.uint32_t(ranges.AgeEnd),
+			C.uint32_t(ranges.MinMeasurementCount), C.uint32_t(ranges.MaxMeasurementCount),
+			(*C.uint8_t)(unsafe.Pointer(&nym[0])),
+			&buf,
+			&pending,
+		)
+	})
+	if err != nil {
+		return nil, err
+	}
+	r := &SubmitRequest{Nym: nym, Request: goBytes(buf), pending: pending}
+	runtime.SetFinalizer(r, (*SubmitRequest).Close)
+	return r, nil
+}
+
+// HandleSubmitResponse replaces the credential with the one issued by the
+// server. The request can't be used again once handled. When the response
+// is rejected it stays pending, for another response or Close.
+func (u *UserState) HandleSubmitResponse(r *SubmitRequest, response []byte) error {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	if r.pending == nil {
+		return &Error{Code: CodeInvalidArgument, Message: "submit request already used"}
+	}
+	resp, respLen := cBytes(response)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_handle_submit_response(u.ptr, r.pending, resp, respLen)
+	})
+	if err == nil {
+		r.pending = nil
+	}
+	return err
+}
+
+// UpdateRequest is a pending credential update
+type UpdateRequest struct {
+	// Request is sent to the server
+	Request []byte
+	pending *C.OoniauthUpdateState
+}
+
+// Close releases an update that will not be completed
+func (r *UpdateRequest) Close() {
+	if r.pending != nil {
+		C.ooniauth_update_state_free(r.pending)
+		r.pending = nil
+	}
+}
+
+// NewUpdateRequest asks to move the credential to the current public
+// parameters, see SetPublicParameters
+func (u *UserState) NewUpdateRequest() (*UpdateRequest, error) {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	var buf C.OoniauthBuffer
+	var pending *C.OoniauthUpdateState
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_update_request(u.ptr, &buf, &pending)
+	})
+	if err != nil {
+		return nil, err
+	}
+	r := &UpdateRequest{Request: goBytes(buf), pending: pending}
+	runtime.SetFinalizer(r, (*UpdateRequest).Close)
+	return r, nil
+}
+
+// HandleUpdateResponse stores the updated credential. The request can't be
+// used again once handled. When the response is rejected it stays pending,
+// for another response or Close.
+func (u *UserState) HandleUpdateResponse(r *UpdateRequest, response []byte) error {
+	u.mu.Lock()
+	defer u.mu.Unlock()
+	if r.pending == nil {
+		return &Error{Code: CodeInvalidArgument, Message: "update request already used"}
+	}
+	resp, respLen := cBytes(response)
+	err := call(func() C.OoniauthStatus {
+		return C.ooniauth_user_handle_update_response(u.ptr, r.pending, resp, respLen)
+	})
+	if err == nil {
+		r.pending = nil
+	}
+	return err
+}
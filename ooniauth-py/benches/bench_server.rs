@@ -1,6 +1,9 @@
-use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
-use ooniauth_py::{ServerState, UserState};
-use pyo3::{Py, Python, types::PyString};
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use ooniauth_py::{ServerState, SubmitRequest, UserState};
+use pyo3::{Bound, Py, Python, types::PyString};
+use std::thread;
 use rand::{distributions::Alphanumeric, Rng};
 use pprof::criterion::{Output, PProfProfiler};
 
@@ -20,10 +23,12 @@ fn bench_submit(c: &mut Criterion) {
             b.iter_batched(|| {
                 let server = ServerState::new();
                 let mut client = UserState::new(py, server.get_public_parameters(py)).unwrap();
-                let req = client.make_registration_request(py).unwrap();
-                let reg_response = server.handle_registration_request(py, req).unwrap();
+                let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+                let reg_response = server
+                    .handle_registration_request(py, req.borrow().request(py))
+                    .unwrap();
                 client
-                    .handle_registration_response(py, reg_response)
+                    .handle_registration_response(py, reg_response, req.borrow_mut())
                     .unwrap();
 
                 let cc: Py<PyString> = PyString::new(py, "VE").into();
@@ -42,6 +47,7 @@ fn bench_submit(c: &mut Criterion) {
                         measurement.clone_ref(py),
                         age_tuple,
                         min_msm,
+                        None,
                     )
                     .unwrap();
 
@@ -50,23 +56,96 @@ fn bench_submit(c: &mut Criterion) {
                 |(server, submit_req, cc, asn, measurement, age_tuple, min_msm)| {
                 server.handle_submit_request_with_hash(
                     py,
-                    submit_req.nym,
-                    submit_req.request,
+                    submit_req.nym(py),
+                    submit_req.request(py),
                     cc,
                     asn,
                     measurement,
                     age_tuple,
-                    min_msm
+                    min_msm,
+                    None,
                 )
             }, BatchSize::SmallInput);
         });
     });
 }
 
+// Proof verification runs without the GIL, so submissions handled from several Python
+// threads should scale with the number of threads
+fn bench_submit_threads(c: &mut Criterion) {
+    pyo3::Python::initialize();
+    let server = ServerState::new();
+    let today = ServerState::today();
+    let age_tuple = (today - 30, today + 1);
+    let measurement_hash = "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+    let client = Python::attach(|py| {
+        let mut client = UserState::new(py, server.get_public_parameters(py)).unwrap();
+        let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+        let reg_response = server
+            .handle_registration_request(py, req.borrow().request(py))
+            .unwrap();
+        client
+            .handle_registration_response(py, reg_response, req.borrow_mut())
+            .unwrap();
+        client
+    });
+
+    let mut group = c.benchmark_group("server.handle_submit_request/threads");
+    for threads in [1, 2, 4, 8] {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            b.iter_batched(|| {
+                Python::attach(|py| {
+                    (0..threads)
+                        .map(|_| {
+                            client
+                                .make_submit_request(
+                                    py,
+                                    PyString::new(py, "VE").into(),
+                                    PyString::new(py, "AS1234").into(),
+                                    PyString::new(py, measurement_hash).into(),
+                                    age_tuple,
+                                    0,
+                                    None,
+                                )
+                                .unwrap()
+                        })
+                        .collect::<Vec<SubmitRequest>>()
+                })
+            },
+            |requests| {
+                thread::scope(|s| {
+                    for submit_req in requests {
+                        let server = &server;
+                        s.spawn(move || {
+                            Python::attach(|py| {
+                                server
+                                    .handle_submit_request(
+                                        py,
+                                        submit_req.nym(py),
+                                        submit_req.request(py),
+                                        PyString::new(py, "VE").into(),
+                                        PyString::new(py, "AS1234").into(),
+                                        PyString::new(py, measurement_hash).into(),
+                                        age_tuple,
+                                        0,
+                                        None,
+                                    )
+                                    .unwrap()
+                            })
+                        });
+                    }
+                });
+            }, BatchSize::SmallInput);
+        });
+    }
+    group.finish();
+}
+
 criterion_group!{
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_submit
+    targets = bench_submit, bench_submit_threads
 }
 
 criterion_main!(benches);
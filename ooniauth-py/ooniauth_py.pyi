@@ -1,16 +1,138 @@
 # This file is automatically generated by pyo3_stub_gen
 # ruff: noqa: E501, F401
 
+import asyncio
 import builtins
+import datetime
 import typing
+from enum import Enum
 
 __version__: builtins.str
 
+class BadDomain(CredentialError):
+    r"""
+    The probe country code or ASN is not well formed
+    """
+
+    @property
+    def reason(self) -> builtins.str:
+        r"""
+        Which of the two is invalid, and why
+        """
+
+class CountBelowMinimum(NotYetEligible):
+    r"""
+    The credential has fewer measurements than required
+    """
+
+    @property
+    def count(self) -> builtins.int:
+        r"""
+        Measurement count of the credential
+        """
+
+    @property
+    def min(self) -> builtins.int:
+        r"""
+        Minimum measurement count accepted
+        """
+
+class CountExceeded(CredentialError):
+    r"""
+    The credential has reached the maximum measurement count
+    """
+
+    @property
+    def count(self) -> builtins.int:
+        r"""
+        Measurement count of the credential
+        """
+
+    @property
+    def max(self) -> builtins.int:
+        r"""
+        First measurement count no longer accepted
+        """
+
 class CredentialError(builtins.Exception):
     r"""
     An authentication error
     """
 
+    @property
+    def code(self) -> ErrorCode:
+        r"""
+        The stable error code of this error
+        """
+
+class CredentialExpired(CredentialError):
+    r"""
+    The credential is older than the accepted age range
+    """
+
+    ...
+
+class CredentialInfo:
+    r"""
+    A read-only view of a credential, returned by `UserState.get_credential_info`
+
+    It shows when the credential was registered and how many measurements were
+    submitted with it. The nym id is never exposed, since it links the
+    measurements together.
+    """
+
+    @property
+    def age(self) -> datetime.date:
+        r"""
+        Day of the registration
+        """
+
+    @property
+    def age_day(self) -> builtins.int:
+        r"""
+        Day of the registration, as a Julian day like `ServerState.today()`
+        """
+
+    @property
+    def measurement_count(self) -> builtins.int:
+        r"""
+        Number of measurements submitted with this credential
+        """
+
+    @property
+    def public_parameters_fingerprint(self) -> builtins.str:
+        r"""
+        Hex fingerprint of the public parameters the credential was issued under.
+        When it differs from the server's, the credential needs an update
+        """
+
+    def days_since_registration(
+        self, today: typing.Optional[builtins.int] = None
+    ) -> builtins.int:
+        r"""
+        Days since the registration, as of `today` (defaults to `ServerState.today()`)
+        """
+
+    def expires(self, policy: SubmitPolicy) -> datetime.date:
+        r"""
+        Last day `policy` accepts submissions made with this credential
+        """
+
+    def is_expired(
+        self, policy: SubmitPolicy, today: typing.Optional[builtins.int] = None
+    ) -> builtins.bool:
+        r"""
+        Whether `policy` refuses submissions made with this credential as of
+        `today` (defaults to `ServerState.today()`)
+        """
+
+    def __repr__(self) -> builtins.str: ...
+
+class CredentialMismatch(CredentialError):
+    r"""
+    The supplied credentials do not match
+    """
+
     ...
 
 class DeserializationFailed(builtins.Exception):
@@ -18,6 +140,55 @@ class DeserializationFailed(builtins.Exception):
     An error trying to deserialize a base64-encoded payload
     """
 
+    @property
+    def code(self) -> ErrorCode:
+        r"""
+        The stable error code of this error
+        """
+
+class InvalidField(CredentialError):
+    r"""
+    A credential attribute is missing or out of range
+    """
+
+    @property
+    def field(self) -> builtins.str:
+        r"""
+        Name of the offending credential attribute
+        """
+
+    @property
+    def reason(self) -> builtins.str:
+        r"""
+        Why the attribute was rejected
+        """
+
+class MalformedMessage(DeserializationFailed):
+    r"""
+    The message could not be decoded
+    """
+
+    ...
+
+class MissingCredential(CredentialError):
+    r"""
+    The operation needs a credential and none is held
+    """
+
+    ...
+
+class NotYetEligible(CredentialError):
+    r"""
+    The credential is too recent, or has too few measurements, for the operation
+    """
+
+    ...
+
+class ProofRejected(ProtocolError):
+    r"""
+    A client or issuer proof did not verify
+    """
+
     ...
 
 class ProtocolError(builtins.Exception):
@@ -25,9 +196,45 @@ class ProtocolError(builtins.Exception):
     An error performing the protocol
     """
 
-    ...
+    @property
+    def code(self) -> ErrorCode:
+        r"""
+        The stable error code of this error
+        """
+
+class RegistrationRequest:
+    r"""
+    A registration request waiting for the server response
+
+    Send `request` (or `request_bytes`) to the server and pass this object to
+    `UserState.handle_registration_response` along with the response.
+    `to_bytes` and `from_bytes` keep it across restarts.
+    """
+
+    @property
+    def request(self) -> str: ...
+    @property
+    def request_bytes(self) -> bytes: ...
+    def to_bytes(self) -> bytes: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> RegistrationRequest: ...
+    def __reduce__(self) -> tuple[typing.Any, tuple[bytes]]: ...
 
 class ServerState:
+    @property
+    def legacy_requests(self) -> builtins.bool:
+        r"""
+        Accept the bare requests of the clients predating the wire envelope,
+        instead of enveloped ones, and reply to them the same way. False by default
+        """
+
+    @legacy_requests.setter
+    def legacy_requests(self, value: builtins.bool) -> None:
+        r"""
+        Accept the bare requests of the clients predating the wire envelope,
+        instead of enveloped ones, and reply to them the same way. False by default
+        """
+
     def __new__(cls) -> ServerState: ...
     @staticmethod
     def from_creds(public_parameters: str, secret_key: str) -> ServerState:
@@ -37,9 +244,41 @@ class ServerState:
         state when needed
         """
 
+    @staticmethod
+    def from_creds_bytes(public_parameters: bytes, secret_key: bytes) -> ServerState:
+        r"""
+        Same as `from_creds`, taking the keys as returned by `get_public_parameters_bytes`
+        and `get_secret_key_bytes`
+        """
+
+    def to_bytes(self) -> bytes:
+        r"""
+        Export the whole server state, keys and `legacy_requests` included, to be
+        restored with `from_bytes`
+        """
+
+    @staticmethod
+    def from_bytes(data: bytes) -> ServerState: ...
+    def __reduce__(self) -> tuple[typing.Any, tuple[bytes]]: ...
     def get_secret_key(self) -> str: ...
+    def get_secret_key_bytes(self) -> bytes: ...
     def get_public_parameters(self) -> str: ...
+    def get_public_parameters_bytes(self) -> bytes: ...
     def handle_registration_request(self, registration_request: str) -> str: ...
+    def handle_registration_request_bytes(
+        self, registration_request: bytes
+    ) -> bytes: ...
+    def handle_registration_request_async(
+        self, registration_request: str
+    ) -> asyncio.Future[builtins.str]:
+        r"""
+        Awaitable version of `handle_registration_request`. The request is handled
+        on a Rust thread pool, and the returned future resolves with the response
+        """
+
+    def handle_registration_request_bytes_async(
+        self, registration_request: bytes
+    ) -> asyncio.Future[builtins.bytes]: ...
     @staticmethod
     def today() -> builtins.int: ...
     def handle_submit_request(
@@ -51,6 +290,7 @@ class ServerState:
         measurement_hash: str,
         age_range: tuple[builtins.int, builtins.int],
         min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
     ) -> str:
         r"""
         Handle a submit request from the client.
@@ -63,9 +303,89 @@ class ServerState:
 
         This validation is done in backend server validating measurements.
 
-        # Panics
+        `age_range` and `[min_measurement_count, max_measurement_count)` are the
+        half-open ranges the credential must fall in, with no upper bound on the
+        count when `max_measurement_count` is left out. They must be the ones the
+        client used, see `SubmitPolicy.submit_args`.
+
+        Raises `BadDomain` if `probe_cc` or `probe_asn` are not valid, and
+        `ValueError` if a range is empty.
+        """
+
+    def handle_submit_request_bytes(
+        self,
+        nym: bytes,
+        request: bytes,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> bytes:
+        r"""
+        Same as `handle_submit_request`, taking the nym, request and measurement hash
+        as bytes and returning the response as bytes
+        """
 
-        The arguments `probe_cc` and `probe_asn` will cause a panic (in debug mode) if not valid.
+    def handle_submit_request_async(
+        self,
+        nym: str,
+        request: str,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: str,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> asyncio.Future[builtins.str]:
+        r"""
+        Awaitable version of `handle_submit_request`. The request is verified on a
+        Rust thread pool, and the returned future resolves with the response or
+        the exception `handle_submit_request` would raise.
+        """
+
+    def handle_submit_request_bytes_async(
+        self,
+        nym: bytes,
+        request: bytes,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> asyncio.Future[builtins.bytes]: ...
+    def handle_submit_request_with_outcome(
+        self,
+        nym: str,
+        request: str,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: str,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> tuple[str, SubmitOutcome]:
+        r"""
+        Same as `handle_submit_request`, also returning the `SubmitOutcome`
+        describing what was verified, to store next to the measurement
+        """
+
+    def handle_submit_request_with_outcome_bytes(
+        self,
+        nym: bytes,
+        request: bytes,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> tuple[bytes, SubmitOutcome]:
+        r"""
+        Same as `handle_submit_request_with_outcome`, taking the nym, request and
+        measurement hash as bytes and returning the response as bytes
         """
 
     def handle_submit_request_with_hash(
@@ -77,6 +397,7 @@ class ServerState:
         measurement: str,
         age_range: tuple[builtins.int, builtins.int],
         min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
     ) -> str:
         r"""
         Performs a submission request computing the hash from the input
@@ -84,29 +405,312 @@ class ServerState:
         [submit_measurement_hash] function.
         """
 
+    def handle_submit_request_with_hash_bytes(
+        self,
+        nym: bytes,
+        request: bytes,
+        probe_cc: str,
+        probe_asn: str,
+        measurement: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> bytes:
+        r"""
+        Same as `handle_submit_request_with_hash`, taking the nym, request and
+        measurement body as bytes and returning the response as bytes
+        """
+
+    def handle_submit_request_with_hash_async(
+        self,
+        nym: str,
+        request: str,
+        probe_cc: str,
+        probe_asn: str,
+        measurement: str,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> asyncio.Future[builtins.str]:
+        r"""
+        Awaitable version of `handle_submit_request_with_hash`. Both the hash and
+        the verification are computed on a Rust thread pool
+        """
+
+    def handle_submit_request_with_hash_bytes_async(
+        self,
+        nym: bytes,
+        request: bytes,
+        probe_cc: str,
+        probe_asn: str,
+        measurement: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> asyncio.Future[builtins.bytes]: ...
+    def handle_submit_requests_batch(
+        self,
+        requests: typing.Sequence[tuple[str, str, str, str, str]],
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> list[builtins.str | builtins.Exception]:
+        r"""
+        Verify many submissions at once, in parallel across CPU cores and without
+        holding the GIL
+
+        Each item is `(nym, request, probe_cc, probe_asn, measurement_hash)`, as
+        for `handle_submit_request`, and all of them are checked against the same
+        ranges. Returns a list in the same order holding either the response or
+        the exception that `handle_submit_request` would have raised for the item.
+
+        Raises `ValueError` if a range is empty.
+        """
+
+    def handle_submit_requests_batch_bytes(
+        self,
+        requests: typing.Sequence[tuple[bytes, bytes, str, str, bytes]],
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> list[builtins.bytes | builtins.Exception]:
+        r"""
+        Same as `handle_submit_requests_batch`, with the nyms, requests and
+        measurement hashes as bytes and the responses as bytes
+        """
+
     def handle_update_request(
         self, req: str, old_public_params: str, old_secret_key: str
     ) -> str: ...
+    def handle_update_request_bytes(
+        self, req: bytes, old_public_params: bytes, old_secret_key: bytes
+    ) -> bytes: ...
+    def handle_update_request_async(
+        self, req: str, old_public_params: str, old_secret_key: str
+    ) -> asyncio.Future[builtins.str]:
+        r"""
+        Awaitable version of `handle_update_request`, handled on a Rust thread pool
+        """
+
+    def handle_update_request_bytes_async(
+        self, req: bytes, old_public_params: bytes, old_secret_key: bytes
+    ) -> asyncio.Future[builtins.bytes]: ...
+
+class SubmitOutcome:
+    r"""
+    What the server verified when accepting a submission, returned by
+    `ServerState.handle_submit_request_with_outcome`
+
+    It is meant to be stored next to the measurement as provenance metadata.
+    """
+
+    @property
+    def probe_id(self) -> bytes:
+        r"""
+        The 32-byte probe ID, the nym the submission was made with
+        """
+
+    @property
+    def probe_cc(self) -> builtins.str: ...
+    @property
+    def probe_asn(self) -> builtins.str: ...
+    @property
+    def measurement_hash(self) -> bytes:
+        r"""
+        The 32-byte hash of the measurement
+        """
+
+    @property
+    def age_range(self) -> tuple[builtins.int, builtins.int]:
+        r"""
+        The credential was registered in this half-open range of Julian days
+        """
+
+    @property
+    def measurement_count_range(self) -> tuple[builtins.int, builtins.int]:
+        r"""
+        The credential had submitted a number of measurements in this half-open
+        range
+        """
+
+    @property
+    def session_id(self) -> bytes:
+        r"""
+        The 32-byte proof session ID, derived from the measurement hash
+        """
+
+    @property
+    def public_parameters_fingerprint(self) -> builtins.str:
+        r"""
+        Hex fingerprint of the public parameters that verified the credential,
+        as in `CredentialInfo.public_parameters_fingerprint`
+        """
+
+    def __repr__(self) -> builtins.str: ...
+
+class SubmitPolicy:
+    r"""
+    The limits a server puts on submissions
+
+    Credentials registered more than `max_age_days` ago are refused, as are
+    credentials with a measurement count outside of
+    `[min_measurement_count, max_measurement_count)`. `submit_args` turns the
+    policy into the last arguments of `UserState.make_submit_request` and
+    `ServerState.handle_submit_request`, so both sides agree on them:
+
+    ```python
+    policy = SubmitPolicy(max_age_days=30, max_measurement_count=1000)
+    pending = user.make_submit_request(cc, asn, hash, *policy.submit_args())
+    ```
+    """
+
+    @property
+    def max_age_days(self) -> builtins.int: ...
+    @property
+    def min_measurement_count(self) -> builtins.int: ...
+    @property
+    def max_measurement_count(self) -> typing.Optional[builtins.int]: ...
+    def __eq__(self, other: builtins.object) -> builtins.bool: ...
+    def __new__(
+        cls,
+        max_age_days: builtins.int = 30,
+        min_measurement_count: builtins.int = 0,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> SubmitPolicy:
+        r"""
+        Raises `ValueError` if `min_measurement_count` is not lower than
+        `max_measurement_count`
+        """
+
+    def age_range(
+        self, today: typing.Optional[builtins.int] = None
+    ) -> tuple[builtins.int, builtins.int]:
+        r"""
+        The `age_range` accepted on `today`, which defaults to `ServerState.today()`:
+        credentials registered between `max_age_days` ago and today
+        """
+
+    def measurement_count_range(self) -> tuple[builtins.int, builtins.int]:
+        r"""
+        The accepted measurement counts, as a half-open `(min, max)` range
+        """
+
+    def submit_args(
+        self, today: typing.Optional[builtins.int] = None
+    ) -> tuple[
+        tuple[builtins.int, builtins.int], builtins.int, typing.Optional[builtins.int]
+    ]:
+        r"""
+        `(age_range, min_measurement_count, max_measurement_count)` for the submit
+        methods of `UserState` and `ServerState`
+        """
+
+    def __repr__(self) -> builtins.str: ...
 
 class SubmitRequest:
+    r"""
+    A measurement submission waiting for the server response
+
+    Send `nym` and `request` (or their `_bytes` versions) to the server and pass
+    this object to `UserState.handle_submit_response` along with the response.
+    `to_bytes` and `from_bytes` keep it across restarts.
+    """
+
     @property
     def nym(self) -> str: ...
     @property
     def request(self) -> str: ...
+    @property
+    def nym_bytes(self) -> bytes: ...
+    @property
+    def request_bytes(self) -> bytes: ...
+    def to_bytes(self) -> bytes: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> SubmitRequest: ...
+    def __reduce__(self) -> tuple[typing.Any, tuple[bytes]]: ...
+
+class TimeThresholdNotMet(NotYetEligible):
+    r"""
+    The credential is too recent for the accepted age range
+    """
+
+    @property
+    def days_remaining(self) -> builtins.int:
+        r"""
+        Days from the end of the accepted age range to the registration day
+        """
+
+class UnknownPublicParameters(DeserializationFailed):
+    r"""
+    The message was produced for public parameters this side does not hold
+    """
+
+    ...
+
+class UpdateRequest:
+    r"""
+    A credential update request waiting for the server response
+
+    Send `request` (or `request_bytes`) to the server and pass this object to
+    `UserState.handle_credential_update_response` along with the response.
+    `to_bytes` and `from_bytes` keep it across restarts.
+    """
+
+    @property
+    def request(self) -> str: ...
+    @property
+    def request_bytes(self) -> bytes: ...
+    def to_bytes(self) -> bytes: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> UpdateRequest: ...
+    def __reduce__(self) -> tuple[typing.Any, tuple[bytes]]: ...
 
 class UserState:
     def __new__(cls, public_params: str) -> UserState: ...
+    @staticmethod
+    def from_public_params_bytes(public_params: bytes) -> UserState:
+        r"""
+        Same as the constructor, taking the public parameters as returned by
+        `ServerState.get_public_parameters_bytes`
+        """
+
+    def to_bytes(self) -> bytes:
+        r"""
+        Export the public parameters and credential, to be restored with `from_bytes`.
+        Pending requests are exported separately, with their own `to_bytes`
+        """
+
+    @staticmethod
+    def from_bytes(data: bytes) -> UserState: ...
+    def __reduce__(self) -> tuple[typing.Any, tuple[bytes]]: ...
     def get_credential(self) -> typing.Optional[str]: ...
+    def get_credential_bytes(self) -> typing.Optional[bytes]: ...
+    def get_credential_info(self) -> typing.Optional[CredentialInfo]:
+        r"""
+        What can be shown about the current credential, `None` before registration
+        """
+
     def set_public_params(self, new_public_params: str) -> None: ...
-    def make_registration_request(self) -> str: ...
-    def handle_registration_response(self, resp: str) -> None:
+    def set_public_params_bytes(self, new_public_params: bytes) -> None: ...
+    def make_registration_request(self) -> RegistrationRequest:
+        r"""
+        Make a registration request. Send its `request` to the server, and pass the
+        returned object along with the server response to `handle_registration_response`
+        """
+
+    def handle_registration_response(
+        self, resp: str, pending: RegistrationRequest
+    ) -> None:
         r"""
         Handle a registration response sent by the server, updating your credentials
 
-        Note that this function will only work if you previously called
-        `make_registration_request`
+        `pending` is the `RegistrationRequest` the response answers. Each pending
+        request can only be handled once
         """
 
+    def handle_registration_response_bytes(
+        self, resp: bytes, pending: RegistrationRequest
+    ) -> None: ...
     def make_submit_request(
         self,
         probe_cc: str,
@@ -114,6 +718,7 @@ class UserState:
         measurement_hash: str,
         age_range: tuple[builtins.int, builtins.int],
         min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
     ) -> SubmitRequest:
         r"""
         Make a submission request, to send a measurement to the server
@@ -126,9 +731,24 @@ class UserState:
 
         This validation is done in backend server validating measurements.
 
-        # Panics
+        `age_range` and `[min_measurement_count, max_measurement_count)` must be the
+        ranges the server checks, see `SubmitPolicy.submit_args`.
+
+        Raises `BadDomain` if `probe_cc` or `probe_asn` are not valid, and
+        `ValueError` if a range is empty.
+        """
 
-        The arguments `probe_cc` and `probe_asn` will cause a panic (in debug mode) if not valid.
+    def make_submit_request_bytes(
+        self,
+        probe_cc: str,
+        probe_asn: str,
+        measurement_hash: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> SubmitRequest:
+        r"""
+        Same as `make_submit_request`, taking the raw 32-byte measurement hash
         """
 
     def make_submit_request_with_hash(
@@ -138,32 +758,101 @@ class UserState:
         measurement: str,
         age_range: tuple[builtins.int, builtins.int],
         min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
     ) -> SubmitRequest:
         r"""
         Creates a submit request computing the hash from the input measurement.
         Computes the hash internally using the [submit_measurement_hash] function
         """
 
-    def handle_submit_response(self, response: str) -> None:
+    def make_submit_request_with_hash_bytes(
+        self,
+        probe_cc: str,
+        probe_asn: str,
+        measurement: bytes,
+        age_range: tuple[builtins.int, builtins.int],
+        min_measurement_count: builtins.int,
+        max_measurement_count: typing.Optional[builtins.int] = None,
+    ) -> SubmitRequest:
+        r"""
+        Same as `make_submit_request_with_hash`, taking the measurement body as bytes
+        """
+
+    def handle_submit_response(self, response: str, pending: SubmitRequest) -> None:
         r"""
         Handle a submit response sent by the server, updating your credentials
 
-        Note that this function will only work if you previously called
-        `make_submit_request`
+        `pending` is the `SubmitRequest` the response answers. Several submissions
+        can be in flight at once, and each response replaces the credential with one
+        derived from the credential its request was made with
         """
 
-    def make_credential_update_request(self) -> str:
+    def handle_submit_response_bytes(
+        self, response: bytes, pending: SubmitRequest
+    ) -> None: ...
+    def make_credential_update_request(self) -> UpdateRequest:
         r"""
         Creates a credential update request to be sent to the server.
         """
 
-    def handle_credential_update_response(self, resp: str) -> None:
+    def handle_credential_update_response(
+        self, resp: str, pending: UpdateRequest
+    ) -> None:
         r"""
         Handles the credential update response sent by the server, updating your credentials.
 
-        This function only works if you previosly called `make_credential_update_request`
+        `pending` is the `UpdateRequest` the response answers.
         """
 
+    def handle_credential_update_response_bytes(
+        self, resp: bytes, pending: UpdateRequest
+    ) -> None: ...
+
+class ErrorCode(Enum):
+    r"""
+    Stable error code carried by every exception raised by this module as its `code`
+    attribute. Mirrors `ooniauth_core::errors::ErrorCode`
+    """
+
+    CredentialExpired = ...
+    NotYetEligible = ...
+    CountExceeded = ...
+    BadDomain = ...
+    UnknownPublicParameters = ...
+    MalformedMessage = ...
+    ProofRejected = ...
+    InvalidField = ...
+    CredentialMismatch = ...
+    MissingCredential = ...
+    Internal = ...
+
+    def __str__(self) -> builtins.str:
+        r"""
+        Stable snake_case name of the code, e.g. `credential_expired`
+        """
+
+def disable_logging() -> None:
+    r"""
+    Stop forwarding logs enabled by `enable_logging`
+    """
+
+def enable_logging(
+    level: builtins.int = 20,
+    targets: typing.Optional[typing.Sequence[builtins.str]] = None,
+) -> None:
+    r"""
+    Forward the logs of the Rust code to the `logging` module
+
+    Records are sent to the logger named after the Rust module, e.g.
+    `ooniauth_core.submit`, and spans log their duration when they close, e.g.
+    `handle_submit took 4.211ms`. Only records at `level` or above are
+    forwarded, and only for the loggers in `targets` (and their children) when
+    given. `TRACE` records use level 5. Calling it again changes the settings.
+
+    Raises `RuntimeError` if another `tracing` subscriber is already installed
+    in the process.
+    """
+
 def get_protocol_version() -> builtins.str:
     r"""
     Returns the version of the `ooniauth-core`, the actual protocol implementation.
@@ -176,3 +865,9 @@ def submit_measurement_hash(measurement: builtins.str) -> builtins.str:
     Returns a base64-encoded 32-byte hash suitable for use with
     `UserState.make_submit_request` and `ServerState.handle_submit_request`.
     """
+
+def submit_measurement_hash_bytes(measurement: bytes) -> bytes:
+    r"""
+    Same as `submit_measurement_hash`, taking the measurement body as bytes and
+    returning the raw 32-byte hash.
+    """
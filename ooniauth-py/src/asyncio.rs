@@ -0,0 +1,60 @@
+//! Awaitables resolved from the rayon thread pool, for the `*_async` methods
+use pyo3::prelude::*;
+use pyo3::types::{PyCFunction, PyTuple};
+
+use crate::exceptions::OoniResult;
+
+/// Run `work` on the rayon thread pool and return an `asyncio.Future` of the
+/// running event loop, resolved with `convert` of its result or with the
+/// exception of its error
+///
+/// `asyncio` futures are not thread-safe, so the worker thread only hands the
+/// result over to the loop with `call_soon_threadsafe`. Raises `RuntimeError`
+/// if there is no running event loop.
+pub fn spawn_future<'py, T: Send + 'static>(
+    py: Python<'py>,
+    work: impl FnOnce() -> OoniResult<T> + Send + 'static,
+    convert: impl FnOnce(Python<'_>, T) -> Py<PyAny> + Send + 'static,
+) -> PyResult<Bound<'py, PyAny>> {
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let future = event_loop.call_method0("create_future")?;
+
+    let event_loop = event_loop.unbind();
+    let result_future = future.clone().unbind();
+    rayon::spawn(move || {
+        let result = work();
+        Python::attach(|py| {
+            let (value, failed) = match result {
+                Ok(value) => (convert(py, value), false),
+                Err(e) => (PyErr::from(e).into_value(py).into_any(), true),
+            };
+            let resolve = PyCFunction::new_closure(
+                py,
+                None,
+                None,
+                move |args: &Bound<'_, PyTuple>, _kwargs| -> PyResult<()> {
+                    let future = result_future.bind(args.py());
+                    // The awaiting task may have been cancelled meanwhile
+                    if future.call_method0("done")?.is_truthy()? {
+                        return Ok(());
+                    }
+                    let method = if failed {
+                        "set_exception"
+                    } else {
+                        "set_result"
+                    };
+                    future.call_method1(method, (args.get_item(0)?,))?;
+                    Ok(())
+                },
+            );
+            // Fails only once the loop is closed, and then nobody is waiting
+            let scheduled = resolve.and_then(|resolve| {
+                event_loop.call_method1(py, "call_soon_threadsafe", (resolve, value))
+            });
+            if let Err(e) = scheduled {
+                e.write_unraisable(py, None);
+            }
+        });
+    });
+    Ok(future)
+}
@@ -0,0 +1,96 @@
+use ooniauth_core as ooni;
+use pyo3::prelude::*;
+use pyo3::types::PyDate;
+use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pymethods};
+
+use crate::{exceptions::OoniResult, OoniErr, SubmitPolicy};
+
+/// A read-only view of a credential, returned by `UserState.get_credential_info`
+///
+/// It shows when the credential was registered and how many measurements were
+/// submitted with it. The nym id is never exposed, since it links the
+/// measurements together.
+#[gen_stub_pyclass]
+#[pyclass(frozen)]
+pub struct CredentialInfo {
+    pub info: ooni::CredentialInfo,
+}
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl CredentialInfo {
+    /// Day of the registration
+    #[getter]
+    pub fn age<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDate>> {
+        julian_day_to_pydate(py, self.info.age)
+    }
+
+    /// Day of the registration, as a Julian day like `ServerState.today()`
+    #[getter]
+    pub fn age_day(&self) -> u32 {
+        self.info.age
+    }
+
+    /// Number of measurements submitted with this credential
+    #[getter]
+    pub fn measurement_count(&self) -> u32 {
+        self.info.measurement_count
+    }
+
+    /// Hex fingerprint of the public parameters the credential was issued under.
+    /// When it differs from the server's, the credential needs an update
+    #[getter]
+    pub fn public_parameters_fingerprint(&self) -> String {
+        hex::encode(self.info.key_id)
+    }
+
+    /// Days since the registration, as of `today` (defaults to `ServerState.today()`)
+    #[pyo3(signature = (today=None))]
+    pub fn days_since_registration(&self, today: Option<u32>) -> u32 {
+        let today = today.unwrap_or_else(ooni::ServerState::today);
+        today.saturating_sub(self.info.age)
+    }
+
+    /// Last day `policy` accepts submissions made with this credential
+    pub fn expires<'py>(
+        &self,
+        py: Python<'py>,
+        policy: &SubmitPolicy,
+    ) -> PyResult<Bound<'py, PyDate>> {
+        let last_day = self
+            .info
+            .age
+            .checked_add(policy.policy.max_age_days)
+            .ok_or_else(|| OoniErr::InvalidArgument {
+                reason: format!("max_age_days ({}) is too large", policy.policy.max_age_days),
+            })?;
+        julian_day_to_pydate(py, last_day)
+    }
+
+    /// Whether `policy` refuses submissions made with this credential as of
+    /// `today` (defaults to `ServerState.today()`)
+    #[pyo3(signature = (policy, today=None))]
+    pub fn is_expired(&self, policy: &SubmitPolicy, today: Option<u32>) -> OoniResult<bool> {
+        let (start, _) = policy.age_range(today)?;
+        Ok(self.info.age < start)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CredentialInfo(age_day={}, measurement_count={}, public_parameters_fingerprint='{}')",
+            self.info.age,
+            self.info.measurement_count,
+            self.public_parameters_fingerprint()
+        )
+    }
+}
+
+fn julian_day_to_pydate(py: Python<'_>, day: u32) -> PyResult<Bound<'_, PyDate>> {
+    let date = i32::try_from(day)
+        .ok()
+        .and_then(|day| time::Date::from_julian_day(day).ok())
+        .ok_or_else(|| OoniErr::InvalidArgument {
+            reason: format!("day {day} is out of the supported date range"),
+        })?;
+    PyDate::new(py, date.year(), date.month().into(), date.day())
+}
@@ -1,8 +1,10 @@
 use cmz::CMZError;
-use ooniauth_core::errors;
-use pyo3::exceptions::PyException;
-use pyo3::PyErr;
-use pyo3_stub_gen::create_exception;
+use ooniauth_core::{bindings, errors};
+use pyo3::exceptions::{PyBaseException, PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3_stub_gen::derive::{gen_stub_pyclass_enum, gen_stub_pymethods};
+use pyo3_stub_gen::type_info::{MemberInfo, PyClassInfo, PyMethodsInfo};
+use pyo3_stub_gen::{create_exception, PyStubType, TypeInfo};
 use thiserror::Error;
 
 // Python excepions: This is what the user sees from the python side when running into an error
@@ -27,7 +29,6 @@ create_exception! {
     "An error trying to deserialize a base64-encoded payload"
 }
 
-// TODO Q: should I create a new python exception per variant of errors::CredentialError?
 create_exception! {
     ooniauth-py,
     CredentialError,
@@ -35,6 +36,218 @@ create_exception! {
     "An authentication error"
 }
 
+/// Stable error code carried by every exception raised by this module as its `code`
+/// attribute. Mirrors `ooniauth_core::errors::ErrorCode`
+#[gen_stub_pyclass_enum]
+#[pyclass(eq, eq_int, frozen, hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    CredentialExpired = 1,
+    NotYetEligible = 2,
+    CountExceeded = 3,
+    BadDomain = 4,
+    UnknownPublicParameters = 5,
+    MalformedMessage = 6,
+    ProofRejected = 7,
+    InvalidField = 8,
+    CredentialMismatch = 9,
+    MissingCredential = 10,
+    Internal = 11,
+}
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl ErrorCode {
+    /// Stable snake_case name of the code, e.g. `credential_expired`
+    fn __str__(&self) -> &'static str {
+        errors::ErrorCode::from(*self).as_str()
+    }
+}
+
+impl From<errors::ErrorCode> for ErrorCode {
+    fn from(value: errors::ErrorCode) -> Self {
+        match value {
+            errors::ErrorCode::CredentialExpired => ErrorCode::CredentialExpired,
+            errors::ErrorCode::NotYetEligible => ErrorCode::NotYetEligible,
+            errors::ErrorCode::CountExceeded => ErrorCode::CountExceeded,
+            errors::ErrorCode::BadDomain => ErrorCode::BadDomain,
+            errors::ErrorCode::UnknownPublicParameters => ErrorCode::UnknownPublicParameters,
+            errors::ErrorCode::MalformedMessage => ErrorCode::MalformedMessage,
+            errors::ErrorCode::ProofRejected => ErrorCode::ProofRejected,
+            errors::ErrorCode::InvalidField => ErrorCode::InvalidField,
+            errors::ErrorCode::CredentialMismatch => ErrorCode::CredentialMismatch,
+            errors::ErrorCode::MissingCredential => ErrorCode::MissingCredential,
+            errors::ErrorCode::Internal => ErrorCode::Internal,
+        }
+    }
+}
+
+impl From<ErrorCode> for errors::ErrorCode {
+    fn from(value: ErrorCode) -> Self {
+        errors::ErrorCode::try_from(value as u32).expect("ErrorCode mirrors the core codes")
+    }
+}
+
+// `pyo3_stub_gen::create_exception` always refers to the base as a builtin, which is wrong
+// for subclasses of our own exceptions. This does the same but names the base as a class of
+// this module, and lets the stubs list the attributes we set on raised instances
+macro_rules! create_subexception {
+    (
+        $module: expr,
+        $name: ident,
+        $base: ident,
+        $doc: expr
+        $(, $attr: ident: $attr_type: ty = $attr_doc: literal)* $(,)?
+    ) => {
+        ::pyo3::create_exception!($module, $name, $base, $doc);
+
+        impl PyStubType for $name {
+            fn type_output() -> TypeInfo {
+                TypeInfo::unqualified(stringify!($name))
+            }
+        }
+
+        pyo3_stub_gen::inventory::submit! {
+            PyClassInfo {
+                pyclass_name: stringify!($name),
+                struct_id: std::any::TypeId::of::<$name>,
+                getters: &[$(MemberInfo {
+                    name: stringify!($attr),
+                    r#type: <$attr_type as PyStubType>::type_output,
+                    doc: $attr_doc,
+                    default: None,
+                    deprecated: None,
+                }),*],
+                setters: &[],
+                module: Some(stringify!($module)),
+                doc: $doc,
+                bases: &[|| TypeInfo::unqualified(stringify!($base))],
+                has_eq: false,
+                has_ord: false,
+                has_hash: false,
+                has_str: false,
+            }
+        }
+    };
+}
+
+// Every exception of this module has a `code` attribute, make the stubs say so on the roots
+macro_rules! stub_code_attribute {
+    ($($name: ident),*) => {
+        $(
+            pyo3_stub_gen::inventory::submit! {
+                PyMethodsInfo {
+                    struct_id: std::any::TypeId::of::<$name>,
+                    attrs: &[],
+                    getters: &[MemberInfo {
+                        name: "code",
+                        r#type: ErrorCode::type_output,
+                        doc: "The stable error code of this error",
+                        default: None,
+                        deprecated: None,
+                    }],
+                    setters: &[],
+                    methods: &[],
+                }
+            }
+        )*
+    };
+}
+
+stub_code_attribute!(ProtocolError, DeserializationFailed, CredentialError);
+
+create_subexception! {
+    ooniauth-py,
+    CredentialExpired,
+    CredentialError,
+    "The credential is older than the accepted age range"
+}
+
+create_subexception! {
+    ooniauth-py,
+    NotYetEligible,
+    CredentialError,
+    "The credential is too recent, or has too few measurements, for the operation"
+}
+
+create_subexception! {
+    ooniauth-py,
+    TimeThresholdNotMet,
+    NotYetEligible,
+    "The credential is too recent for the accepted age range",
+    days_remaining: u32 = "Days from the end of the accepted age range to the registration day",
+}
+
+create_subexception! {
+    ooniauth-py,
+    CountBelowMinimum,
+    NotYetEligible,
+    "The credential has fewer measurements than required",
+    count: u32 = "Measurement count of the credential",
+    min: u32 = "Minimum measurement count accepted",
+}
+
+create_subexception! {
+    ooniauth-py,
+    CountExceeded,
+    CredentialError,
+    "The credential has reached the maximum measurement count",
+    count: u32 = "Measurement count of the credential",
+    max: u32 = "First measurement count no longer accepted",
+}
+
+create_subexception! {
+    ooniauth-py,
+    BadDomain,
+    CredentialError,
+    "The probe country code or ASN is not well formed",
+    reason: String = "Which of the two is invalid, and why",
+}
+
+create_subexception! {
+    ooniauth-py,
+    InvalidField,
+    CredentialError,
+    "A credential attribute is missing or out of range",
+    field: String = "Name of the offending credential attribute",
+    reason: String = "Why the attribute was rejected",
+}
+
+create_subexception! {
+    ooniauth-py,
+    CredentialMismatch,
+    CredentialError,
+    "The supplied credentials do not match"
+}
+
+create_subexception! {
+    ooniauth-py,
+    MissingCredential,
+    CredentialError,
+    "The operation needs a credential and none is held"
+}
+
+create_subexception! {
+    ooniauth-py,
+    ProofRejected,
+    ProtocolError,
+    "A client or issuer proof did not verify"
+}
+
+create_subexception! {
+    ooniauth-py,
+    UnknownPublicParameters,
+    DeserializationFailed,
+    "The message was produced for public parameters this side does not hold"
+}
+
+create_subexception! {
+    ooniauth-py,
+    MalformedMessage,
+    DeserializationFailed,
+    "The message could not be decoded"
+}
+
 // The following errors are useful to map rust errors to their corresponding python exceptions
 // defined above
 
@@ -48,21 +261,125 @@ pub enum OoniErr {
 
     #[error("Deserialization Error: {reason}")]
     DeserializationFailed { reason: String },
+
+    #[error("Deserialization Error: {reason}")]
+    WireError { reason: errors::WireError },
+
+    /// Misuse of the bindings, raised as a plain `ValueError`
+    #[error("{reason}")]
+    InvalidArgument { reason: String },
+}
+
+impl OoniErr {
+    /// The stable error code of this error
+    pub fn code(&self) -> errors::ErrorCode {
+        match self {
+            OoniErr::ProtocolError { reason } => reason.into(),
+            OoniErr::CredentialError { reason } => reason.code(),
+            OoniErr::DeserializationFailed { .. } => errors::ErrorCode::MalformedMessage,
+            OoniErr::WireError { reason } => reason.code(),
+            OoniErr::InvalidArgument { .. } => errors::ErrorCode::Internal,
+        }
+    }
 }
 
 pub type OoniResult<T> = Result<T, OoniErr>;
 
 impl From<OoniErr> for PyErr {
     fn from(value: OoniErr) -> Self {
-        // This function maps from rust error enums to their corresponding python exception
-        match value {
-            OoniErr::ProtocolError { reason } => ProtocolError::new_err(format!("{reason}")),
-            OoniErr::DeserializationFailed { reason } => DeserializationFailed::new_err(reason),
+        // This function maps from rust error enums to their corresponding python exception,
+        // one subclass per error code, refined for the credential errors carrying details
+        if let OoniErr::InvalidArgument { reason } = value {
+            return PyValueError::new_err(reason);
+        }
+        let code = value.code();
+        let message = match &value {
+            OoniErr::ProtocolError { reason } => reason.to_string(),
+            OoniErr::DeserializationFailed { reason } => reason.clone(),
             OoniErr::CredentialError {
                 reason: errors::CredentialError::CMZError(e),
-            } => ProtocolError::new_err(format!("{e}")),
-            OoniErr::CredentialError { reason } => CredentialError::new_err(format!("{reason}")),
-        }
+            } => e.to_string(),
+            OoniErr::CredentialError { reason } => reason.to_string(),
+            OoniErr::WireError { reason } => reason.to_string(),
+            OoniErr::InvalidArgument { reason } => reason.clone(),
+        };
+        let err = match &value {
+            OoniErr::CredentialError {
+                reason: errors::CredentialError::TimeThresholdNotMet(_),
+            } => TimeThresholdNotMet::new_err(message),
+            OoniErr::CredentialError {
+                reason: errors::CredentialError::CountBelowMinimum { .. },
+            } => CountBelowMinimum::new_err(message),
+            _ => match code {
+                errors::ErrorCode::CredentialExpired => CredentialExpired::new_err(message),
+                errors::ErrorCode::NotYetEligible => NotYetEligible::new_err(message),
+                errors::ErrorCode::CountExceeded => CountExceeded::new_err(message),
+                errors::ErrorCode::BadDomain => BadDomain::new_err(message),
+                errors::ErrorCode::UnknownPublicParameters => {
+                    UnknownPublicParameters::new_err(message)
+                }
+                errors::ErrorCode::MalformedMessage => MalformedMessage::new_err(message),
+                errors::ErrorCode::ProofRejected => ProofRejected::new_err(message),
+                errors::ErrorCode::InvalidField => InvalidField::new_err(message),
+                errors::ErrorCode::CredentialMismatch => CredentialMismatch::new_err(message),
+                errors::ErrorCode::MissingCredential => MissingCredential::new_err(message),
+                errors::ErrorCode::Internal => ProtocolError::new_err(message),
+            },
+        };
+        Python::attach(|py| {
+            set_attributes(err.value(py), &value, code).expect("exceptions accept new attributes");
+        });
+        err
+    }
+}
+
+/// Sets `code` and the per-exception details on a raised exception
+fn set_attributes(
+    exc: &Bound<'_, PyBaseException>,
+    value: &OoniErr,
+    code: errors::ErrorCode,
+) -> PyResult<()> {
+    exc.setattr("code", ErrorCode::from(code))?;
+    let reason = match value {
+        OoniErr::ProtocolError { reason } => reason,
+        OoniErr::CredentialError { reason } => match reason {
+            errors::CredentialError::TimeThresholdNotMet(days) => {
+                return exc.setattr("days_remaining", days);
+            }
+            errors::CredentialError::CountBelowMinimum { count, min } => {
+                exc.setattr("count", count)?;
+                return exc.setattr("min", min);
+            }
+            errors::CredentialError::CountExceeded { count, max } => {
+                exc.setattr("count", count)?;
+                return exc.setattr("max", max);
+            }
+            errors::CredentialError::InvalidField(field, why) => {
+                exc.setattr("field", field)?;
+                return exc.setattr("reason", why);
+            }
+            errors::CredentialError::BadDomain(why) => return exc.setattr("reason", why),
+            errors::CredentialError::CMZError(reason) => reason,
+            _ => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+    if let Some(field) = missing_attribute(reason) {
+        exc.setattr("field", field)?;
+        exc.setattr("reason", reason.to_string())?;
+    }
+    Ok(())
+}
+
+/// Name of the credential attribute a [`CMZError`] complains about, if any
+fn missing_attribute(e: &CMZError) -> Option<&'static str> {
+    match e {
+        CMZError::HideAttrMissing(_, attr)
+        | CMZError::RevealAttrMissing(_, attr)
+        | CMZError::ImplicitAttrCliMissing(_, attr)
+        | CMZError::ImplicitAttrIssMissing(_, attr)
+        | CMZError::SetAttrMissing(_, attr) => Some(attr),
+        _ => None,
     }
 }
 
@@ -72,6 +389,28 @@ impl From<CMZError> for OoniErr {
     }
 }
 
+impl From<errors::WireError> for OoniErr {
+    fn from(value: errors::WireError) -> Self {
+        OoniErr::WireError { reason: value }
+    }
+}
+
+impl From<bindings::AlreadyHandled> for OoniErr {
+    fn from(value: bindings::AlreadyHandled) -> Self {
+        OoniErr::InvalidArgument {
+            reason: value.to_string(),
+        }
+    }
+}
+
+impl From<errors::RangeError> for OoniErr {
+    fn from(value: errors::RangeError) -> Self {
+        OoniErr::InvalidArgument {
+            reason: value.to_string(),
+        }
+    }
+}
+
 impl From<errors::CredentialError> for OoniErr {
     fn from(value: errors::CredentialError) -> Self {
         OoniErr::CredentialError { reason: value }
@@ -1,11 +1,20 @@
 use pyo3::prelude::*;
 use pyo3_stub_gen::define_stub_info_gatherer;
 
+mod asyncio;
+pub mod credential;
 pub mod exceptions;
+pub mod logging;
+pub mod outcome;
+pub mod policy;
 pub mod protocol;
 mod utils;
 
+pub use credential::*;
 pub use exceptions::*;
+pub use logging::{disable_logging, enable_logging};
+pub use outcome::*;
+pub use policy::*;
 pub use protocol::*;
 
 pyo3_stub_gen::module_variable!("ooniauth-py", "__version__", &str);
@@ -16,15 +25,45 @@ fn ooniauth_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add_function(wrap_pyfunction!(get_protocol_version, m)?)?;
     m.add_function(wrap_pyfunction!(submit_measurement_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(submit_measurement_hash_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_logging, m)?)?;
     m.add_class::<ServerState>()?;
     m.add_class::<UserState>()?;
+    m.add_class::<RegistrationRequest>()?;
     m.add_class::<SubmitRequest>()?;
+    m.add_class::<UpdateRequest>()?;
+    m.add_class::<SubmitPolicy>()?;
+    m.add_class::<CredentialInfo>()?;
+    m.add_class::<SubmitOutcome>()?;
     m.add("ProtocolError", m.py().get_type::<ProtocolError>())?;
     m.add("CredentialError", m.py().get_type::<CredentialError>())?;
     m.add(
         "DeserializationFailed",
         m.py().get_type::<DeserializationFailed>(),
     )?;
+    m.add_class::<ErrorCode>()?;
+    m.add("CredentialExpired", m.py().get_type::<CredentialExpired>())?;
+    m.add("NotYetEligible", m.py().get_type::<NotYetEligible>())?;
+    m.add(
+        "TimeThresholdNotMet",
+        m.py().get_type::<TimeThresholdNotMet>(),
+    )?;
+    m.add("CountBelowMinimum", m.py().get_type::<CountBelowMinimum>())?;
+    m.add("CountExceeded", m.py().get_type::<CountExceeded>())?;
+    m.add("BadDomain", m.py().get_type::<BadDomain>())?;
+    m.add("InvalidField", m.py().get_type::<InvalidField>())?;
+    m.add(
+        "CredentialMismatch",
+        m.py().get_type::<CredentialMismatch>(),
+    )?;
+    m.add("MissingCredential", m.py().get_type::<MissingCredential>())?;
+    m.add("ProofRejected", m.py().get_type::<ProofRejected>())?;
+    m.add(
+        "UnknownPublicParameters",
+        m.py().get_type::<UnknownPublicParameters>(),
+    )?;
+    m.add("MalformedMessage", m.py().get_type::<MalformedMessage>())?;
     Ok(())
 }
 
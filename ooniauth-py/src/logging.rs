@@ -0,0 +1,266 @@
+//! Opt-in forwarding of the `tracing` events and spans of the Rust code to
+//! Python's `logging` module
+use std::sync::{OnceLock, RwLock};
+
+use ooniauth_core::logging::ForwardingLayer;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3_stub_gen::derive::gen_stub_pyfunction;
+use tracing::{Level, Metadata};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// What `enable_logging` asked for. `None` while logging is disabled
+static CONFIG: RwLock<Option<Config>> = RwLock::new(None);
+
+/// Whether our subscriber could be installed as the global default
+static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+struct Config {
+    /// Lowest Python logging level forwarded
+    level: u32,
+    /// Logger names forwarded, along with their children. `None` forwards all
+    targets: Option<Vec<String>>,
+}
+
+impl Config {
+    fn allows(&self, metadata: &Metadata<'_>) -> bool {
+        if python_level(metadata.level()) < self.level {
+            return false;
+        }
+        let name = logger_name(metadata.target());
+        self.targets.as_ref().is_none_or(|targets| {
+            targets
+                .iter()
+                .any(|target| is_logger_or_child(&name, target))
+        })
+    }
+}
+
+/// Whether `name` is the logger `parent` or one of its children, following the
+/// dotted hierarchy of `logging`
+fn is_logger_or_child(name: &str, parent: &str) -> bool {
+    name.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// The `logging` level matching a `tracing` level. `TRACE` has no standard
+/// counterpart and is mapped to 5, below `logging.DEBUG`
+fn python_level(level: &Level) -> u32 {
+    match *level {
+        Level::ERROR => 40,
+        Level::WARN => 30,
+        Level::INFO => 20,
+        Level::DEBUG => 10,
+        Level::TRACE => 5,
+    }
+}
+
+/// Rust module paths become dotted logger names, e.g. `ooniauth_core.submit`
+fn logger_name(target: &str) -> String {
+    target.replace("::", ".")
+}
+
+fn is_enabled(metadata: &Metadata<'_>) -> bool {
+    CONFIG
+        .read()
+        .map(|config| {
+            config
+                .as_ref()
+                .is_some_and(|config| config.allows(metadata))
+        })
+        .unwrap_or(false)
+}
+
+fn emit(metadata: &Metadata<'_>, message: String) {
+    // Spans are only checked when they open, and the configuration may have
+    // changed since: check again, so that filtered records never wait for the
+    // GIL
+    if !is_enabled(metadata) {
+        return;
+    }
+    let level = python_level(metadata.level());
+    let name = logger_name(metadata.target());
+    // Events are also emitted while the GIL is released, so take it back here
+    Python::attach(|py| {
+        let result = py
+            .import("logging")
+            .and_then(|logging| logging.call_method1("getLogger", (name,)))
+            .and_then(|logger| logger.call_method1("log", (level, message)));
+        if let Err(e) = result {
+            e.write_unraisable(py, None);
+        }
+    });
+}
+
+/// Forward the logs of the Rust code to the `logging` module
+///
+/// Records are sent to the logger named after the Rust module, e.g.
+/// `ooniauth_core.submit`, and spans log their duration when they close, e.g.
+/// `handle_submit took 4.211ms`. Only records at `level` or above are
+/// forwarded, and only for the loggers in `targets` (and their children) when
+/// given. `TRACE` records use level 5. Calling it again changes the settings.
+///
+/// Raises `RuntimeError` if another `tracing` subscriber is already installed
+/// in the process.
+#[gen_stub_pyfunction]
+#[pyfunction]
+#[pyo3(signature = (level=20, targets=None))]
+pub fn enable_logging(level: u32, targets: Option<Vec<String>>) -> PyResult<()> {
+    // The subscriber is installed on first use and stays, disabling only clears
+    // the configuration
+    let installed = *INSTALLED.get_or_init(|| {
+        let subscriber =
+            tracing_subscriber::registry().with(ForwardingLayer::new(is_enabled, emit));
+        tracing::subscriber::set_global_default(subscriber).is_ok()
+    });
+    if !installed {
+        return Err(PyRuntimeError::new_err(
+            "another tracing subscriber is already installed",
+        ));
+    }
+
+    if let Ok(mut config) = CONFIG.write() {
+        *config = Some(Config {
+            level,
+            targets: targets.map(|targets| targets.iter().map(|t| logger_name(t)).collect()),
+        });
+    }
+    tracing::callsite::rebuild_interest_cache();
+    Ok(())
+}
+
+/// Stop forwarding logs enabled by `enable_logging`
+#[gen_stub_pyfunction]
+#[pyfunction]
+pub fn disable_logging() {
+    if let Ok(mut config) = CONFIG.write() {
+        *config = None;
+    }
+    tracing::callsite::rebuild_interest_cache();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use pyo3::types::{PyDict, PyList};
+
+    /// The configuration is global, so the tests changing it run one at a time
+    static LOGGING_TESTS: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_is_logger_or_child() {
+        assert!(is_logger_or_child("ooniauth_core", "ooniauth_core"));
+        assert!(is_logger_or_child("ooniauth_core.submit", "ooniauth_core"));
+        assert!(!is_logger_or_child("ooniauth_core_extra", "ooniauth_core"));
+        assert!(!is_logger_or_child("ooniauth_core", "ooniauth_core.submit"));
+        assert_eq!(logger_name("ooniauth_core::submit"), "ooniauth_core.submit");
+    }
+
+    #[test]
+    fn test_forwards_to_logging() {
+        let _serial = LOGGING_TESTS.lock().unwrap();
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let records = PyList::empty(py);
+            let globals = PyDict::new(py);
+            globals.set_item("records", &records).unwrap();
+            py.run(
+                c"import logging
+class Capture(logging.Handler):
+    def emit(self, record):
+        records.append((record.name, record.levelno, record.getMessage()))
+logger = logging.getLogger('ooniauth_core')
+logger.addHandler(Capture())
+logger.setLevel(1)
+",
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+
+            enable_logging(10, Some(vec!["ooniauth_core::submit".into()])).unwrap();
+            let server = crate::ServerState::new();
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .unwrap();
+            let cc = pyo3::types::PyString::new(py, "VE").unbind();
+            let asn = pyo3::types::PyString::new(py, "AS1234").unbind();
+            let measurement = pyo3::types::PyString::new(py, "measurement").unbind();
+            let today = crate::ServerState::today();
+            let submit = client
+                .make_submit_request_with_hash(
+                    py,
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    measurement.clone_ref(py),
+                    (today - 30, today + 1),
+                    0,
+                    None,
+                )
+                .unwrap();
+            server
+                .handle_submit_request_with_hash(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    cc,
+                    asn,
+                    measurement,
+                    (today - 30, today + 1),
+                    0,
+                    None,
+                )
+                .unwrap();
+            disable_logging();
+
+            let records: Vec<(String, u32, String)> = records.extract().unwrap();
+            let has = |level: u32, prefix: &str| {
+                records.iter().any(|(name, levelno, message)| {
+                    name == "ooniauth_core.submit"
+                        && *levelno == level
+                        && message.starts_with(prefix)
+                })
+            };
+            assert!(has(
+                10,
+                "handle_submit: Submit request verified successfully"
+            ));
+            assert!(has(20, "handle_submit took "));
+            assert!(has(20, "submit_request took "));
+            // Below the requested level
+            assert!(!has(5, ""));
+        });
+    }
+
+    #[test]
+    fn test_filtered_records_skip_the_gil() {
+        let _serial = LOGGING_TESTS.lock().unwrap();
+        pyo3::Python::initialize();
+        enable_logging(10, Some(vec!["ooniauth_core".into()])).unwrap();
+        let span = tracing::info_span!(target: "ooniauth_core::submit", "handle_submit");
+        enable_logging(40, Some(vec!["ooniauth_core".into()])).unwrap();
+
+        // This thread holds the GIL, so the other one would block if it tried
+        // to take it for the filtered records
+        Python::attach(|_py| {
+            let (done, finished) = mpsc::channel();
+            std::thread::spawn(move || {
+                tracing::info!(target: "ooniauth_core::submit", parent: &span, "filtered");
+                drop(span);
+                tracing::error!(target: "other", "filtered by target");
+                let _ = done.send(());
+            });
+            let finished = finished.recv_timeout(Duration::from_secs(10));
+            disable_logging();
+            assert!(finished.is_ok(), "a filtered record waited for the GIL");
+        });
+    }
+}
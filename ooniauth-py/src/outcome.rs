@@ -0,0 +1,80 @@
+use ooniauth_core as ooni;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pymethods};
+
+use crate::utils::to_pybytes;
+
+/// What the server verified when accepting a submission, returned by
+/// `ServerState.handle_submit_request_with_outcome`
+///
+/// It is meant to be stored next to the measurement as provenance metadata.
+#[gen_stub_pyclass]
+#[pyclass(frozen)]
+pub struct SubmitOutcome {
+    pub outcome: ooni::submit::SubmitOutcome,
+}
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl SubmitOutcome {
+    /// The 32-byte probe ID, the nym the submission was made with
+    #[getter]
+    pub fn probe_id(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.outcome.probe_id)
+    }
+
+    #[getter]
+    pub fn probe_cc(&self) -> &str {
+        &self.outcome.probe_cc
+    }
+
+    #[getter]
+    pub fn probe_asn(&self) -> &str {
+        &self.outcome.probe_asn
+    }
+
+    /// The 32-byte hash of the measurement
+    #[getter]
+    pub fn measurement_hash(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.outcome.measurement_hash)
+    }
+
+    /// The credential was registered in this half-open range of Julian days
+    #[getter]
+    pub fn age_range(&self) -> (u32, u32) {
+        (self.outcome.age_range.start, self.outcome.age_range.end)
+    }
+
+    /// The credential had submitted a number of measurements in this half-open
+    /// range
+    #[getter]
+    pub fn measurement_count_range(&self) -> (u32, u32) {
+        let range = &self.outcome.measurement_count_range;
+        (range.start, range.end)
+    }
+
+    /// The 32-byte proof session ID, derived from the measurement hash
+    #[getter]
+    pub fn session_id(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.outcome.session_id)
+    }
+
+    /// Hex fingerprint of the public parameters that verified the credential,
+    /// as in `CredentialInfo.public_parameters_fingerprint`
+    #[getter]
+    pub fn public_parameters_fingerprint(&self) -> String {
+        hex::encode(self.outcome.key_id)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SubmitOutcome(probe_cc='{}', probe_asn='{}', age_range={:?}, measurement_count_range={:?}, public_parameters_fingerprint='{}')",
+            self.outcome.probe_cc,
+            self.outcome.probe_asn,
+            self.age_range(),
+            self.measurement_count_range(),
+            self.public_parameters_fingerprint()
+        )
+    }
+}
@@ -0,0 +1,126 @@
+use ooniauth_core as ooni;
+use pyo3::prelude::*;
+use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pymethods};
+
+use crate::exceptions::OoniResult;
+
+/// The limits a server puts on submissions
+///
+/// Credentials registered more than `max_age_days` ago are refused, as are
+/// credentials with a measurement count outside of
+/// `[min_measurement_count, max_measurement_count)`. `submit_args` turns the
+/// policy into the last arguments of `UserState.make_submit_request` and
+/// `ServerState.handle_submit_request`, so both sides agree on them:
+///
+/// ```python
+/// policy = SubmitPolicy(max_age_days=30, max_measurement_count=1000)
+/// pending = user.make_submit_request(cc, asn, hash, *policy.submit_args())
+/// ```
+#[gen_stub_pyclass]
+#[pyclass(frozen, eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubmitPolicy {
+    pub policy: ooni::submit::SubmitPolicy,
+}
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl SubmitPolicy {
+    /// Raises `ValueError` if `min_measurement_count` is not lower than
+    /// `max_measurement_count`
+    #[new]
+    #[pyo3(signature = (max_age_days=30, min_measurement_count=0, max_measurement_count=None))]
+    pub fn new(
+        max_age_days: u32,
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<Self> {
+        let policy = ooni::submit::SubmitPolicy::new(
+            max_age_days,
+            min_measurement_count,
+            max_measurement_count,
+        )?;
+        Ok(Self { policy })
+    }
+
+    #[getter]
+    pub fn max_age_days(&self) -> u32 {
+        self.policy.max_age_days
+    }
+
+    #[getter]
+    pub fn min_measurement_count(&self) -> u32 {
+        self.policy.min_measurement_count
+    }
+
+    #[getter]
+    pub fn max_measurement_count(&self) -> Option<u32> {
+        self.policy.max_measurement_count
+    }
+
+    /// The `age_range` accepted on `today`, which defaults to `ServerState.today()`:
+    /// credentials registered between `max_age_days` ago and today
+    #[pyo3(signature = (today=None))]
+    pub fn age_range(&self, today: Option<u32>) -> OoniResult<(u32, u32)> {
+        let today = today.unwrap_or_else(ooni::ServerState::today);
+        let range = self.policy.age_range(today)?;
+        Ok((range.start, range.end))
+    }
+
+    /// The accepted measurement counts, as a half-open `(min, max)` range
+    pub fn measurement_count_range(&self) -> OoniResult<(u32, u32)> {
+        let range = self.policy.measurement_count_range()?;
+        Ok((range.start, range.end))
+    }
+
+    /// `(age_range, min_measurement_count, max_measurement_count)` for the submit
+    /// methods of `UserState` and `ServerState`
+    #[pyo3(signature = (today=None))]
+    pub fn submit_args(&self, today: Option<u32>) -> OoniResult<((u32, u32), u32, Option<u32>)> {
+        Ok((
+            self.age_range(today)?,
+            self.policy.min_measurement_count,
+            self.policy.max_measurement_count,
+        ))
+    }
+
+    fn __repr__(&self) -> String {
+        let max = match self.policy.max_measurement_count {
+            Some(max) => max.to_string(),
+            None => "None".into(),
+        };
+        format!(
+            "SubmitPolicy(max_age_days={}, min_measurement_count={}, max_measurement_count={max})",
+            self.policy.max_age_days, self.policy.min_measurement_count
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubmitPolicy;
+    use crate::OoniErr;
+
+    #[test]
+    fn test_submit_policy() {
+        let policy = SubmitPolicy::new(30, 2, Some(10)).unwrap();
+        assert_eq!(policy.age_range(Some(100)).unwrap(), (70, 101));
+        assert_eq!(policy.measurement_count_range().unwrap(), (2, 10));
+        assert_eq!(
+            policy.submit_args(Some(100)).unwrap(),
+            ((70, 101), 2, Some(10))
+        );
+
+        let unbounded = SubmitPolicy::new(30, 0, None).unwrap();
+        assert_eq!(unbounded.measurement_count_range().unwrap(), (0, u32::MAX));
+
+        assert!(matches!(
+            SubmitPolicy::new(30, 10, Some(10)),
+            Err(OoniErr::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            policy.age_range(Some(29)),
+            Err(OoniErr::InvalidArgument { .. })
+        ));
+    }
+}
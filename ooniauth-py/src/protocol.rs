@@ -1,15 +1,27 @@
 use base64::prelude::*;
+use ooniauth_core::bindings::{from_bytes, to_bytes, Pending};
 use ooniauth_core::registration::open_registration;
 use ooniauth_core::submit::submit;
 use ooniauth_core::submit::submit_measurement_hash as core_submit_measurement_hash;
 use ooniauth_core::update::*;
+use ooniauth_core::wire::DecodeMode;
 use ooniauth_core::{self as ooni, PublicParameters, SecretKey};
 
-use pyo3::{prelude::*, types::PyString};
+use pyo3::{
+    prelude::*,
+    types::{PyBytes, PyString},
+    PyTypeInfo,
+};
 use pyo3_stub_gen::derive::{gen_stub_pyclass, gen_stub_pyfunction, gen_stub_pymethods};
+use rayon::prelude::*;
+use std::ops::Range;
+use std::sync::Arc;
 
-use crate::utils::{from_pystring, to_pystring};
-use crate::{exceptions::OoniResult, OoniErr};
+use crate::asyncio::spawn_future;
+use crate::utils::{
+    array_32, from_pybase64, from_pystring, to_pybase64, to_pybytes, to_pystring, Binary,
+};
+use crate::{exceptions::OoniResult, CredentialInfo, OoniErr, SubmitOutcome};
 
 fn py_string_arg<'py>(
     py: Python<'py>,
@@ -23,22 +35,6 @@ fn py_string_arg<'py>(
         })
 }
 
-fn base64_32_arg<'py>(
-    py: Python<'py>,
-    value: &'py Py<PyString>,
-    name: &str,
-) -> OoniResult<[u8; 32]> {
-    BASE64_STANDARD
-        .decode(py_string_arg(py, value, name)?)
-        .map_err(|e| OoniErr::DeserializationFailed {
-            reason: e.to_string(),
-        })?
-        .try_into()
-        .map_err(|value: Vec<u8>| OoniErr::DeserializationFailed {
-            reason: format!("{name} must decode to 32 bytes, got {}", value.len()),
-        })
-}
-
 /// Returns the version of the `ooniauth-core`, the actual protocol implementation.
 #[gen_stub_pyfunction(module = "ooniauth-py")]
 #[pyfunction]
@@ -56,10 +52,25 @@ pub fn submit_measurement_hash(measurement: &str) -> String {
     BASE64_STANDARD.encode(core_submit_measurement_hash(measurement.as_bytes()))
 }
 
+/// Same as `submit_measurement_hash`, taking the measurement body as bytes and
+/// returning the raw 32-byte hash.
+#[gen_stub_pyfunction(module = "ooniauth-py")]
+#[pyfunction]
+pub fn submit_measurement_hash_bytes(py: Python<'_>, measurement: Py<PyBytes>) -> Py<PyBytes> {
+    let measurement = measurement.as_bytes(py);
+    let hash = py.detach(|| core_submit_measurement_hash(measurement));
+    to_pybytes(py, &hash)
+}
+
 #[gen_stub_pyclass]
 #[pyclass]
 pub struct ServerState {
-    pub state: ooni::ServerState,
+    /// Shared with the threads handling the requests of the `*_async` methods
+    pub state: Arc<ooni::ServerState>,
+    /// Accept the bare requests of the clients predating the wire envelope,
+    /// instead of enveloped ones, and reply to them the same way. False by default
+    #[pyo3(get, set)]
+    pub legacy_requests: bool,
 }
 
 #[gen_stub_pymethods]
@@ -70,7 +81,8 @@ impl ServerState {
     pub fn new() -> Self {
         let mut rng = rand::thread_rng();
         Self {
-            state: ooni::ServerState::new(&mut rng),
+            state: Arc::new(ooni::ServerState::new(&mut rng)),
+            legacy_requests: false,
         }
     }
 
@@ -87,27 +99,103 @@ impl ServerState {
         let sk = from_pystring(py, &secret_key)?;
 
         Ok(Self {
-            state: ooni::ServerState::from_creds(sk, pp),
+            state: Arc::new(ooni::ServerState::from_creds(sk, pp)),
+            legacy_requests: false,
+        })
+    }
+
+    /// Same as `from_creds`, taking the keys as returned by `get_public_parameters_bytes`
+    /// and `get_secret_key_bytes`
+    #[staticmethod]
+    fn from_creds_bytes(
+        py: Python<'_>,
+        public_parameters: Py<PyBytes>,
+        secret_key: Py<PyBytes>,
+    ) -> OoniResult<Self> {
+        let pp = from_bytes(public_parameters.as_bytes(py))?;
+        let sk = from_bytes(secret_key.as_bytes(py))?;
+
+        Ok(Self {
+            state: Arc::new(ooni::ServerState::from_creds(sk, pp)),
+            legacy_requests: false,
+        })
+    }
+
+    /// Export the whole server state, keys and `legacy_requests` included, to be
+    /// restored with `from_bytes`
+    pub fn to_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &to_bytes(&(&*self.state, self.legacy_requests)))
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(py: Python<'_>, data: Py<PyBytes>) -> OoniResult<Self> {
+        let (state, legacy_requests) = from_bytes(data.as_bytes(py))?;
+        Ok(Self {
+            state: Arc::new(state),
+            legacy_requests,
         })
     }
 
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Reduced<'py>> {
+        reduce_from_bytes::<Self>(py, self.to_bytes(py))
+    }
+
     pub fn get_secret_key(&self, py: Python<'_>) -> Py<PyString> {
         to_pystring(py, self.state.secret_key_ref())
     }
 
+    pub fn get_secret_key_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &to_bytes(self.state.secret_key_ref()))
+    }
+
     pub fn get_public_parameters(&self, py: Python<'_>) -> Py<PyString> {
         to_pystring(py, self.state.public_parameters_ref())
     }
 
+    pub fn get_public_parameters_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &to_bytes(self.state.public_parameters_ref()))
+    }
+
     pub fn handle_registration_request(
         &self,
         py: Python<'_>,
         registration_request: Py<PyString>,
     ) -> OoniResult<Py<PyString>> {
-        let req = from_pystring(py, &registration_request)?;
-        let reply = self.state.open_registration(req)?;
-        let result = to_pystring(py, &reply);
-        Ok(result)
+        self.run(py, registration_work(py, &registration_request)?)
+    }
+
+    pub fn handle_registration_request_bytes(
+        &self,
+        py: Python<'_>,
+        registration_request: Py<PyBytes>,
+    ) -> OoniResult<Py<PyBytes>> {
+        self.run(py, registration_work(py, &registration_request)?)
+    }
+
+    /// Awaitable version of `handle_registration_request`. The request is handled
+    /// on a Rust thread pool, and the returned future resolves with the response
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.str]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_registration_request_async<'py>(
+        &self,
+        py: Python<'py>,
+        registration_request: Py<PyString>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.spawn::<PyString>(py, registration_work(py, &registration_request)?)
+    }
+
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.bytes]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_registration_request_bytes_async<'py>(
+        &self,
+        py: Python<'py>,
+        registration_request: Py<PyBytes>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.spawn::<PyBytes>(py, registration_work(py, &registration_request)?)
     }
 
     #[staticmethod]
@@ -125,11 +213,19 @@ impl ServerState {
     ///
     /// This validation is done in backend server validating measurements.
     ///
-    /// # Panics
+    /// `age_range` and `[min_measurement_count, max_measurement_count)` are the
+    /// half-open ranges the credential must fall in, with no upper bound on the
+    /// count when `max_measurement_count` is left out. They must be the ones the
+    /// client used, see `SubmitPolicy.submit_args`.
     ///
-    /// The arguments `probe_cc` and `probe_asn` will cause a panic (in debug mode) if not valid.
+    /// Raises `BadDomain` if `probe_cc` or `probe_asn` are not valid, and
+    /// `ValueError` if a range is empty.
     #[allow(clippy::too_many_arguments)]
-    fn handle_submit_request(
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn handle_submit_request(
         &self,
         py: Python<'_>,
         nym: Py<PyString>,
@@ -139,26 +235,148 @@ impl ServerState {
         measurement_hash: Py<PyString>,
         age_range: (u32, u32),
         min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
     ) -> OoniResult<Py<PyString>> {
-        // Convert arguments from py types to rust types
-        let measurement_hash = base64_32_arg(py, &measurement_hash, "measurement_hash")?;
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run(py, args.work(py, &measurement_hash, ranges)?)
+    }
 
-        self.handle_submit_request_impl(
-            py,
-            nym,
-            request,
-            probe_cc,
-            probe_asn,
-            &measurement_hash,
-            age_range,
-            min_measurement_count,
-        )
+    /// Same as `handle_submit_request`, taking the nym, request and measurement hash
+    /// as bytes and returning the response as bytes
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn handle_submit_request_bytes(
+        &self,
+        py: Python<'_>,
+        nym: Py<PyBytes>,
+        request: Py<PyBytes>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement_hash: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<Py<PyBytes>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run(py, args.work(py, &measurement_hash, ranges)?)
+    }
+
+    /// Awaitable version of `handle_submit_request`. The request is verified on a
+    /// Rust thread pool, and the returned future resolves with the response or
+    /// the exception `handle_submit_request` would raise.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.str]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_submit_request_async<'py>(
+        &self,
+        py: Python<'py>,
+        nym: Py<PyString>,
+        request: Py<PyString>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement_hash: Py<PyString>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.spawn::<PyString>(py, args.work(py, &measurement_hash, ranges)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.bytes]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_submit_request_bytes_async<'py>(
+        &self,
+        py: Python<'py>,
+        nym: Py<PyBytes>,
+        request: Py<PyBytes>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement_hash: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.spawn::<PyBytes>(py, args.work(py, &measurement_hash, ranges)?)
+    }
+
+    /// Same as `handle_submit_request`, also returning the `SubmitOutcome`
+    /// describing what was verified, to store next to the measurement
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn handle_submit_request_with_outcome(
+        &self,
+        py: Python<'_>,
+        nym: Py<PyString>,
+        request: Py<PyString>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement_hash: Py<PyString>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<(Py<PyString>, SubmitOutcome)> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run_with_outcome(py, args, &measurement_hash, ranges)
+    }
+
+    /// Same as `handle_submit_request_with_outcome`, taking the nym, request and
+    /// measurement hash as bytes and returning the response as bytes
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn handle_submit_request_with_outcome_bytes(
+        &self,
+        py: Python<'_>,
+        nym: Py<PyBytes>,
+        request: Py<PyBytes>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement_hash: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<(Py<PyBytes>, SubmitOutcome)> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run_with_outcome(py, args, &measurement_hash, ranges)
     }
 
     /// Performs a submission request computing the hash from the input
     /// measurement. Computes the hash internally using the
     /// [submit_measurement_hash] function.
     #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
     pub fn handle_submit_request_with_hash(
         &self,
         py: Python<'_>,
@@ -169,20 +387,138 @@ impl ServerState {
         measurement: Py<PyString>,
         age_range: (u32, u32),
         min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
     ) -> OoniResult<Py<PyString>> {
-        let measurement_str = py_string_arg(py, &measurement, "measurement")?;
-        let measurement_hash = core_submit_measurement_hash(measurement_str.as_bytes());
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run(py, args.work_with_hash(py, &measurement, ranges)?)
+    }
 
-        self.handle_submit_request_impl(
-            py,
-            nym,
-            request,
-            probe_cc,
-            probe_asn,
-            &measurement_hash,
-            age_range,
-            min_measurement_count,
-        )
+    /// Same as `handle_submit_request_with_hash`, taking the nym, request and
+    /// measurement body as bytes and returning the response as bytes
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn handle_submit_request_with_hash_bytes(
+        &self,
+        py: Python<'_>,
+        nym: Py<PyBytes>,
+        request: Py<PyBytes>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<Py<PyBytes>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.run(py, args.work_with_hash(py, &measurement, ranges)?)
+    }
+
+    /// Awaitable version of `handle_submit_request_with_hash`. Both the hash and
+    /// the verification are computed on a Rust thread pool
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.str]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_submit_request_with_hash_async<'py>(
+        &self,
+        py: Python<'py>,
+        nym: Py<PyString>,
+        request: Py<PyString>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement: Py<PyString>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.spawn::<PyString>(py, args.work_with_hash(py, &measurement, ranges)?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        nym, request, probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.bytes]",
+        imports = ("asyncio", "builtins")
+    ))]
+    pub fn handle_submit_request_with_hash_bytes_async<'py>(
+        &self,
+        py: Python<'py>,
+        nym: Py<PyBytes>,
+        request: Py<PyBytes>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let args = SubmitArgs::new(py, &nym, &request, &probe_cc, &probe_asn)?;
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        self.spawn::<PyBytes>(py, args.work_with_hash(py, &measurement, ranges)?)
+    }
+
+    /// Verify many submissions at once, in parallel across CPU cores and without
+    /// holding the GIL
+    ///
+    /// Each item is `(nym, request, probe_cc, probe_asn, measurement_hash)`, as
+    /// for `handle_submit_request`, and all of them are checked against the same
+    /// ranges. Returns a list in the same order holding either the response or
+    /// the exception that `handle_submit_request` would have raised for the item.
+    ///
+    /// Raises `ValueError` if a range is empty.
+    #[pyo3(signature = (
+        requests, age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "list[builtins.str | builtins.Exception]",
+        imports = ("builtins")
+    ))]
+    pub fn handle_submit_requests_batch(
+        &self,
+        py: Python<'_>,
+        requests: Vec<BatchArgs<PyString>>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<Vec<Py<PyAny>>> {
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        Ok(self.run_batch(py, requests, ranges))
+    }
+
+    /// Same as `handle_submit_requests_batch`, with the nyms, requests and
+    /// measurement hashes as bytes and the responses as bytes
+    #[pyo3(signature = (
+        requests, age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    #[gen_stub(override_return_type(
+        type_repr = "list[builtins.bytes | builtins.Exception]",
+        imports = ("builtins")
+    ))]
+    pub fn handle_submit_requests_batch_bytes(
+        &self,
+        py: Python<'_>,
+        requests: Vec<BatchArgs<PyBytes>>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<Vec<Py<PyAny>>> {
+        let ranges = submit_ranges(age_range, min_measurement_count, max_measurement_count)?;
+        Ok(self.run_batch(py, requests, ranges))
     }
 
     fn handle_update_request(
@@ -192,53 +528,228 @@ impl ServerState {
         old_public_params: Py<PyString>,
         old_secret_key: Py<PyString>,
     ) -> OoniResult<Py<PyString>> {
-        let req = from_pystring::<update::Request>(py, &req)?;
-        let old_sk = from_pystring::<SecretKey>(py, &old_secret_key)?;
-        let old_pp = from_pystring::<PublicParameters>(py, &old_public_params)?;
+        self.run(
+            py,
+            update_work(py, &req, &old_public_params, &old_secret_key)?,
+        )
+    }
 
-        let mut rng = rand::thread_rng();
-        let resp = self.state.handle_update(&mut rng, req, &old_sk, &old_pp)?;
+    fn handle_update_request_bytes(
+        &self,
+        py: Python<'_>,
+        req: Py<PyBytes>,
+        old_public_params: Py<PyBytes>,
+        old_secret_key: Py<PyBytes>,
+    ) -> OoniResult<Py<PyBytes>> {
+        self.run(
+            py,
+            update_work(py, &req, &old_public_params, &old_secret_key)?,
+        )
+    }
 
-        Ok(to_pystring(py, &resp))
+    /// Awaitable version of `handle_update_request`, handled on a Rust thread pool
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.str]",
+        imports = ("asyncio", "builtins")
+    ))]
+    fn handle_update_request_async<'py>(
+        &self,
+        py: Python<'py>,
+        req: Py<PyString>,
+        old_public_params: Py<PyString>,
+        old_secret_key: Py<PyString>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let work = update_work(py, &req, &old_public_params, &old_secret_key)?;
+        self.spawn::<PyString>(py, work)
+    }
+
+    #[gen_stub(override_return_type(
+        type_repr = "asyncio.Future[builtins.bytes]",
+        imports = ("asyncio", "builtins")
+    ))]
+    fn handle_update_request_bytes_async<'py>(
+        &self,
+        py: Python<'py>,
+        req: Py<PyBytes>,
+        old_public_params: Py<PyBytes>,
+        old_secret_key: Py<PyBytes>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let work = update_work(py, &req, &old_public_params, &old_secret_key)?;
+        self.spawn::<PyBytes>(py, work)
     }
 }
 
-// Methods in this implementation block are not exposed to Python
+// Each method of the server exists in up to four forms: base64 strings or
+// bytes, returning the response or an awaitable. They only differ in how the
+// arguments are converted, with `Binary`, and in how the work is run, with
+// `run` or `spawn`: the work itself is one closure over Rust data, run without
+// holding the GIL, either on the calling thread or on the thread pool
 impl ServerState {
-    #[allow(clippy::too_many_arguments)]
-    fn handle_submit_request_impl(
+    fn decode_mode(&self) -> DecodeMode {
+        if self.legacy_requests {
+            DecodeMode::Legacy
+        } else {
+            DecodeMode::Envelope
+        }
+    }
+
+    /// Run `work` on the calling thread, without holding the GIL
+    fn run<B: Binary>(&self, py: Python<'_>, work: impl Work) -> OoniResult<Py<B>> {
+        let mode = self.decode_mode();
+        let reply = py.detach(|| work(&self.state, mode))?;
+        Ok(B::from_slice(py, &reply))
+    }
+
+    /// Verify a submission on the calling thread, without holding the GIL, and
+    /// return its reply along with its outcome
+    fn run_with_outcome<B: Binary>(
         &self,
         py: Python<'_>,
-        nym: Py<PyString>,
-        request: Py<PyString>,
-        probe_cc: Py<PyString>,
-        probe_asn: Py<PyString>,
-        measurement_hash: &[u8; 32],
-        age_range: (u32, u32),
-        min_measurement_count: u32,
-    ) -> OoniResult<Py<PyString>> {
-        let nym = base64_32_arg(py, &nym, "nym")?;
-
-        let request = from_pystring::<ooniauth_core::submit::SubmitRequest>(py, &request)?;
-        let probe_cc = py_string_arg(py, &probe_cc, "probe_cc")?;
-        let probe_asn = py_string_arg(py, &probe_asn, "probe_asn")?;
+        mut args: SubmitArgs,
+        measurement_hash: &Py<B>,
+        ranges: (Range<u32>, Range<u32>),
+    ) -> OoniResult<(Py<B>, SubmitOutcome)> {
+        args.measurement_hash = array_32(py, measurement_hash, "measurement_hash")?;
+        let mode = self.decode_mode();
+        let (reply, outcome) =
+            py.detach(|| verify_submit_with_outcome(&self.state, mode, &args, ranges))?;
+        Ok((B::from_slice(py, &reply), SubmitOutcome { outcome }))
+    }
 
-        let mut rng = rand::thread_rng();
-        let result = self.state.handle_submit(
-            &mut rng,
-            request,
-            &nym,
-            probe_cc,
-            probe_asn,
-            measurement_hash,
-            age_range.0..age_range.1,
-            min_measurement_count..u32::MAX,
-        )?;
+    /// Run `work` on the thread pool, returning a future resolved with its reply
+    fn spawn<'py, B: Binary>(
+        &self,
+        py: Python<'py>,
+        work: impl Work + 'static,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = self.state.clone();
+        let mode = self.decode_mode();
+        spawn_future(
+            py,
+            move || work(&state, mode),
+            |py, reply| B::from_slice(py, &reply).into_any(),
+        )
+    }
 
-        Ok(to_pystring(py, &result))
+    /// Verify the items of a batch in parallel, each result being the reply
+    /// or the exception of the item
+    fn run_batch<B: Binary>(
+        &self,
+        py: Python<'_>,
+        requests: Vec<BatchArgs<B>>,
+        ranges: (Range<u32>, Range<u32>),
+    ) -> Vec<Py<PyAny>> {
+        let items = requests
+            .iter()
+            .map(|(nym, request, probe_cc, probe_asn, measurement_hash)| {
+                let mut args = SubmitArgs::new(py, nym, request, probe_cc, probe_asn)?;
+                args.measurement_hash = array_32(py, measurement_hash, "measurement_hash")?;
+                Ok(args)
+            })
+            .collect();
+
+        let mode = self.decode_mode();
+        let results = py.detach(|| verify_submit_batch(&self.state, mode, items, ranges));
+        batch_results(py, results, |reply| B::from_slice(py, &reply).into_any())
     }
 }
 
+/// The server side of a request, working on Rust data only
+trait Work: FnOnce(&ooni::ServerState, DecodeMode) -> OoniResult<Vec<u8>> + Send {}
+
+impl<F: FnOnce(&ooni::ServerState, DecodeMode) -> OoniResult<Vec<u8>> + Send> Work for F {}
+
+fn registration_work<B: Binary>(py: Python<'_>, request: &Py<B>) -> OoniResult<impl Work> {
+    let request = B::to_vec(py, request)?;
+    Ok(move |state: &ooni::ServerState, mode| handle_registration(state, mode, &request))
+}
+
+fn update_work<B: Binary>(
+    py: Python<'_>,
+    request: &Py<B>,
+    old_public_params: &Py<B>,
+    old_secret_key: &Py<B>,
+) -> OoniResult<impl Work> {
+    let request = B::to_vec(py, request)?;
+    let old_sk = from_bytes::<SecretKey>(&B::to_vec(py, old_secret_key)?)?;
+    let old_pp = from_bytes::<PublicParameters>(&B::to_vec(py, old_public_params)?)?;
+    Ok(move |state: &ooni::ServerState, mode| {
+        handle_update(state, mode, &request, &old_sk, &old_pp)
+    })
+}
+
+fn handle_registration(
+    state: &ooni::ServerState,
+    mode: DecodeMode,
+    request: &[u8],
+) -> OoniResult<Vec<u8>> {
+    let (req, framing) = state.decode_request_as::<open_registration::Request>(request, mode)?;
+    let mut rng = rand::thread_rng();
+    let reply = state.open_registration(&mut rng, req)?;
+    Ok(state.encode_reply(&reply, framing))
+}
+
+/// Verify a submission and make the reply
+fn verify_submit(
+    state: &ooni::ServerState,
+    mode: DecodeMode,
+    args: &SubmitArgs,
+    ranges: (Range<u32>, Range<u32>),
+) -> OoniResult<Vec<u8>> {
+    let (reply, _) = verify_submit_with_outcome(state, mode, args, ranges)?;
+    Ok(reply)
+}
+
+/// Like `verify_submit`, also returning what was verified
+fn verify_submit_with_outcome(
+    state: &ooni::ServerState,
+    mode: DecodeMode,
+    args: &SubmitArgs,
+    (age_range, measurement_count_range): (Range<u32>, Range<u32>),
+) -> OoniResult<(Vec<u8>, ooni::submit::SubmitOutcome)> {
+    let (request, framing) =
+        state.decode_request_as::<ooniauth_core::submit::SubmitRequest>(&args.request, mode)?;
+    let mut rng = rand::thread_rng();
+    let (reply, outcome) = state.handle_submit_with_outcome(
+        &mut rng,
+        request,
+        &args.nym,
+        &args.probe_cc,
+        &args.probe_asn,
+        &args.measurement_hash,
+        age_range,
+        measurement_count_range,
+    )?;
+    Ok((state.encode_reply(&reply, framing), outcome))
+}
+
+/// Verify the decoded submissions of a batch in parallel on the rayon pool.
+/// Items that could not be decoded keep their error
+fn verify_submit_batch(
+    state: &ooni::ServerState,
+    mode: DecodeMode,
+    items: Vec<OoniResult<SubmitArgs>>,
+    ranges: (Range<u32>, Range<u32>),
+) -> Vec<OoniResult<Vec<u8>>> {
+    items
+        .into_par_iter()
+        .map(|item| verify_submit(state, mode, &item?, ranges.clone()))
+        .collect()
+}
+
+fn handle_update(
+    state: &ooni::ServerState,
+    mode: DecodeMode,
+    request: &[u8],
+    old_sk: &SecretKey,
+    old_pp: &PublicParameters,
+) -> OoniResult<Vec<u8>> {
+    let (req, framing) = state.decode_request_as::<update::Request>(request, mode)?;
+    let mut rng = rand::thread_rng();
+    let resp = state.handle_update(&mut rng, req, old_sk, old_pp)?;
+    Ok(state.encode_reply(&resp, framing))
+}
+
 impl Default for ServerState {
     fn default() -> Self {
         Self::new()
@@ -249,9 +760,6 @@ impl Default for ServerState {
 #[pyclass]
 pub struct UserState {
     pub state: ooni::UserState,
-    pub registration_client_state: Option<open_registration::ClientState>,
-    pub submit_client_state: Option<submit::ClientState>,
-    pub update_client_state: Option<update::ClientState>,
 }
 
 #[gen_stub_pymethods]
@@ -260,18 +768,54 @@ impl UserState {
     #[new]
     pub fn new(py: Python<'_>, public_params: Py<PyString>) -> OoniResult<Self> {
         let params = from_pystring(py, &public_params)?;
+        Ok(Self::with_public_params(params))
+    }
+
+    /// Same as the constructor, taking the public parameters as returned by
+    /// `ServerState.get_public_parameters_bytes`
+    #[staticmethod]
+    pub fn from_public_params_bytes(
+        py: Python<'_>,
+        public_params: Py<PyBytes>,
+    ) -> OoniResult<Self> {
+        Ok(Self::with_public_params(from_bytes(
+            public_params.as_bytes(py),
+        )?))
+    }
+
+    /// Export the public parameters and credential, to be restored with `from_bytes`.
+    /// Pending requests are exported separately, with their own `to_bytes`
+    pub fn to_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &to_bytes(&self.state))
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(py: Python<'_>, data: Py<PyBytes>) -> OoniResult<Self> {
         Ok(Self {
-            state: ooni::UserState::new(params),
-            registration_client_state: None,
-            submit_client_state: None,
-            update_client_state: None,
+            state: from_bytes(data.as_bytes(py))?,
         })
     }
 
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Reduced<'py>> {
+        reduce_from_bytes::<Self>(py, self.to_bytes(py))
+    }
+
     pub fn get_credential(&self, py: Python<'_>) -> Option<Py<PyString>> {
         self.state.get_credential().map(|c| to_pystring(py, c))
     }
 
+    pub fn get_credential_bytes(&self, py: Python<'_>) -> Option<Py<PyBytes>> {
+        self.state
+            .get_credential()
+            .map(|c| to_pybytes(py, &to_bytes(c)))
+    }
+
+    /// What can be shown about the current credential, `None` before registration
+    pub fn get_credential_info(&self) -> OoniResult<Option<CredentialInfo>> {
+        let info = self.state.credential_info()?;
+        Ok(info.map(|info| CredentialInfo { info }))
+    }
+
     pub fn set_public_params(
         &mut self,
         py: Python<'_>,
@@ -282,36 +826,48 @@ impl UserState {
         Ok(())
     }
 
-    pub fn make_registration_request(&mut self, py: Python<'_>) -> OoniResult<Py<PyString>> {
-        let mut rng = rand::thread_rng();
-
-        let (req, state) = self.state.request(&mut rng)?;
+    pub fn set_public_params_bytes(
+        &mut self,
+        py: Python<'_>,
+        new_public_params: Py<PyBytes>,
+    ) -> OoniResult<()> {
+        self.state.pp = from_bytes(new_public_params.as_bytes(py))?;
+        Ok(())
+    }
 
-        self.registration_client_state = Some(state);
+    /// Make a registration request. Send its `request` to the server, and pass the
+    /// returned object along with the server response to `handle_registration_response`
+    pub fn make_registration_request(&self, py: Python<'_>) -> OoniResult<RegistrationRequest> {
+        let (request, state) = py.detach(|| -> OoniResult<_> {
+            let mut rng = rand::thread_rng();
+            let (request, state) = self.state.request(&mut rng)?;
+            Ok((self.state.encode_request(&request), state))
+        })?;
 
-        let result = to_pystring(py, &req);
-        Ok(result)
+        Ok(RegistrationRequest(Pending::new((), request, state)))
     }
 
     /// Handle a registration response sent by the server, updating your credentials
     ///
-    /// Note that this function will only work if you previously called
-    /// `make_registration_request`
+    /// `pending` is the `RegistrationRequest` the response answers. Each pending
+    /// request can only be handled once
     pub fn handle_registration_response(
         &mut self,
         py: Python<'_>,
         resp: Py<PyString>,
+        pending: PyRefMut<'_, RegistrationRequest>,
     ) -> OoniResult<()> {
-        let response = from_pystring::<open_registration::Reply>(py, &resp)?;
-
-        let client_state = self.registration_client_state.take().expect(
-            "Calling `handle_registration_response` without a registration client state. \
-                    Did you forget to call `make_registration_request` before?",
-        );
-
-        self.state.handle_response(client_state, response)?;
+        let bytes = from_pybase64(py, &resp)?;
+        self.handle_registration_response_impl(py, &bytes, pending)
+    }
 
-        Ok(())
+    pub fn handle_registration_response_bytes(
+        &mut self,
+        py: Python<'_>,
+        resp: Py<PyBytes>,
+        pending: PyRefMut<'_, RegistrationRequest>,
+    ) -> OoniResult<()> {
+        self.handle_registration_response_impl(py, resp.as_bytes(py), pending)
     }
 
     /// Make a submission request, to send a measurement to the server
@@ -324,153 +880,476 @@ impl UserState {
     ///
     /// This validation is done in backend server validating measurements.
     ///
-    /// # Panics
+    /// `age_range` and `[min_measurement_count, max_measurement_count)` must be the
+    /// ranges the server checks, see `SubmitPolicy.submit_args`.
     ///
-    /// The arguments `probe_cc` and `probe_asn` will cause a panic (in debug mode) if not valid.
+    /// Raises `BadDomain` if `probe_cc` or `probe_asn` are not valid, and
+    /// `ValueError` if a range is empty.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
     pub fn make_submit_request(
-        &mut self,
+        &self,
         py: Python<'_>,
         probe_cc: Py<PyString>,
         probe_asn: Py<PyString>,
         measurement_hash: Py<PyString>,
         age_range: (u32, u32),
         min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
     ) -> OoniResult<SubmitRequest> {
-        let measurement_hash = base64_32_arg(py, &measurement_hash, "measurement_hash")?;
+        let measurement_hash = array_32(py, &measurement_hash, "measurement_hash")?;
 
         self.make_submit_request_impl(
             py,
             probe_cc,
             probe_asn,
             &measurement_hash,
-            age_range,
-            min_measurement_count,
+            submit_ranges(age_range, min_measurement_count, max_measurement_count)?,
         )
     }
 
-    /// Creates a submit request computing the hash from the input measurement.
-    /// Computes the hash internally using the [submit_measurement_hash] function
-    pub fn make_submit_request_with_hash(
-        &mut self,
+    /// Same as `make_submit_request`, taking the raw 32-byte measurement hash
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        probe_cc, probe_asn, measurement_hash,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn make_submit_request_bytes(
+        &self,
         py: Python<'_>,
         probe_cc: Py<PyString>,
         probe_asn: Py<PyString>,
-        measurement: Py<PyString>,
+        measurement_hash: Py<PyBytes>,
         age_range: (u32, u32),
         min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
     ) -> OoniResult<SubmitRequest> {
-        let measurement_str = py_string_arg(py, &measurement, "measurement")?;
-        let measurement_hash = core_submit_measurement_hash(measurement_str.as_bytes());
+        let measurement_hash = array_32(py, &measurement_hash, "measurement_hash")?;
 
         self.make_submit_request_impl(
             py,
             probe_cc,
             probe_asn,
             &measurement_hash,
-            age_range,
-            min_measurement_count,
+            submit_ranges(age_range, min_measurement_count, max_measurement_count)?,
         )
     }
 
-    /// Handle a submit response sent by the server, updating your credentials
-    ///
-    /// Note that this function will only work if you previously called
-    /// `make_submit_request`
-    pub fn handle_submit_response(
-        &mut self,
+    /// Creates a submit request computing the hash from the input measurement.
+    /// Computes the hash internally using the [submit_measurement_hash] function
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn make_submit_request_with_hash(
+        &self,
         py: Python<'_>,
-        response: Py<PyString>,
-    ) -> OoniResult<()> {
-        let response = from_pystring::<submit::Reply>(py, &response)?;
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement: Py<PyString>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<SubmitRequest> {
+        let measurement_str = py_string_arg(py, &measurement, "measurement")?;
+        let measurement_hash =
+            py.detach(|| core_submit_measurement_hash(measurement_str.as_bytes()));
 
-        let submit_state = self.submit_client_state.take().expect(
-            "Calling `handle_submit_response` without a submit client state. \
-                    Did you forget to call `make_submit_request` before?",
-        );
+        self.make_submit_request_impl(
+            py,
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            submit_ranges(age_range, min_measurement_count, max_measurement_count)?,
+        )
+    }
 
-        self.state.handle_submit_response(submit_state, response)?;
+    /// Same as `make_submit_request_with_hash`, taking the measurement body as bytes
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        probe_cc, probe_asn, measurement,
+        age_range, min_measurement_count, max_measurement_count=None
+    ))]
+    pub fn make_submit_request_with_hash_bytes(
+        &self,
+        py: Python<'_>,
+        probe_cc: Py<PyString>,
+        probe_asn: Py<PyString>,
+        measurement: Py<PyBytes>,
+        age_range: (u32, u32),
+        min_measurement_count: u32,
+        max_measurement_count: Option<u32>,
+    ) -> OoniResult<SubmitRequest> {
+        let measurement = measurement.as_bytes(py);
+        let measurement_hash = py.detach(|| core_submit_measurement_hash(measurement));
 
-        Ok(())
+        self.make_submit_request_impl(
+            py,
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            submit_ranges(age_range, min_measurement_count, max_measurement_count)?,
+        )
     }
 
-    /// Creates a credential update request to be sent to the server.
-    pub fn make_credential_update_request(&mut self, py: Python<'_>) -> OoniResult<Py<PyString>> {
-        let mut rng = rand::thread_rng();
-        let (request, new_state) = self.state.update_request(&mut rng)?;
-        self.update_client_state = Some(new_state);
+    /// Handle a submit response sent by the server, updating your credentials
+    ///
+    /// `pending` is the `SubmitRequest` the response answers. Several submissions
+    /// can be in flight at once, and each response replaces the credential with one
+    /// derived from the credential its request was made with
+    pub fn handle_submit_response(
+        &mut self,
+        py: Python<'_>,
+        response: Py<PyString>,
+        pending: PyRefMut<'_, SubmitRequest>,
+    ) -> OoniResult<()> {
+        let bytes = from_pybase64(py, &response)?;
+        self.handle_submit_response_impl(py, &bytes, pending)
+    }
+
+    pub fn handle_submit_response_bytes(
+        &mut self,
+        py: Python<'_>,
+        response: Py<PyBytes>,
+        pending: PyRefMut<'_, SubmitRequest>,
+    ) -> OoniResult<()> {
+        self.handle_submit_response_impl(py, response.as_bytes(py), pending)
+    }
 
-        Ok(to_pystring(py, &request))
+    /// Creates a credential update request to be sent to the server.
+    pub fn make_credential_update_request(&self, py: Python<'_>) -> OoniResult<UpdateRequest> {
+        let (request, state) = py.detach(|| -> OoniResult<_> {
+            let mut rng = rand::thread_rng();
+            let (request, state) = self.state.update_request(&mut rng)?;
+            Ok((self.state.encode_request(&request), state))
+        })?;
+
+        Ok(UpdateRequest(Pending::new((), request, state)))
     }
 
     /// Handles the credential update response sent by the server, updating your credentials.
     ///
-    /// This function only works if you previosly called `make_credential_update_request`
+    /// `pending` is the `UpdateRequest` the response answers.
     pub fn handle_credential_update_response(
         &mut self,
         py: Python<'_>,
         resp: Py<PyString>,
+        pending: PyRefMut<'_, UpdateRequest>,
     ) -> OoniResult<()> {
-        let response = from_pystring::<update::Reply>(py, &resp)?;
-
-        let update_state = self.update_client_state.take().expect(
-            "Calling `handle_submit_response` without a submit client state. \
-                    Did you forget to call `make_submit_request` before?",
-        );
-
-        self.state.handle_update_response(update_state, response)?;
+        let bytes = from_pybase64(py, &resp)?;
+        self.handle_credential_update_response_impl(py, &bytes, pending)
+    }
 
-        Ok(())
+    pub fn handle_credential_update_response_bytes(
+        &mut self,
+        py: Python<'_>,
+        resp: Py<PyBytes>,
+        pending: PyRefMut<'_, UpdateRequest>,
+    ) -> OoniResult<()> {
+        self.handle_credential_update_response_impl(py, resp.as_bytes(py), pending)
     }
 }
 
 // Methods in this implementation block are not exposed to Python
 impl UserState {
-    fn make_submit_request_impl(
+    fn with_public_params(params: PublicParameters) -> Self {
+        Self {
+            state: ooni::UserState::new(params),
+        }
+    }
+
+    fn handle_registration_response_impl(
         &mut self,
         py: Python<'_>,
+        resp: &[u8],
+        mut pending: PyRefMut<'_, RegistrationRequest>,
+    ) -> OoniResult<()> {
+        pending.0.handle("registration request", |client_state| {
+            let response = self.state.decode_reply::<open_registration::Reply>(resp)?;
+            Ok(py.detach(|| self.state.handle_response(client_state, response))?)
+        })
+    }
+
+    fn make_submit_request_impl(
+        &self,
+        py: Python<'_>,
         probe_cc: Py<PyString>,
         probe_asn: Py<PyString>,
         measurement_hash: &[u8; 32],
-        age_range: (u32, u32),
-        min_measurement_count: u32,
+        (age_range, measurement_count_range): (Range<u32>, Range<u32>),
     ) -> OoniResult<SubmitRequest> {
-        let probe_cc = py_string_arg(py, &probe_cc, "probe_cc")?;
-        let probe_asn = py_string_arg(py, &probe_asn, "probe_asn")?;
+        let probe_cc = py_string_arg(py, &probe_cc, "probe_cc")?.to_owned();
+        let probe_asn = py_string_arg(py, &probe_asn, "probe_asn")?.to_owned();
+
+        py.detach(|| {
+            let mut rng = rand::thread_rng();
+            let ((request, state), nym) = self.state.submit_request(
+                &mut rng,
+                probe_cc,
+                probe_asn,
+                measurement_hash,
+                age_range,
+                measurement_count_range,
+            )?;
+            let request = self.state.encode_request(&request);
+            Ok(SubmitRequest(Pending::new(nym, request, state)))
+        })
+    }
 
-        let mut rng = rand::thread_rng();
-        let ((result, client_state), nym) = self.state.submit_request(
-            &mut rng,
-            probe_cc.into(),
-            probe_asn.into(),
-            measurement_hash,
-            age_range.0..age_range.1,
-            min_measurement_count..u32::MAX,
-        )?;
+    fn handle_submit_response_impl(
+        &mut self,
+        py: Python<'_>,
+        response: &[u8],
+        mut pending: PyRefMut<'_, SubmitRequest>,
+    ) -> OoniResult<()> {
+        pending.0.handle("submit request", |submit_state| {
+            let response = self.state.decode_reply::<submit::Reply>(response)?;
+            Ok(py.detach(|| self.state.handle_submit_response(submit_state, response))?)
+        })
+    }
+
+    fn handle_credential_update_response_impl(
+        &mut self,
+        py: Python<'_>,
+        resp: &[u8],
+        mut pending: PyRefMut<'_, UpdateRequest>,
+    ) -> OoniResult<()> {
+        pending.0.handle("update request", |update_state| {
+            let response = self.state.decode_reply::<update::Reply>(resp)?;
+            Ok(py.detach(|| self.state.handle_update_response(update_state, response))?)
+        })
+    }
+}
 
-        self.submit_client_state = Some(client_state);
+/// What `__reduce__` returns: a callable and its arguments
+type Reduced<'py> = (Bound<'py, PyAny>, (Py<PyBytes>,));
 
-        Ok(SubmitRequest {
-            nym: to_pystring(py, &nym),
-            request: to_pystring(py, &result),
+/// Pickle support for the classes with a `from_bytes` static method, so that
+/// `pickle` and `copy` rebuild them from `to_bytes`
+fn reduce_from_bytes<T: PyTypeInfo>(py: Python<'_>, data: Py<PyBytes>) -> PyResult<Reduced<'_>> {
+    Ok((py.get_type::<T>().getattr("from_bytes")?, (data,)))
+}
+
+/// One item of `handle_submit_requests_batch`: `(nym, request, probe_cc,
+/// probe_asn, measurement_hash)`, with `T` the type of the binary fields
+type BatchArgs<T> = (Py<T>, Py<T>, Py<PyString>, Py<PyString>, Py<T>);
+
+/// The arguments of a submit request, copied out of their Python objects
+struct SubmitArgs {
+    nym: [u8; 32],
+    request: Vec<u8>,
+    probe_cc: String,
+    probe_asn: String,
+    measurement_hash: [u8; 32],
+}
+
+impl SubmitArgs {
+    /// Copy the arguments, but the measurement hash
+    fn new<B: Binary>(
+        py: Python<'_>,
+        nym: &Py<B>,
+        request: &Py<B>,
+        probe_cc: &Py<PyString>,
+        probe_asn: &Py<PyString>,
+    ) -> OoniResult<Self> {
+        Ok(Self {
+            nym: array_32(py, nym, "nym")?,
+            request: B::to_vec(py, request)?,
+            probe_cc: py_string_arg(py, probe_cc, "probe_cc")?.to_owned(),
+            probe_asn: py_string_arg(py, probe_asn, "probe_asn")?.to_owned(),
+            measurement_hash: [0; 32],
+        })
+    }
+
+    fn work<B: Binary>(
+        mut self,
+        py: Python<'_>,
+        measurement_hash: &Py<B>,
+        ranges: (Range<u32>, Range<u32>),
+    ) -> OoniResult<impl Work> {
+        self.measurement_hash = array_32(py, measurement_hash, "measurement_hash")?;
+        Ok(move |state: &ooni::ServerState, mode| verify_submit(state, mode, &self, ranges))
+    }
+
+    /// Like `work`, hashing the measurement as part of the work
+    fn work_with_hash<B: Binary>(
+        mut self,
+        py: Python<'_>,
+        measurement: &Py<B>,
+        ranges: (Range<u32>, Range<u32>),
+    ) -> OoniResult<impl Work> {
+        let measurement = B::measurement(py, measurement)?;
+        Ok(move |state: &ooni::ServerState, mode| {
+            self.measurement_hash = core_submit_measurement_hash(&measurement);
+            verify_submit(state, mode, &self, ranges)
         })
     }
 }
 
+/// Turn the outcome of each batch item into its reply, or into the exception it
+/// would have raised
+fn batch_results(
+    py: Python<'_>,
+    results: Vec<OoniResult<Vec<u8>>>,
+    reply: impl Fn(Vec<u8>) -> Py<PyAny>,
+) -> Vec<Py<PyAny>> {
+    results
+        .into_iter()
+        .map(|result| match result {
+            Ok(bytes) => reply(bytes),
+            Err(e) => PyErr::from(e).into_value(py).into_any(),
+        })
+        .collect()
+}
+
+/// Check the submission ranges given from Python. Both are half-open, and a
+/// missing `max_measurement_count` means no upper bound
+fn submit_ranges(
+    age_range: (u32, u32),
+    min_measurement_count: u32,
+    max_measurement_count: Option<u32>,
+) -> OoniResult<(Range<u32>, Range<u32>)> {
+    let age = age_range.0..age_range.1;
+    let count = min_measurement_count..max_measurement_count.unwrap_or(u32::MAX);
+    ooni::submit::check_submit_ranges(&age, &count)?;
+    Ok((age, count))
+}
+
+/// A registration request waiting for the server response
+///
+/// Send `request` (or `request_bytes`) to the server and pass this object to
+/// `UserState.handle_registration_response` along with the response.
+/// `to_bytes` and `from_bytes` keep it across restarts.
 #[gen_stub_pyclass]
 #[pyclass]
-pub struct SubmitRequest {
-    #[pyo3(get)]
-    pub nym: Py<PyString>,
-    #[pyo3(get)]
-    pub request: Py<PyString>,
+pub struct RegistrationRequest(Pending<(), open_registration::ClientState>);
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl RegistrationRequest {
+    #[getter]
+    pub fn request(&self, py: Python<'_>) -> Py<PyString> {
+        to_pybase64(py, &self.0.request)
+    }
+
+    #[getter]
+    pub fn request_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.0.request)
+    }
+
+    pub fn to_bytes(&self, py: Python<'_>) -> OoniResult<Py<PyBytes>> {
+        Ok(to_pybytes(py, &self.0.to_bytes("registration request")?))
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(py: Python<'_>, data: Py<PyBytes>) -> OoniResult<Self> {
+        Ok(Self(from_bytes(data.as_bytes(py))?))
+    }
+
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Reduced<'py>> {
+        reduce_from_bytes::<Self>(py, self.to_bytes(py)?)
+    }
+}
+
+/// A measurement submission waiting for the server response
+///
+/// Send `nym` and `request` (or their `_bytes` versions) to the server and pass
+/// this object to `UserState.handle_submit_response` along with the response.
+/// `to_bytes` and `from_bytes` keep it across restarts.
+#[gen_stub_pyclass]
+#[pyclass]
+pub struct SubmitRequest(Pending<[u8; 32], submit::ClientState>);
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl SubmitRequest {
+    #[getter]
+    pub fn nym(&self, py: Python<'_>) -> Py<PyString> {
+        to_pybase64(py, &self.0.nym)
+    }
+
+    #[getter]
+    pub fn request(&self, py: Python<'_>) -> Py<PyString> {
+        to_pybase64(py, &self.0.request)
+    }
+
+    #[getter]
+    pub fn nym_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.0.nym)
+    }
+
+    #[getter]
+    pub fn request_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.0.request)
+    }
+
+    pub fn to_bytes(&self, py: Python<'_>) -> OoniResult<Py<PyBytes>> {
+        Ok(to_pybytes(py, &self.0.to_bytes("submit request")?))
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(py: Python<'_>, data: Py<PyBytes>) -> OoniResult<Self> {
+        Ok(Self(from_bytes(data.as_bytes(py))?))
+    }
+
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Reduced<'py>> {
+        reduce_from_bytes::<Self>(py, self.to_bytes(py)?)
+    }
+}
+
+/// A credential update request waiting for the server response
+///
+/// Send `request` (or `request_bytes`) to the server and pass this object to
+/// `UserState.handle_credential_update_response` along with the response.
+/// `to_bytes` and `from_bytes` keep it across restarts.
+#[gen_stub_pyclass]
+#[pyclass]
+pub struct UpdateRequest(Pending<(), update::ClientState>);
+
+#[gen_stub_pymethods]
+#[pymethods]
+impl UpdateRequest {
+    #[getter]
+    pub fn request(&self, py: Python<'_>) -> Py<PyString> {
+        to_pybase64(py, &self.0.request)
+    }
+
+    #[getter]
+    pub fn request_bytes(&self, py: Python<'_>) -> Py<PyBytes> {
+        to_pybytes(py, &self.0.request)
+    }
+
+    pub fn to_bytes(&self, py: Python<'_>) -> OoniResult<Py<PyBytes>> {
+        Ok(to_pybytes(py, &self.0.to_bytes("update request")?))
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(py: Python<'_>, data: Py<PyBytes>) -> OoniResult<Self> {
+        Ok(Self(from_bytes(data.as_bytes(py))?))
+    }
+
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Reduced<'py>> {
+        reduce_from_bytes::<Self>(py, self.to_bytes(py)?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::OoniErr;
     use base64::{prelude::BASE64_STANDARD, Engine};
-    use ooniauth_core::{registration::open_registration::Request, ServerState, UserState};
-    use pyo3::{types::PyString, Py, Python};
+    use ooniauth_core::errors::{CredentialError, ErrorCode};
+    use ooniauth_core::registration::open_registration::{self, Request};
+    use ooniauth_core::{ServerState, UserState};
+    use pyo3::{
+        exceptions::PyValueError,
+        prelude::PyAnyMethods,
+        types::{PyBytes, PyString},
+        Bound, Py, PyErr, Python,
+    };
     use rand::{rngs::ThreadRng, thread_rng};
 
     fn test_measurement_hash(py: Python<'_>, value: u8) -> Py<PyString> {
@@ -497,7 +1376,56 @@ mod tests {
         let req_str = BASE64_STANDARD.encode(req_bin);
         let req_bin = BASE64_STANDARD.decode(req_str).unwrap();
         let req = bincode::deserialize::<Request>(&req_bin).unwrap();
-        assert!(server.open_registration(req).is_ok());
+        assert!(server.open_registration(&mut rng, req).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_registration_request() {
+        // Clients predating the wire envelope send bare bincode and must
+        // receive a bare bincode reply
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let mut rng = thread_rng();
+            let mut server = crate::ServerState::new();
+            let mut client = UserState::new(server.state.public_parameters());
+            let (req, state) = client.request(&mut rng).unwrap();
+
+            let legacy_req = crate::utils::to_pystring(py, &req);
+            assert!(server
+                .handle_registration_request(py, legacy_req.clone_ref(py))
+                .is_err());
+
+            server.legacy_requests = true;
+            let resp = server.handle_registration_request(py, legacy_req).unwrap();
+            let reply = crate::utils::from_pystring::<open_registration::Reply>(py, &resp).unwrap();
+
+            assert!(client.handle_response(state, reply).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_legacy_server_pickle() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let mut rng = thread_rng();
+            let mut server = crate::ServerState::new();
+            server.legacy_requests = true;
+            let mut client = UserState::new(server.state.public_parameters());
+            let (req, state) = client.request(&mut rng).unwrap();
+
+            // A server handed to a worker process keeps accepting legacy
+            // requests. `copy` goes through `__reduce__`, like `pickle` does
+            let deepcopy = py.import("copy").unwrap().getattr("deepcopy").unwrap();
+            let server = deepcopy.call1((Bound::new(py, server).unwrap(),)).unwrap();
+            let server = server.cast::<crate::ServerState>().unwrap().borrow();
+            assert!(server.legacy_requests);
+
+            let resp = server
+                .handle_registration_request(py, crate::utils::to_pystring(py, &req))
+                .unwrap();
+            let reply = crate::utils::from_pystring::<open_registration::Reply>(py, &resp).unwrap();
+            assert!(client.handle_response(state, reply).is_ok());
+        });
     }
 
     #[test]
@@ -514,10 +1442,12 @@ mod tests {
 
             // Test registration
             let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
-            let req = client.make_registration_request(py).unwrap();
-            let reg_response = server.handle_registration_request(py, req).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let reg_response = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
             assert!(client
-                .handle_registration_response(py, reg_response)
+                .handle_registration_response(py, reg_response, req.borrow_mut())
                 .is_ok());
 
             // Test submit
@@ -535,19 +1465,21 @@ mod tests {
                     measurement_hash.clone_ref(py),
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .unwrap();
 
             assert!(server
                 .handle_submit_request(
                     py,
-                    submit_req.nym,
-                    submit_req.request,
+                    submit_req.nym(py),
+                    submit_req.request(py),
                     cc.into(),
                     asn.into(),
                     measurement_hash,
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .is_ok());
         });
@@ -560,10 +1492,12 @@ mod tests {
             let server = crate::ServerState::new();
             let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
 
-            let req = client.make_registration_request(py).unwrap();
-            let reg_response = server.handle_registration_request(py, req).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let reg_response = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
             client
-                .handle_registration_response(py, reg_response)
+                .handle_registration_response(py, reg_response, req.borrow_mut())
                 .unwrap();
 
             let cc: Py<PyString> = PyString::new(py, "VE").into();
@@ -581,23 +1515,29 @@ mod tests {
                     measurement.clone_ref(py),
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .unwrap();
 
+            let submit_req = Bound::new(py, submit_req).unwrap();
+
             let resp = server
                 .handle_submit_request_with_hash(
                     py,
-                    submit_req.nym,
-                    submit_req.request,
+                    submit_req.borrow().nym(py),
+                    submit_req.borrow().request(py),
                     cc,
                     asn,
                     measurement,
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .unwrap();
 
-            assert!(client.handle_submit_response(py, resp).is_ok());
+            assert!(client
+                .handle_submit_response(py, resp, submit_req.borrow_mut())
+                .is_ok());
         });
     }
 
@@ -619,11 +1559,12 @@ mod tests {
             let register_req = client
                 .make_registration_request(py)
                 .expect("Unable to make registration request");
+            let register_req = Bound::new(py, register_req).unwrap();
             let resp = old_state
-                .handle_registration_request(py, register_req)
+                .handle_registration_request(py, register_req.borrow().request(py))
                 .expect("Unable to handle registration request");
             client
-                .handle_registration_response(py, resp)
+                .handle_registration_response(py, resp, register_req.borrow_mut())
                 .expect("Unable to handle registration response");
 
             // Update credential
@@ -633,13 +1574,19 @@ mod tests {
             let update_req = client
                 .make_credential_update_request(py)
                 .expect("Unable to make credential update request");
+            let update_req = Bound::new(py, update_req).unwrap();
 
             let resp = new_state
-                .handle_update_request(py, update_req, old_pub_params, old_secret_key)
+                .handle_update_request(
+                    py,
+                    update_req.borrow().request(py),
+                    old_pub_params,
+                    old_secret_key,
+                )
                 .expect("Bad credential update request");
 
             client
-                .handle_credential_update_response(py, resp)
+                .handle_credential_update_response(py, resp, update_req.borrow_mut())
                 .expect("Bad credential update response");
         });
     }
@@ -659,11 +1606,12 @@ mod tests {
             let register_req = client
                 .make_registration_request(py)
                 .expect("Unable to make registration request");
+            let register_req = Bound::new(py, register_req).unwrap();
             let resp = old_state
-                .handle_registration_request(py, register_req)
+                .handle_registration_request(py, register_req.borrow().request(py))
                 .expect("Unable to handle registration request");
             client
-                .handle_registration_response(py, resp)
+                .handle_registration_response(py, resp, register_req.borrow_mut())
                 .expect("Unable to handle registration response");
 
             // submit measurement
@@ -682,24 +1630,28 @@ mod tests {
                     measurement_hash.clone_ref(py),
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .expect("Unable to make submit request");
 
+            let submit = Bound::new(py, submit).unwrap();
+
             let resp = old_state
                 .handle_submit_request(
                     py,
-                    submit.nym,
-                    submit.request,
+                    submit.borrow().nym(py),
+                    submit.borrow().request(py),
                     probe_cc.clone_ref(py),
                     probe_asn.clone_ref(py),
                     measurement_hash,
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .expect("Invalid submit request");
 
             client
-                .handle_submit_response(py, resp)
+                .handle_submit_response(py, resp, submit.borrow_mut())
                 .expect("Bad submit response");
 
             // Create new server state and update credentials
@@ -711,13 +1663,19 @@ mod tests {
             let update_req = client
                 .make_credential_update_request(py)
                 .expect("Unable to make credential update request");
+            let update_req = Bound::new(py, update_req).unwrap();
 
             let resp = new_state
-                .handle_update_request(py, update_req, old_pub_params, old_secret_key)
+                .handle_update_request(
+                    py,
+                    update_req.borrow().request(py),
+                    old_pub_params,
+                    old_secret_key,
+                )
                 .expect("Bad credential update request");
 
             client
-                .handle_credential_update_response(py, resp)
+                .handle_credential_update_response(py, resp, update_req.borrow_mut())
                 .expect("Bad credential update response");
 
             // Now make sure you can send another measurement
@@ -730,31 +1688,340 @@ mod tests {
                     measurement_hash.clone_ref(py),
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .expect("Unable to make submit request");
+            let submit = Bound::new(py, submit).unwrap();
 
             let resp = new_state
                 .handle_submit_request(
                     py,
-                    submit.nym,
-                    submit.request,
+                    submit.borrow().nym(py),
+                    submit.borrow().request(py),
                     probe_cc,
                     probe_asn,
                     measurement_hash,
                     age_tuple,
                     min_msm,
+                    None,
                 )
                 .expect("Invalid submit request");
 
             client
-                .handle_submit_response(py, resp)
+                .handle_submit_response(py, resp, submit.borrow_mut())
                 .expect("Bad submit response");
         });
     }
 
-    fn submit_fixture(
-        py: Python<'_>,
-    ) -> (
+    #[test]
+    fn test_bytes_api() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let server = crate::ServerState::new();
+            let server = crate::ServerState::from_creds_bytes(
+                py,
+                server.get_public_parameters_bytes(py),
+                server.get_secret_key_bytes(py),
+            )
+            .unwrap();
+            let mut client = crate::UserState::from_public_params_bytes(
+                py,
+                server.get_public_parameters_bytes(py),
+            )
+            .unwrap();
+
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request_bytes(py, req.borrow().request_bytes(py))
+                .unwrap();
+            client
+                .handle_registration_response_bytes(py, resp, req.borrow_mut())
+                .unwrap();
+            assert!(client.get_credential_bytes(py).is_some());
+
+            let cc: Py<PyString> = PyString::new(py, "VE").into();
+            let asn: Py<PyString> = PyString::new(py, "AS1234").into();
+            let measurement = PyBytes::new(py, b"measurement:VE:AS1234").unbind();
+            let hash = crate::submit_measurement_hash_bytes(py, measurement.clone_ref(py));
+            assert_eq!(
+                hash.as_bytes(py),
+                ooniauth_core::submit::submit_measurement_hash(b"measurement:VE:AS1234")
+            );
+            let today = ServerState::today();
+            let age_tuple = (today - 30, today + 1);
+
+            let submit = client
+                .make_submit_request_bytes(
+                    py,
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    hash,
+                    age_tuple,
+                    0,
+                    None,
+                )
+                .unwrap();
+
+            let submit = Bound::new(py, submit).unwrap();
+            assert_eq!(
+                BASE64_STANDARD
+                    .decode(submit.borrow().nym(py).to_str(py).unwrap())
+                    .unwrap(),
+                submit.borrow().nym_bytes(py).as_bytes(py)
+            );
+            assert_eq!(
+                BASE64_STANDARD
+                    .decode(submit.borrow().request(py).to_str(py).unwrap())
+                    .unwrap(),
+                submit.borrow().request_bytes(py).as_bytes(py)
+            );
+
+            let resp = server
+                .handle_submit_request_with_hash_bytes(
+                    py,
+                    submit.borrow().nym_bytes(py),
+                    submit.borrow().request_bytes(py),
+                    cc,
+                    asn,
+                    measurement,
+                    age_tuple,
+                    0,
+                    None,
+                )
+                .unwrap();
+            client
+                .handle_submit_response_bytes(py, resp, submit.borrow_mut())
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn test_concurrent_submit_requests() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let server = crate::ServerState::new();
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .unwrap();
+
+            // A handled request can't be handled or persisted again
+            let err = client
+                .handle_registration_response(py, PyString::new(py, "").into(), req.borrow_mut())
+                .unwrap_err();
+            assert!(matches!(err, OoniErr::InvalidArgument { .. }));
+            assert!(req.borrow().to_bytes(py).is_err());
+
+            let cc: Py<PyString> = PyString::new(py, "VE").into();
+            let asn: Py<PyString> = PyString::new(py, "AS1234").into();
+            let today = ServerState::today();
+            let age_tuple = (today - 30, today + 1);
+
+            let mut pending = Vec::new();
+            for value in 1..=2 {
+                let hash = test_measurement_hash(py, value);
+                let submit = client
+                    .make_submit_request(
+                        py,
+                        cc.clone_ref(py),
+                        asn.clone_ref(py),
+                        hash.clone_ref(py),
+                        age_tuple,
+                        0,
+                        None,
+                    )
+                    .unwrap();
+                pending.push((hash, submit));
+            }
+
+            // Both requests are valid, and each survives a round trip through bytes
+            let mut responses = Vec::new();
+            for (hash, submit) in pending {
+                let submit =
+                    crate::SubmitRequest::from_bytes(py, submit.to_bytes(py).unwrap()).unwrap();
+                let resp = server
+                    .handle_submit_request(
+                        py,
+                        submit.nym(py),
+                        submit.request(py),
+                        cc.clone_ref(py),
+                        asn.clone_ref(py),
+                        hash,
+                        age_tuple,
+                        0,
+                        None,
+                    )
+                    .unwrap();
+                responses.push((resp, Bound::new(py, submit).unwrap()));
+            }
+
+            let (resp, submit) = responses.remove(0);
+            client
+                .handle_submit_response(py, resp.clone_ref(py), submit.borrow_mut())
+                .unwrap();
+            let err = client
+                .handle_submit_response(py, resp, submit.borrow_mut())
+                .unwrap_err();
+            assert!(matches!(err, OoniErr::InvalidArgument { .. }));
+        });
+    }
+
+    #[test]
+    fn test_bad_reply_keeps_pending_request() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let server = crate::ServerState::new();
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let other = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            let other_resp = server
+                .handle_registration_request(py, other.borrow().request(py))
+                .unwrap();
+
+            // Not a reply at all
+            let garbage = PyString::new(py, &BASE64_STANDARD.encode(b"garbage")).into();
+            assert!(client
+                .handle_registration_response(py, garbage, req.borrow_mut())
+                .is_err());
+            // A valid reply, but to another request
+            assert!(client
+                .handle_registration_response(py, other_resp, req.borrow_mut())
+                .is_err());
+
+            client
+                .handle_registration_response(py, resp.clone_ref(py), req.borrow_mut())
+                .unwrap();
+            assert!(client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .is_err());
+        });
+    }
+
+    #[test]
+    fn test_state_export() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let server = crate::ServerState::new();
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .unwrap();
+
+            let cc: Py<PyString> = PyString::new(py, "VE").into();
+            let asn: Py<PyString> = PyString::new(py, "AS1234").into();
+            let today = ServerState::today();
+            let age_tuple = (today - 30, today + 1);
+            let hash = test_measurement_hash(py, 1);
+            let submit = client
+                .make_submit_request(
+                    py,
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    hash.clone_ref(py),
+                    age_tuple,
+                    0,
+                    None,
+                )
+                .unwrap();
+
+            // `copy` goes through `__reduce__`, like `pickle` does
+            let deepcopy = py.import("copy").unwrap().getattr("deepcopy").unwrap();
+            let server = deepcopy.call1((Bound::new(py, server).unwrap(),)).unwrap();
+            let server = server.cast::<crate::ServerState>().unwrap().borrow();
+            let client = deepcopy.call1((Bound::new(py, client).unwrap(),)).unwrap();
+            let client = client.cast::<crate::UserState>().unwrap();
+            let submit = deepcopy.call1((Bound::new(py, submit).unwrap(),)).unwrap();
+            let submit = submit.cast::<crate::SubmitRequest>().unwrap();
+
+            let resp = server
+                .handle_submit_request(
+                    py,
+                    submit.borrow().nym(py),
+                    submit.borrow().request(py),
+                    cc,
+                    asn,
+                    hash,
+                    age_tuple,
+                    0,
+                    None,
+                )
+                .unwrap();
+            client
+                .borrow_mut()
+                .handle_submit_response(py, resp, submit.borrow_mut())
+                .unwrap();
+
+            // A handled request has nothing left to export
+            assert!(deepcopy.call1((submit,)).is_err());
+        });
+    }
+
+    #[test]
+    fn test_credential_info() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let server = crate::ServerState::new();
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            assert!(client.get_credential_info().unwrap().is_none());
+
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .unwrap();
+
+            let info = client.get_credential_info().unwrap().unwrap();
+            let today = ServerState::today();
+            assert_eq!(info.age_day(), today);
+            assert_eq!(info.days_since_registration(None), 0);
+            assert_eq!(info.measurement_count(), 0);
+            assert_eq!(
+                info.public_parameters_fingerprint(),
+                hex::encode(server.state.key_id())
+            );
+            let age = info.age(py).unwrap();
+            let date = py.import("datetime").unwrap().getattr("date").unwrap();
+            // Julian day 1721426 is the first day of the proleptic Gregorian calendar
+            let expected = date
+                .call_method1("fromordinal", (today - 1_721_425,))
+                .unwrap();
+            assert!(age.eq(expected).unwrap());
+
+            let policy = crate::SubmitPolicy::new(30, 0, None).unwrap();
+            assert!(!info.is_expired(&policy, None).unwrap());
+            assert!(info.is_expired(&policy, Some(today + 31)).unwrap());
+            let expires = info.expires(py, &policy).unwrap();
+            assert_eq!(
+                expires
+                    .call_method1("__sub__", (age,))
+                    .unwrap()
+                    .getattr("days")
+                    .unwrap()
+                    .extract::<u32>()
+                    .unwrap(),
+                30
+            );
+
+            // The nym id must never be reachable from Python
+            let info = Bound::new(py, info).unwrap();
+            assert!(!info.hasattr("nym_id").unwrap());
+        });
+    }
+
+    type SubmitFixture = (
         crate::ServerState,
         crate::SubmitRequest,
         Py<PyString>,
@@ -762,12 +2029,18 @@ mod tests {
         Py<PyString>,
         (u32, u32),
         u32,
-    ) {
+    );
+
+    fn submit_fixture(py: Python<'_>) -> SubmitFixture {
         let server = crate::ServerState::new();
         let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
-        let req = client.make_registration_request(py).unwrap();
-        let resp = server.handle_registration_request(py, req).unwrap();
-        client.handle_registration_response(py, resp).unwrap();
+        let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+        let resp = server
+            .handle_registration_request(py, req.borrow().request(py))
+            .unwrap();
+        client
+            .handle_registration_response(py, resp, req.borrow_mut())
+            .unwrap();
         let cc: Py<PyString> = PyString::new(py, "VE").into();
         let asn: Py<PyString> = PyString::new(py, "AS1234").into();
         let today = crate::ServerState::today();
@@ -782,6 +2055,7 @@ mod tests {
                 measurement_hash.clone_ref(py),
                 age_tuple,
                 min_msm,
+                None,
             )
             .unwrap();
         (
@@ -806,18 +2080,356 @@ mod tests {
                 .handle_submit_request(
                     py,
                     bad_nym,
-                    submit.request,
+                    submit.request(py),
                     cc,
                     asn,
                     measurement_hash,
                     age_range,
                     min_msm,
+                    None,
                 )
                 .unwrap_err();
             assert!(matches!(err, OoniErr::DeserializationFailed { .. }));
         });
     }
 
+    #[test]
+    fn test_handle_submit_request_rejects_bad_domain() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let (server, submit, _cc, asn, measurement_hash, age_range, min_msm) =
+                submit_fixture(py);
+            let err = server
+                .handle_submit_request(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    PyString::new(py, "ve").into(),
+                    asn,
+                    measurement_hash,
+                    age_range,
+                    min_msm,
+                    None,
+                )
+                .unwrap_err();
+            assert_eq!(err.code(), ErrorCode::BadDomain);
+
+            let err = PyErr::from(err);
+            assert!(err.is_instance_of::<crate::BadDomain>(py));
+            assert!(err.is_instance_of::<crate::CredentialError>(py));
+            let code = err.value(py).getattr("code").unwrap();
+            assert_eq!(
+                code.extract::<crate::ErrorCode>().unwrap(),
+                crate::ErrorCode::BadDomain
+            );
+        });
+    }
+
+    #[test]
+    fn test_submit_outcome() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let (server, submit, cc, asn, measurement_hash, age_range, min_msm) =
+                submit_fixture(py);
+            let (_, outcome) = server
+                .handle_submit_request_with_outcome(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    cc,
+                    asn,
+                    measurement_hash.clone_ref(py),
+                    age_range,
+                    min_msm,
+                    None,
+                )
+                .unwrap();
+            let nym = BASE64_STANDARD
+                .decode(submit.nym(py).to_str(py).unwrap())
+                .unwrap();
+            assert_eq!(outcome.probe_id(py).as_bytes(py), &nym[..]);
+            assert_eq!(outcome.probe_cc(), "VE");
+            assert_eq!(outcome.probe_asn(), "AS1234");
+            let hash = BASE64_STANDARD
+                .decode(measurement_hash.to_str(py).unwrap())
+                .unwrap();
+            assert_eq!(outcome.measurement_hash(py).as_bytes(py), &hash[..]);
+            assert_eq!(outcome.age_range(), age_range);
+            assert_eq!(outcome.measurement_count_range(), (min_msm, u32::MAX));
+            assert_eq!(
+                outcome.public_parameters_fingerprint(),
+                hex::encode(server.state.key_id())
+            );
+        });
+    }
+
+    #[test]
+    fn test_submit_measurement_count_range() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let (server, submit, cc, asn, measurement_hash, age_range, _) = submit_fixture(py);
+
+            // The client proved its count is in [0, u32::MAX), which a server
+            // enforcing an upper bound doesn't accept
+            let policy = crate::SubmitPolicy::new(30, 0, Some(100)).unwrap();
+            let (_, min, max) = policy.submit_args(None).unwrap();
+            let err = server
+                .handle_submit_request(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    measurement_hash.clone_ref(py),
+                    age_range,
+                    min,
+                    max,
+                )
+                .unwrap_err();
+            assert_eq!(err.code(), ErrorCode::ProofRejected);
+
+            // Empty ranges are refused before doing any work
+            for (age_range, min, max) in [((10, 10), 0, None), (age_range, 5, Some(5))] {
+                let err = server
+                    .handle_submit_request(
+                        py,
+                        submit.nym(py),
+                        submit.request(py),
+                        cc.clone_ref(py),
+                        asn.clone_ref(py),
+                        measurement_hash.clone_ref(py),
+                        age_range,
+                        min,
+                        max,
+                    )
+                    .unwrap_err();
+                assert!(matches!(err, OoniErr::InvalidArgument { .. }));
+                assert!(PyErr::from(err).is_instance_of::<PyValueError>(py));
+            }
+
+            // Both sides using the policy agree
+            let mut client = crate::UserState::new(py, server.get_public_parameters(py)).unwrap();
+            let req = Bound::new(py, client.make_registration_request(py).unwrap()).unwrap();
+            let resp = server
+                .handle_registration_request(py, req.borrow().request(py))
+                .unwrap();
+            client
+                .handle_registration_response(py, resp, req.borrow_mut())
+                .unwrap();
+            let (age_range, min, max) = policy.submit_args(None).unwrap();
+            let submit = client
+                .make_submit_request(
+                    py,
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    measurement_hash.clone_ref(py),
+                    age_range,
+                    min,
+                    max,
+                )
+                .unwrap();
+            assert!(server
+                .handle_submit_request(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    cc,
+                    asn,
+                    measurement_hash,
+                    age_range,
+                    min,
+                    max,
+                )
+                .is_ok());
+        });
+    }
+
+    #[test]
+    fn test_submit_batch() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let (server, submit, cc, asn, measurement_hash, age_range, min_msm) =
+                submit_fixture(py);
+            let item = |nym: Py<PyString>, cc: &str| {
+                (
+                    nym,
+                    submit.request(py),
+                    PyString::new(py, cc).unbind(),
+                    asn.clone_ref(py),
+                    measurement_hash.clone_ref(py),
+                )
+            };
+            let short_nym = PyString::new(py, &BASE64_STANDARD.encode([7u8; 31])).unbind();
+            let results = server
+                .handle_submit_requests_batch(
+                    py,
+                    vec![
+                        item(submit.nym(py), cc.to_str(py).unwrap()),
+                        item(submit.nym(py), "ve"),
+                        item(short_nym, "VE"),
+                    ],
+                    age_range,
+                    min_msm,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(results.len(), 3);
+            assert!(results[0].bind(py).is_instance_of::<PyString>());
+            assert!(results[1].bind(py).is_instance_of::<crate::BadDomain>());
+            assert!(results[2]
+                .bind(py)
+                .is_instance_of::<crate::DeserializationFailed>());
+
+            let results = server
+                .handle_submit_requests_batch_bytes(
+                    py,
+                    vec![(
+                        submit.nym_bytes(py),
+                        submit.request_bytes(py),
+                        cc,
+                        asn.clone_ref(py),
+                        PyBytes::new(
+                            py,
+                            &BASE64_STANDARD
+                                .decode(measurement_hash.to_str(py).unwrap())
+                                .unwrap(),
+                        )
+                        .unbind(),
+                    )],
+                    age_range,
+                    min_msm,
+                    None,
+                )
+                .unwrap();
+            assert!(results[0].bind(py).is_instance_of::<PyBytes>());
+
+            // Bad ranges fail the whole call
+            let err = server
+                .handle_submit_requests_batch(py, vec![], age_range, 3, Some(3))
+                .unwrap_err();
+            assert!(matches!(err, OoniErr::InvalidArgument { .. }));
+        });
+    }
+
+    #[test]
+    fn test_submit_async() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let (server, submit, cc, asn, measurement_hash, age_range, min_msm) =
+                submit_fixture(py);
+            let server = Bound::new(py, server).unwrap();
+
+            // There is no event loop to resolve the future with
+            let err = server
+                .borrow()
+                .handle_submit_request_async(
+                    py,
+                    submit.nym(py),
+                    submit.request(py),
+                    cc.clone_ref(py),
+                    asn.clone_ref(py),
+                    measurement_hash.clone_ref(py),
+                    age_range,
+                    min_msm,
+                    None,
+                )
+                .unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyRuntimeError>(py));
+
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("server", &server).unwrap();
+            globals
+                .set_item(
+                    "args",
+                    (
+                        submit.nym(py),
+                        submit.request(py),
+                        cc,
+                        asn,
+                        measurement_hash,
+                        age_range,
+                        min_msm,
+                    ),
+                )
+                .unwrap();
+            py.run(
+                c"import asyncio
+async def main():
+    nym, request, cc, asn, hash, age_range, min_msm = args
+    return await asyncio.gather(
+        server.handle_submit_request_async(*args),
+        server.handle_submit_request_async(nym, request, 've', asn, hash, age_range, min_msm),
+        return_exceptions=True,
+    )
+results = asyncio.run(main())
+",
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            let results = globals.get_item("results").unwrap();
+            assert!(results.get_item(0).unwrap().is_instance_of::<PyString>());
+            assert!(results
+                .get_item(1)
+                .unwrap()
+                .is_instance_of::<crate::BadDomain>());
+        });
+    }
+
+    #[test]
+    fn test_credential_error_attributes() {
+        pyo3::Python::initialize();
+        Python::attach(|py| {
+            let err = PyErr::from(OoniErr::from(CredentialError::TimeThresholdNotMet(3)));
+            assert!(err.is_instance_of::<crate::TimeThresholdNotMet>(py));
+            assert!(err.is_instance_of::<crate::NotYetEligible>(py));
+            let exc = err.value(py);
+            assert_eq!(
+                exc.getattr("days_remaining")
+                    .unwrap()
+                    .extract::<u32>()
+                    .unwrap(),
+                3
+            );
+            assert_eq!(
+                exc.getattr("code")
+                    .unwrap()
+                    .extract::<crate::ErrorCode>()
+                    .unwrap(),
+                crate::ErrorCode::NotYetEligible
+            );
+
+            let err = PyErr::from(OoniErr::from(CredentialError::InvalidField(
+                "age".into(),
+                "does not fit in u32".into(),
+            )));
+            assert!(err.is_instance_of::<crate::InvalidField>(py));
+            let exc = err.value(py);
+            assert_eq!(
+                exc.getattr("field").unwrap().extract::<String>().unwrap(),
+                "age"
+            );
+            assert_eq!(
+                exc.getattr("reason").unwrap().extract::<String>().unwrap(),
+                "does not fit in u32"
+            );
+
+            let err = PyErr::from(OoniErr::from(CredentialError::CountExceeded {
+                count: 100,
+                max: 100,
+            }));
+            assert!(err.is_instance_of::<crate::CountExceeded>(py));
+            assert_eq!(
+                err.value(py)
+                    .getattr("max")
+                    .unwrap()
+                    .extract::<u32>()
+                    .unwrap(),
+                100
+            );
+        });
+    }
+
     fn setup() -> (ThreadRng, UserState, ServerState) {
         let mut rng = thread_rng();
         let server = ServerState::new(&mut rng);
@@ -2,26 +2,88 @@ use std::fmt::Display;
 
 use crate::{exceptions::OoniErr, OoniResult};
 use base64::prelude::*;
-use pyo3::{prelude::*, types::PyString};
+use ooniauth_core::bindings;
+use pyo3::{
+    prelude::*,
+    types::{PyBytes, PyString},
+};
 
 pub fn to_pystring<T: serde::Serialize>(py: Python<'_>, value: &T) -> Py<PyString> {
-    // We consider a bad serialization as a programming error since most of the times
-    // we want to serialize a structure made by us that should be well-formed
-    let bytes =
-        bincode::serialize(&value).unwrap_or_else(|e| panic!("Could not serialize value: {e}"));
+    PyString::new(py, &BASE64_STANDARD.encode(bindings::to_bytes(value))).into()
+}
+
+/// Copy raw bytes into a Python `bytes` object
+pub fn to_pybytes(py: Python<'_>, bytes: &[u8]) -> Py<PyBytes> {
+    PyBytes::new(py, bytes).into()
+}
+
+/// Encode raw bytes, such as a framed protocol message, as a base64 Python string
+pub fn to_pybase64(py: Python<'_>, bytes: &[u8]) -> Py<PyString> {
     PyString::new(py, &BASE64_STANDARD.encode(bytes)).into()
 }
 
+/// Decode a base64 Python string into raw bytes
+pub fn from_pybase64(py: Python<'_>, py_string: &Py<PyString>) -> OoniResult<Vec<u8>> {
+    let s = to_dser_err(py_string.to_str(py))?;
+    to_dser_err(BASE64_STANDARD.decode(s))
+}
+
 pub fn from_pystring<T: serde::de::DeserializeOwned>(
     py: Python<'_>,
     py_string: &Py<PyString>,
 ) -> OoniResult<T> {
-    // We consider bad deserialization an user error, since most of the time
-    // what we are deserializing comes from the user in python world
-    let s = to_dser_err(py_string.to_str(py))?;
-    let bytes = to_dser_err(BASE64_STANDARD.decode(s))?;
-    let result = bincode::deserialize::<T>(bytes.as_ref());
-    to_dser_err(result)
+    Ok(bindings::from_bytes(&from_pybase64(py, py_string)?)?)
+}
+
+/// The two forms of binary values in the Python API: base64 strings, and
+/// `bytes` for the `_bytes` methods. Both forms of a method share one
+/// implementation working on the raw bytes
+pub trait Binary: Sized {
+    /// The raw bytes of a value given from Python
+    fn to_vec(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>>;
+
+    /// The value to return to Python for some raw bytes
+    fn from_slice(py: Python<'_>, bytes: &[u8]) -> Py<Self>;
+
+    /// A measurement body: the UTF-8 text of a string, or the bytes as-is
+    fn measurement(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>>;
+}
+
+impl Binary for PyString {
+    fn to_vec(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>> {
+        from_pybase64(py, value)
+    }
+
+    fn from_slice(py: Python<'_>, bytes: &[u8]) -> Py<Self> {
+        to_pybase64(py, bytes)
+    }
+
+    fn measurement(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>> {
+        Ok(to_dser_err(value.to_str(py))?.as_bytes().to_vec())
+    }
+}
+
+impl Binary for PyBytes {
+    fn to_vec(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>> {
+        Ok(value.as_bytes(py).to_vec())
+    }
+
+    fn from_slice(py: Python<'_>, bytes: &[u8]) -> Py<Self> {
+        to_pybytes(py, bytes)
+    }
+
+    fn measurement(py: Python<'_>, value: &Py<Self>) -> OoniResult<Vec<u8>> {
+        Self::to_vec(py, value)
+    }
+}
+
+/// A 32-byte value, such as a nym or a measurement hash
+pub fn array_32<B: Binary>(py: Python<'_>, value: &Py<B>, name: &str) -> OoniResult<[u8; 32]> {
+    B::to_vec(py, value)?
+        .try_into()
+        .map_err(|value: Vec<u8>| OoniErr::DeserializationFailed {
+            reason: format!("{name} must be 32 bytes, got {}", value.len()),
+        })
 }
 
 fn to_dser_err<T, E: Display>(x: Result<T, E>) -> Result<T, OoniErr> {
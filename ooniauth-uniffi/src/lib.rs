@@ -0,0 +1,230 @@
+//! UniFFI bindings of the client side of the protocol, for the Kotlin (Android)
+//! and Swift (iOS) apps
+//!
+//! The bindings are generated from the compiled library by the `uniffi-bindgen`
+//! binary of this crate, see the README.
+use ooniauth_core::bindings::AlreadyHandled;
+use ooniauth_core::errors::{CredentialError, ErrorCode, RangeError, WireError};
+use ooniauth_core::submit;
+use ooniauth_core::ServerState;
+
+use cmz::CMZError;
+
+#[cfg(feature = "test-server")]
+mod test_server;
+mod user;
+
+#[cfg(feature = "test-server")]
+pub use test_server::*;
+pub use user::*;
+
+uniffi::setup_scaffolding!();
+
+/// Errors raised by the bindings. The variants are the
+/// [`ooniauth_core::errors::ErrorCode`]s, plus `InvalidArgument` for the
+/// arguments rejected before reaching the protocol
+///
+/// In Kotlin, they are the subclasses of `OoniauthException`.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum OoniauthError {
+    #[error("{0}")]
+    CredentialExpired(String),
+    #[error("{0}")]
+    NotYetEligible(String),
+    #[error("{0}")]
+    CountExceeded(String),
+    #[error("{0}")]
+    BadDomain(String),
+    #[error("{0}")]
+    UnknownPublicParameters(String),
+    #[error("{0}")]
+    MalformedMessage(String),
+    #[error("{0}")]
+    ProofRejected(String),
+    #[error("{0}")]
+    InvalidField(String),
+    #[error("{0}")]
+    CredentialMismatch(String),
+    #[error("{0}")]
+    MissingCredential(String),
+    #[error("{0}")]
+    Internal(String),
+    /// A malformed measurement hash, an empty range or a pending request
+    /// handled twice
+    #[error("{0}")]
+    InvalidArgument(String),
+}
+
+impl OoniauthError {
+    fn new(code: ErrorCode, message: String) -> Self {
+        match code {
+            ErrorCode::CredentialExpired => Self::CredentialExpired(message),
+            ErrorCode::NotYetEligible => Self::NotYetEligible(message),
+            ErrorCode::CountExceeded => Self::CountExceeded(message),
+            ErrorCode::BadDomain => Self::BadDomain(message),
+            ErrorCode::UnknownPublicParameters => Self::UnknownPublicParameters(message),
+            ErrorCode::MalformedMessage => Self::MalformedMessage(message),
+            ErrorCode::ProofRejected => Self::ProofRejected(message),
+            ErrorCode::InvalidField => Self::InvalidField(message),
+            ErrorCode::CredentialMismatch => Self::CredentialMismatch(message),
+            ErrorCode::MissingCredential => Self::MissingCredential(message),
+            ErrorCode::Internal => Self::Internal(message),
+        }
+    }
+}
+
+impl From<CredentialError> for OoniauthError {
+    fn from(err: CredentialError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl From<WireError> for OoniauthError {
+    fn from(err: WireError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl From<RangeError> for OoniauthError {
+    fn from(err: RangeError) -> Self {
+        Self::InvalidArgument(err.to_string())
+    }
+}
+
+impl From<AlreadyHandled> for OoniauthError {
+    fn from(err: AlreadyHandled) -> Self {
+        Self::InvalidArgument(err.to_string())
+    }
+}
+
+impl From<CMZError> for OoniauthError {
+    fn from(err: CMZError) -> Self {
+        Self::new(ErrorCode::from(&err), format!("{err:?}"))
+    }
+}
+
+type Result<T, E = OoniauthError> = std::result::Result<T, E>;
+
+/// The public view of the credential, see `UserState.credential_info`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct CredentialInfo {
+    /// Day of the registration, as a Julian day like `today()`
+    pub age: u32,
+    /// Number of measurements submitted with the credential
+    pub measurement_count: u32,
+    /// Fingerprint of the public parameters the credential was issued under
+    pub key_id: Vec<u8>,
+}
+
+impl From<ooniauth_core::CredentialInfo> for CredentialInfo {
+    fn from(info: ooniauth_core::CredentialInfo) -> Self {
+        Self {
+            age: info.age,
+            measurement_count: info.measurement_count,
+            key_id: info.key_id.to_vec(),
+        }
+    }
+}
+
+/// Which credentials the server accepts submissions from. It must match the
+/// policy of the server, or the proofs are rejected
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct SubmitPolicy {
+    /// Oldest accepted registration, in days before today
+    #[uniffi(default = 30)]
+    pub max_age_days: u32,
+    #[uniffi(default = 0)]
+    pub min_measurement_count: u32,
+    /// Excluded upper bound of the measurement count, unbounded if null
+    #[uniffi(default = None)]
+    pub max_measurement_count: Option<u32>,
+}
+
+/// The half-open ranges passed to `UserState.submit_request`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct SubmitRanges {
+    pub age_start: u32,
+    pub age_end: u32,
+    pub min_measurement_count: u32,
+    pub max_measurement_count: u32,
+}
+
+impl From<SubmitPolicy> for submit::SubmitPolicy {
+    fn from(policy: SubmitPolicy) -> Self {
+        Self {
+            max_age_days: policy.max_age_days,
+            min_measurement_count: policy.min_measurement_count,
+            max_measurement_count: policy.max_measurement_count,
+        }
+    }
+}
+
+/// The ranges of `policy` on `today`, a Julian day such as `today()`
+#[uniffi::export]
+pub fn submit_ranges(policy: SubmitPolicy, today: u32) -> Result<SubmitRanges> {
+    let (age_range, measurement_count_range) = submit::SubmitPolicy::from(policy).ranges(today)?;
+    Ok(SubmitRanges {
+        age_start: age_range.start,
+        age_end: age_range.end,
+        min_measurement_count: measurement_count_range.start,
+        max_measurement_count: measurement_count_range.end,
+    })
+}
+
+impl SubmitRanges {
+    fn check(&self) -> Result<()> {
+        Ok(submit::check_submit_ranges(
+            &(self.age_start..self.age_end),
+            &(self.min_measurement_count..self.max_measurement_count),
+        )?)
+    }
+}
+
+/// Today's date as a Julian day, the unit of the credential age
+#[uniffi::export]
+pub fn today() -> u32 {
+    ServerState::today()
+}
+
+/// Hash of a measurement, to pass to `UserState.submit_request`
+#[uniffi::export]
+pub fn submit_measurement_hash(measurement: Vec<u8>) -> Vec<u8> {
+    submit::submit_measurement_hash(&measurement).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_submit_ranges() {
+        let policy = SubmitPolicy {
+            max_age_days: 30,
+            min_measurement_count: 2,
+            max_measurement_count: Some(10),
+        };
+        assert_eq!(
+            submit_ranges(policy.clone(), 100).unwrap(),
+            SubmitRanges {
+                age_start: 70,
+                age_end: 101,
+                min_measurement_count: 2,
+                max_measurement_count: 10,
+            }
+        );
+        assert!(matches!(
+            submit_ranges(policy.clone(), 29),
+            Err(OoniauthError::InvalidArgument(_))
+        ));
+
+        let empty = SubmitPolicy {
+            max_measurement_count: Some(2),
+            ..policy
+        };
+        assert!(matches!(
+            submit_ranges(empty, 100),
+            Err(OoniauthError::InvalidArgument(_))
+        ));
+    }
+}
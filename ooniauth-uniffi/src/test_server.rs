@@ -0,0 +1,61 @@
+//! A server for the tests of the generated bindings, which only cover the
+//! client. Built with the `test-server` feature
+use std::sync::Arc;
+
+use ooniauth_core::registration::open_registration;
+use ooniauth_core::ServerState;
+
+use ooniauth_core::bindings;
+
+use crate::{OoniauthError, Result, SubmitRanges};
+
+#[derive(uniffi::Object)]
+pub struct TestServer(ServerState);
+
+#[uniffi::export]
+impl TestServer {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self(ServerState::new(&mut rand::thread_rng())))
+    }
+
+    pub fn public_parameters(&self) -> Vec<u8> {
+        bindings::to_bytes(self.0.public_parameters_ref())
+    }
+
+    pub fn handle_registration(&self, request: Vec<u8>) -> Result<Vec<u8>> {
+        let (req, framing) = self
+            .0
+            .decode_request::<open_registration::Request>(&request)?;
+        let reply = self.0.open_registration(&mut rand::thread_rng(), req)?;
+        Ok(self.0.encode_reply(&reply, framing))
+    }
+
+    pub fn handle_submit(
+        &self,
+        nym: Vec<u8>,
+        request: Vec<u8>,
+        probe_cc: String,
+        probe_asn: String,
+        measurement_hash: Vec<u8>,
+        ranges: SubmitRanges,
+    ) -> Result<Vec<u8>> {
+        let array_32 = |bytes: Vec<u8>, name: &str| -> Result<[u8; 32]> {
+            bytes
+                .try_into()
+                .map_err(|_| OoniauthError::InvalidArgument(format!("{name} must be 32 bytes")))
+        };
+        let (req, framing) = self.0.decode_request(&request)?;
+        let reply = self.0.handle_submit(
+            &mut rand::thread_rng(),
+            req,
+            &array_32(nym, "nym")?,
+            &probe_cc,
+            &probe_asn,
+            &array_32(measurement_hash, "measurement_hash")?,
+            ranges.age_start..ranges.age_end,
+            ranges.min_measurement_count..ranges.max_measurement_count,
+        )?;
+        Ok(self.0.encode_reply(&reply, framing))
+    }
+}
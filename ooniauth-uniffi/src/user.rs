@@ -0,0 +1,414 @@
+//! Client state and pending requests
+//!
+//! Each request method returns an object holding the message to send and the
+//! state needed to handle the response, which the matching `handle_*` method
+//! consumes once the response is handled. Several requests may be pending at
+//! once.
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ooniauth_core::bindings::{self, Pending};
+use ooniauth_core::errors::CredentialError;
+use ooniauth_core::registration::{open_registration, UserAuthCredential};
+use ooniauth_core::submit::submit;
+use ooniauth_core::update::update;
+use ooniauth_core::PublicParameters;
+
+use crate::{CredentialInfo, OoniauthError, Result, SubmitRanges};
+
+/// The client state: the server public parameters and the credential
+#[derive(uniffi::Object)]
+pub struct UserState(Mutex<ooniauth_core::UserState>);
+
+#[uniffi::export]
+impl UserState {
+    /// A client without a credential for the given public parameters
+    #[uniffi::constructor]
+    pub fn new(public_parameters: Vec<u8>) -> Result<Arc<Self>> {
+        let pp: PublicParameters = bindings::from_bytes(&public_parameters)?;
+        Ok(Self::wrap(ooniauth_core::UserState::new(pp)))
+    }
+
+    /// Restores a client exported by `to_bytes`
+    #[uniffi::constructor]
+    pub fn from_bytes(data: Vec<u8>) -> Result<Arc<Self>> {
+        Ok(Self::wrap(bindings::from_bytes(&data)?))
+    }
+
+    /// Exports the public parameters and the credential
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bindings::to_bytes(&*self.lock())
+    }
+
+    /// Exports the credential. Fails with `MissingCredential` before registration
+    pub fn credential(&self) -> Result<Vec<u8>> {
+        let user = self.lock();
+        let credential = user
+            .get_credential()
+            .ok_or(CredentialError::MissingCredential)?;
+        Ok(bindings::to_bytes(credential))
+    }
+
+    /// Imports a credential exported by `credential`, replacing the current one
+    pub fn set_credential(&self, credential: Vec<u8>) -> Result<()> {
+        let credential: UserAuthCredential = bindings::from_bytes(&credential)?;
+        self.lock().set_credential(credential);
+        Ok(())
+    }
+
+    /// Replaces the public parameters, after the server rotated its keys.
+    /// Follow with a credential update
+    pub fn set_public_parameters(&self, public_parameters: Vec<u8>) -> Result<()> {
+        self.lock().pp = bindings::from_bytes(&public_parameters)?;
+        Ok(())
+    }
+
+    /// What can be shown to the user about the credential, null before
+    /// registration
+    pub fn credential_info(&self) -> Result<Option<CredentialInfo>> {
+        Ok(self.lock().credential_info()?.map(CredentialInfo::from))
+    }
+
+    pub fn registration_request(&self) -> Result<Arc<RegistrationRequest>> {
+        let user = self.lock();
+        let (req, state) = user.request(&mut rand::thread_rng())?;
+        Ok(Arc::new(RegistrationRequest(Mutex::new(Pending::new(
+            (),
+            user.encode_request(&req),
+            state,
+        )))))
+    }
+
+    /// Stores the credential issued by the server
+    pub fn handle_registration_response(
+        &self,
+        pending: Arc<RegistrationRequest>,
+        response: Vec<u8>,
+    ) -> Result<()> {
+        let mut user = self.lock();
+        let reply = user.decode_reply::<open_registration::Reply>(&response)?;
+        pending
+            .0
+            .lock()
+            .unwrap()
+            .handle("registration request", |state| {
+                Ok(user.handle_response(state, reply)?)
+            })
+    }
+
+    /// Makes a submit request for a measurement, whose hash comes from
+    /// `submit_measurement_hash`. The ranges must match the ones of the server
+    pub fn submit_request(
+        &self,
+        probe_cc: String,
+        probe_asn: String,
+        measurement_hash: Vec<u8>,
+        ranges: SubmitRanges,
+    ) -> Result<Arc<SubmitRequest>> {
+        let measurement_hash: [u8; 32] = measurement_hash.try_into().map_err(|hash: Vec<u8>| {
+            OoniauthError::InvalidArgument(format!(
+                "measurement_hash is {} bytes long, expected 32",
+                hash.len()
+            ))
+        })?;
+        ranges.check()?;
+
+        let user = self.lock();
+        let ((req, state), nym) = user.submit_request(
+            &mut rand::thread_rng(),
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            ranges.age_start..ranges.age_end,
+            ranges.min_measurement_count..ranges.max_measurement_count,
+        )?;
+        Ok(Arc::new(SubmitRequest(Mutex::new(Pending::new(
+            nym,
+            user.encode_request(&req),
+            state,
+        )))))
+    }
+
+    /// Replaces the credential with the one issued by the server in response
+    /// to a submission
+    pub fn handle_submit_response(
+        &self,
+        pending: Arc<SubmitRequest>,
+        response: Vec<u8>,
+    ) -> Result<()> {
+        let mut user = self.lock();
+        let reply = user.decode_reply::<submit::Reply>(&response)?;
+        pending.0.lock().unwrap().handle("submit request", |state| {
+            Ok(user.handle_submit_response(state, reply)?)
+        })
+    }
+
+    /// Makes a request to move the credential to the current public parameters
+    pub fn update_request(&self) -> Result<Arc<UpdateRequest>> {
+        let user = self.lock();
+        let (req, state) = user.update_request(&mut rand::thread_rng())?;
+        Ok(Arc::new(UpdateRequest(Mutex::new(Pending::new(
+            (),
+            user.encode_request(&req),
+            state,
+        )))))
+    }
+
+    /// Stores the updated credential
+    pub fn handle_update_response(
+        &self,
+        pending: Arc<UpdateRequest>,
+        response: Vec<u8>,
+    ) -> Result<()> {
+        let mut user = self.lock();
+        let reply = user.decode_reply::<update::Reply>(&response)?;
+        pending.0.lock().unwrap().handle("update request", |state| {
+            Ok(user.handle_update_response(state, reply)?)
+        })
+    }
+}
+
+impl UserState {
+    fn wrap(user: ooniauth_core::UserState) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(user)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ooniauth_core::UserState> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// A registration request waiting for the server response
+///
+/// Send `request` to the server and pass this object to
+/// `UserState.handle_registration_response` along with the response.
+/// `to_bytes` and `from_bytes` keep it across restarts.
+#[derive(uniffi::Object)]
+pub struct RegistrationRequest(Mutex<Pending<(), open_registration::ClientState>>);
+
+#[uniffi::export]
+impl RegistrationRequest {
+    #[uniffi::constructor]
+    pub fn from_bytes(data: Vec<u8>) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self(Mutex::new(bindings::from_bytes(&data)?))))
+    }
+
+    pub fn request(&self) -> Vec<u8> {
+        self.0.lock().unwrap().request.clone()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.lock().unwrap().to_bytes("registration request")?)
+    }
+}
+
+/// A measurement submission waiting for the server response
+///
+/// Send `nym` and `request` to the server and pass this object to
+/// `UserState.handle_submit_response` along with the response. `to_bytes` and
+/// `from_bytes` keep it across restarts.
+#[derive(uniffi::Object)]
+pub struct SubmitRequest(Mutex<Pending<[u8; 32], submit::ClientState>>);
+
+#[uniffi::export]
+impl SubmitRequest {
+    #[uniffi::constructor]
+    pub fn from_bytes(data: Vec<u8>) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self(Mutex::new(bindings::from_bytes(&data)?))))
+    }
+
+    pub fn nym(&self) -> Vec<u8> {
+        self.0.lock().unwrap().nym.to_vec()
+    }
+
+    pub fn request(&self) -> Vec<u8> {
+        self.0.lock().unwrap().request.clone()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.lock().unwrap().to_bytes("submit request")?)
+    }
+}
+
+/// A credential update request waiting for the server response
+///
+/// Send `request` to the server and pass this object to
+/// `UserState.handle_update_response` along with the response. `to_bytes` and
+/// `from_bytes` keep it across restarts.
+#[derive(uniffi::Object)]
+pub struct UpdateRequest(Mutex<Pending<(), update::ClientState>>);
+
+#[uniffi::export]
+impl UpdateRequest {
+    #[uniffi::constructor]
+    pub fn from_bytes(data: Vec<u8>) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self(Mutex::new(bindings::from_bytes(&data)?))))
+    }
+
+    pub fn request(&self) -> Vec<u8> {
+        self.0.lock().unwrap().request.clone()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.lock().unwrap().to_bytes("update request")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{submit_measurement_hash, submit_ranges, today, SubmitPolicy};
+    use ooniauth_core::ServerState;
+
+    fn policy() -> SubmitPolicy {
+        SubmitPolicy {
+            max_age_days: 30,
+            min_measurement_count: 0,
+            max_measurement_count: None,
+        }
+    }
+
+    fn register(server: &ServerState) -> Arc<UserState> {
+        let user = UserState::new(bindings::to_bytes(server.public_parameters_ref())).unwrap();
+        let pending = user.registration_request().unwrap();
+        let (req, framing) = server.decode_request(&pending.request()).unwrap();
+        let reply = server
+            .open_registration(&mut rand::thread_rng(), req)
+            .unwrap();
+        user.handle_registration_response(pending, server.encode_reply(&reply, framing))
+            .unwrap();
+        user
+    }
+
+    #[test]
+    fn test_register_and_submit() {
+        let server = ServerState::new(&mut rand::thread_rng());
+        let user = register(&server);
+        let info = user.credential_info().unwrap().unwrap();
+        assert_eq!(info.age, today());
+        assert_eq!(info.measurement_count, 0);
+
+        let ranges = submit_ranges(policy(), today()).unwrap();
+        let hash = submit_measurement_hash(b"measurement".to_vec());
+        let pending = user
+            .submit_request("US".into(), "AS1234".into(), hash.clone(), ranges)
+            .unwrap();
+        // A pending request survives a restart
+        let pending = SubmitRequest::from_bytes(pending.to_bytes().unwrap()).unwrap();
+        let (req, framing) = server.decode_request(&pending.request()).unwrap();
+        let reply = server
+            .handle_submit(
+                &mut rand::thread_rng(),
+                req,
+                &pending.nym().try_into().unwrap(),
+                "US",
+                "AS1234",
+                &hash.clone().try_into().unwrap(),
+                ranges.age_start..ranges.age_end,
+                ranges.min_measurement_count..ranges.max_measurement_count,
+            )
+            .unwrap();
+        let response = server.encode_reply(&reply, framing);
+
+        // The proof is bound to the probe domain
+        let (req, _) = server.decode_request(&pending.request()).unwrap();
+        let err = server
+            .handle_submit(
+                &mut rand::thread_rng(),
+                req,
+                &pending.nym().try_into().unwrap(),
+                "IT",
+                "AS1234",
+                &hash.clone().try_into().unwrap(),
+                ranges.age_start..ranges.age_end,
+                ranges.min_measurement_count..ranges.max_measurement_count,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            OoniauthError::from(err),
+            OoniauthError::ProofRejected(_)
+        ));
+
+        user.handle_submit_response(pending.clone(), response.clone())
+            .unwrap();
+        assert_eq!(
+            user.credential_info().unwrap().unwrap().measurement_count,
+            1
+        );
+
+        assert!(matches!(
+            user.handle_submit_response(pending.clone(), response),
+            Err(OoniauthError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            pending.to_bytes(),
+            Err(OoniauthError::InvalidArgument(_))
+        ));
+
+        let restored = UserState::from_bytes(user.to_bytes()).unwrap();
+        assert_eq!(restored.credential().unwrap(), user.credential().unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let server = ServerState::new(&mut rand::thread_rng());
+        let user = UserState::new(bindings::to_bytes(server.public_parameters_ref())).unwrap();
+        assert_eq!(user.credential_info().unwrap(), None);
+        assert!(matches!(
+            user.credential(),
+            Err(OoniauthError::MissingCredential(_))
+        ));
+        assert!(matches!(
+            user.update_request(),
+            Err(OoniauthError::MissingCredential(_))
+        ));
+        assert!(matches!(
+            UserState::new(vec![1, 2, 3]),
+            Err(OoniauthError::MalformedMessage(_))
+        ));
+
+        let user = register(&server);
+        let ranges = submit_ranges(policy(), today()).unwrap();
+        assert!(matches!(
+            user.submit_request("US".into(), "AS1234".into(), vec![0; 31], ranges),
+            Err(OoniauthError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            user.submit_request("USA".into(), "AS1234".into(), vec![0; 32], ranges),
+            Err(OoniauthError::BadDomain(_))
+        ));
+        assert!(matches!(
+            user.handle_registration_response(user.registration_request().unwrap(), vec![0; 8]),
+            Err(OoniauthError::MalformedMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_bad_response_keeps_pending_request() {
+        let server = ServerState::new(&mut rand::thread_rng());
+        let user = UserState::new(bindings::to_bytes(server.public_parameters_ref())).unwrap();
+        let pending = user.registration_request().unwrap();
+        let other = user.registration_request().unwrap();
+        let reply_to = |pending: &RegistrationRequest| {
+            let (req, framing) = server.decode_request(&pending.request()).unwrap();
+            let reply = server
+                .open_registration(&mut rand::thread_rng(), req)
+                .unwrap();
+            server.encode_reply(&reply, framing)
+        };
+        let response = reply_to(&pending);
+
+        assert!(matches!(
+            user.handle_registration_response(pending.clone(), vec![0; 8]),
+            Err(OoniauthError::MalformedMessage(_))
+        ));
+        // A valid response, but to another request
+        assert!(user
+            .handle_registration_response(pending.clone(), reply_to(&other))
+            .is_err());
+        assert!(pending.to_bytes().is_ok());
+
+        user.handle_registration_response(pending.clone(), response)
+            .unwrap();
+        assert!(user.credential_info().unwrap().is_some());
+    }
+}
@@ -0,0 +1,37 @@
+//! Checks that the Kotlin and Swift bindings are generated with the expected API
+mod common;
+
+use std::fs;
+
+#[test]
+fn test_kotlin_bindings() {
+    let out_dir = common::generate_bindings("kotlin");
+    let kotlin = fs::read_to_string(out_dir.join("org/ooni/userauth/ooniauth_uniffi.kt")).unwrap();
+    for item in [
+        "open class UserState",
+        "open class SubmitRequest",
+        "data class SubmitPolicy",
+        "sealed class OoniauthException",
+        "class CredentialExpired(message: String) : OoniauthException(message)",
+        "fun `submitRanges`(`policy`: SubmitPolicy, `today`: kotlin.UInt): SubmitRanges",
+    ] {
+        assert!(kotlin.contains(item), "{item} is missing");
+    }
+}
+
+#[test]
+fn test_swift_bindings() {
+    let out_dir = common::generate_bindings("swift");
+    let swift = fs::read_to_string(out_dir.join("OoniAuth.swift")).unwrap();
+    for item in [
+        "open class UserState",
+        "public struct SubmitPolicy",
+        "public enum OoniauthError",
+        "case CredentialExpired(message: String)",
+        "public func submitRanges(policy: SubmitPolicy, today: UInt32)throws  -> SubmitRanges",
+    ] {
+        assert!(swift.contains(item), "{item} is missing");
+    }
+    assert!(out_dir.join("OoniAuthUniFFI.h").exists());
+    assert!(out_dir.join("OoniAuthUniFFI.modulemap").exists());
+}
@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory holding the library, next to the test binaries which live in
+/// `<target>/<profile>/deps`
+pub fn profile_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_owned()
+}
+
+/// Runs `uniffi-bindgen` on the library, writing the bindings for `language`
+/// under `<tmp>/<language>`
+pub fn generate_bindings(language: &str) -> PathBuf {
+    let library = profile_dir().join(format!(
+        "{}ooniauth_uniffi{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    assert!(library.exists(), "{} is missing", library.display());
+
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(language);
+    let status = Command::new(env!("CARGO_BIN_EXE_uniffi-bindgen"))
+        .args([
+            "generate",
+            "--no-format",
+            "--language",
+            language,
+            "--library",
+        ])
+        .arg(&library)
+        .arg("--out-dir")
+        .arg(&out_dir)
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "generating the {language} bindings failed"
+    );
+    out_dir
+}
@@ -0,0 +1,46 @@
+//! Runs `tests/kotlin/test_ooniauth.kts` against the generated bindings
+//!
+//! Needs `kotlinc` (or `$KOTLINC`) and the JNA jar in `$CLASSPATH`, run it
+//! with `cargo test -p ooniauth-uniffi --features test-server --test kotlin`.
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_kotlin() {
+    let out_dir = common::generate_bindings("kotlin");
+    let kotlinc = std::env::var("KOTLINC").unwrap_or_else(|_| "kotlinc".into());
+    let classpath = std::env::var("CLASSPATH").expect("CLASSPATH must contain the JNA jar");
+
+    let jar = out_dir.join("ooniauth.jar");
+    let status = Command::new(&kotlinc)
+        .arg(out_dir.join("org/ooni/userauth/ooniauth_uniffi.kt"))
+        .arg("-classpath")
+        .arg(&classpath)
+        .arg("-d")
+        .arg(&jar)
+        .status()
+        .expect("kotlinc is needed, set KOTLINC to pick one");
+    assert!(status.success(), "compiling the bindings failed");
+
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/kotlin/test_ooniauth.kts");
+    let output = Command::new(&kotlinc)
+        .arg("-classpath")
+        .arg(format!("{classpath}:{}", jar.display()))
+        .arg("-J-ea")
+        .arg(format!(
+            "-J-Djna.library.path={}",
+            common::profile_dir().display()
+        ))
+        .arg("-script")
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "test_ooniauth.kts failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,63 @@
+// Exercises the Kotlin bindings against `TestServer`, see `tests/kotlin.rs`
+import org.ooni.userauth.*
+
+inline fun <reified T : Throwable> assertThrows(block: () -> Unit) {
+    try {
+        block()
+    } catch (e: Throwable) {
+        assert(e is T) { "expected ${T::class.simpleName}, got $e" }
+        return
+    }
+    throw AssertionError("expected ${T::class.simpleName}")
+}
+
+val server = TestServer()
+val user = UserState(server.publicParameters())
+assert(user.credentialInfo() == null)
+assertThrows<OoniauthException.MissingCredential> { user.credential() }
+assertThrows<OoniauthException.MalformedMessage> { UserState(byteArrayOf(1, 2, 3)) }
+
+// Registration, with the pending request kept across a restart
+val registration = RegistrationRequest.fromBytes(user.registrationRequest().toBytes())
+user.handleRegistrationResponse(registration, server.handleRegistration(registration.request()))
+assertThrows<OoniauthException.InvalidArgument> { registration.toBytes() }
+val info = user.credentialInfo()!!
+assert(info.age == today())
+assert(info.measurementCount == 0u)
+
+// Submission
+val ranges = submitRanges(SubmitPolicy(maxMeasurementCount = 10u), today())
+val hash = submitMeasurementHash("measurement".toByteArray())
+assert(hash.size == 32)
+val submission = user.submitRequest("US", "AS1234", hash, ranges)
+val response = server.handleSubmit(
+    submission.nym(), submission.request(), "US", "AS1234", hash, ranges
+)
+user.handleSubmitResponse(submission, response)
+assert(user.credentialInfo()!!.measurementCount == 1u)
+assertThrows<OoniauthException.InvalidArgument> {
+    user.handleSubmitResponse(submission, response)
+}
+
+// Errors
+assertThrows<OoniauthException.BadDomain> { user.submitRequest("USA", "AS1234", hash, ranges) }
+assertThrows<OoniauthException.InvalidArgument> {
+    user.submitRequest("US", "AS1234", hash.copyOf(31), ranges)
+}
+assertThrows<OoniauthException.InvalidArgument> {
+    submitRanges(SubmitPolicy(minMeasurementCount = 10u, maxMeasurementCount = 10u), today())
+}
+assertThrows<OoniauthException.ProofRejected> {
+    server.handleSubmit(
+        submission.nym(), submission.request(), "IT", "AS1234", hash, ranges
+    )
+}
+
+// Persistence
+val restored = UserState.fromBytes(user.toBytes())
+assert(restored.credential().contentEquals(user.credential()))
+val other = UserState(server.publicParameters())
+other.setCredential(user.credential())
+assert(other.credentialInfo()!!.keyId.contentEquals(info.keyId))
+
+println("ok")
@@ -0,0 +1,218 @@
+//! WebAssembly bindings of the client side of the protocol, for browsers and
+//! Node
+//!
+//! Build the npm package with `wasm-pack build ooniauth-wasm`, which also
+//! writes the TypeScript declarations. Binary values (keys, credentials,
+//! messages, hashes) are `Uint8Array`s, days are Julian days like `today()`.
+use cmz::CMZError;
+use ooniauth_core::bindings::AlreadyHandled;
+use ooniauth_core::errors::{CredentialError, ErrorCode, RangeError, WireError};
+use ooniauth_core::{submit, ServerState};
+use wasm_bindgen::prelude::*;
+
+mod user;
+
+pub use user::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const ERROR_TS: &str = r#"
+/**
+ * The `code` of the errors thrown by this package: the names of
+ * `ooniauth_core::errors::ErrorCode`, plus `invalid_argument`.
+ */
+export type ErrorCode =
+  | "credential_expired"
+  | "not_yet_eligible"
+  | "count_exceeded"
+  | "bad_domain"
+  | "unknown_public_parameters"
+  | "malformed_message"
+  | "proof_rejected"
+  | "invalid_field"
+  | "credential_mismatch"
+  | "missing_credential"
+  | "internal"
+  | "invalid_argument";
+
+export interface OoniauthError extends Error {
+  name: "OoniauthError";
+  code: ErrorCode;
+}
+"#;
+
+/// Thrown to JavaScript as an `Error` named `OoniauthError`, with the stable
+/// name of the error code in `code`
+#[derive(Debug)]
+pub struct Error {
+    code: &'static str,
+    message: String,
+}
+
+impl Error {
+    fn new(code: ErrorCode, message: String) -> Self {
+        Self {
+            code: code.as_str(),
+            message,
+        }
+    }
+
+    /// A malformed hash, an empty range or a pending request handled twice
+    fn invalid_argument(message: impl Into<String>) -> Self {
+        Self {
+            code: "invalid_argument",
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        let error = js_sys::Error::new(&err.message);
+        error.set_name("OoniauthError");
+        js_sys::Reflect::set(&error, &"code".into(), &err.code.into())
+            .expect("setting a property of a new Error");
+        error.into()
+    }
+}
+
+impl From<CredentialError> for Error {
+    fn from(err: CredentialError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl From<WireError> for Error {
+    fn from(err: WireError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl From<RangeError> for Error {
+    fn from(err: RangeError) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+
+impl From<AlreadyHandled> for Error {
+    fn from(err: AlreadyHandled) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+
+impl From<CMZError> for Error {
+    fn from(err: CMZError) -> Self {
+        Self::new(ErrorCode::from(&err), format!("{err:?}"))
+    }
+}
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Which credentials the server accepts submissions from. It must match the
+/// policy of the server, or the proofs are rejected
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitPolicy(submit::SubmitPolicy);
+
+#[wasm_bindgen]
+impl SubmitPolicy {
+    /// Defaults to 30 days and any measurement count. Fails if
+    /// `minMeasurementCount` is not lower than `maxMeasurementCount`
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        max_age_days: Option<u32>,
+        min_measurement_count: Option<u32>,
+        max_measurement_count: Option<u32>,
+    ) -> Result<SubmitPolicy> {
+        let default = submit::SubmitPolicy::default();
+        Ok(Self(submit::SubmitPolicy::new(
+            max_age_days.unwrap_or(default.max_age_days),
+            min_measurement_count.unwrap_or(default.min_measurement_count),
+            max_measurement_count,
+        )?))
+    }
+
+    #[wasm_bindgen(getter, js_name = maxAgeDays)]
+    pub fn max_age_days(&self) -> u32 {
+        self.0.max_age_days
+    }
+
+    #[wasm_bindgen(getter, js_name = minMeasurementCount)]
+    pub fn min_measurement_count(&self) -> u32 {
+        self.0.min_measurement_count
+    }
+
+    /// Excluded upper bound of the measurement count, unbounded if undefined
+    #[wasm_bindgen(getter, js_name = maxMeasurementCount)]
+    pub fn max_measurement_count(&self) -> Option<u32> {
+        self.0.max_measurement_count
+    }
+
+    /// The ranges accepted on `today`, which defaults to `today()`
+    pub fn ranges(&self, today: Option<u32>) -> Result<SubmitRanges> {
+        let (age_range, measurement_count_range) =
+            self.0.ranges(today.unwrap_or_else(ServerState::today))?;
+        Ok(SubmitRanges {
+            age_start: age_range.start,
+            age_end: age_range.end,
+            min_measurement_count: measurement_count_range.start,
+            max_measurement_count: measurement_count_range.end,
+        })
+    }
+}
+
+/// The half-open ranges passed to `UserState.submitRequest`
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitRanges {
+    #[wasm_bindgen(js_name = ageStart)]
+    pub age_start: u32,
+    #[wasm_bindgen(js_name = ageEnd)]
+    pub age_end: u32,
+    #[wasm_bindgen(js_name = minMeasurementCount)]
+    pub min_measurement_count: u32,
+    #[wasm_bindgen(js_name = maxMeasurementCount)]
+    pub max_measurement_count: u32,
+}
+
+#[wasm_bindgen]
+impl SubmitRanges {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        age_start: u32,
+        age_end: u32,
+        min_measurement_count: u32,
+        max_measurement_count: u32,
+    ) -> Self {
+        Self {
+            age_start,
+            age_end,
+            min_measurement_count,
+            max_measurement_count,
+        }
+    }
+}
+
+impl SubmitRanges {
+    fn check(&self) -> Result<()> {
+        Ok(submit::check_submit_ranges(
+            &(self.age_start..self.age_end),
+            &(self.min_measurement_count..self.max_measurement_count),
+        )?)
+    }
+}
+
+/// Today's date as a Julian day, from the clock of the JavaScript host
+#[wasm_bindgen]
+pub fn today() -> u32 {
+    ServerState::today()
+}
+
+/// Hash of a measurement, to pass to `UserState.submitRequest`
+#[wasm_bindgen(js_name = submitMeasurementHash)]
+pub fn submit_measurement_hash(measurement: &[u8]) -> Vec<u8> {
+    submit::submit_measurement_hash(measurement).to_vec()
+}
@@ -0,0 +1,271 @@
+//! Client state and pending requests
+//!
+//! Each request method returns an object holding the message to send and the
+//! state needed to handle the response, which the matching `handle*` method
+//! consumes once the response is handled. Several requests may be pending at
+//! once.
+use ooniauth_core::bindings::{self, Pending};
+use ooniauth_core::errors::CredentialError;
+use ooniauth_core::registration::{open_registration, UserAuthCredential};
+use ooniauth_core::submit::submit;
+use ooniauth_core::update::update;
+use ooniauth_core::{PublicParameters, ServerState};
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, Result, SubmitRanges};
+
+/// The client state: the server public parameters and the credential
+#[wasm_bindgen]
+pub struct UserState(ooniauth_core::UserState);
+
+#[wasm_bindgen]
+impl UserState {
+    /// A client without a credential for the given public parameters
+    #[wasm_bindgen(constructor)]
+    pub fn new(public_parameters: &[u8]) -> Result<UserState> {
+        let pp: PublicParameters = bindings::from_bytes(public_parameters)?;
+        Ok(Self(ooniauth_core::UserState::new(pp)))
+    }
+
+    /// Restores a client exported by `toBytes`
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<UserState> {
+        Ok(Self(bindings::from_bytes(data)?))
+    }
+
+    /// Exports the public parameters and the credential
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bindings::to_bytes(&self.0)
+    }
+
+    /// Exports the credential. Throws `missing_credential` before registration
+    pub fn credential(&self) -> Result<Vec<u8>> {
+        let credential = self
+            .0
+            .get_credential()
+            .ok_or(CredentialError::MissingCredential)?;
+        Ok(bindings::to_bytes(credential))
+    }
+
+    /// Imports a credential exported by `credential`, replacing the current one
+    #[wasm_bindgen(js_name = setCredential)]
+    pub fn set_credential(&mut self, credential: &[u8]) -> Result<()> {
+        let credential: UserAuthCredential = bindings::from_bytes(credential)?;
+        self.0.set_credential(credential);
+        Ok(())
+    }
+
+    /// Replaces the public parameters, after the server rotated its keys.
+    /// Follow with a credential update
+    #[wasm_bindgen(js_name = setPublicParameters)]
+    pub fn set_public_parameters(&mut self, public_parameters: &[u8]) -> Result<()> {
+        self.0.pp = bindings::from_bytes(public_parameters)?;
+        Ok(())
+    }
+
+    /// What can be shown to the user about the credential, undefined before
+    /// registration
+    #[wasm_bindgen(js_name = credentialInfo)]
+    pub fn credential_info(&self) -> Result<Option<CredentialInfo>> {
+        Ok(self.0.credential_info()?.map(|info| CredentialInfo {
+            age: info.age,
+            measurement_count: info.measurement_count,
+            key_id: info.key_id.to_vec(),
+        }))
+    }
+
+    #[wasm_bindgen(js_name = registrationRequest)]
+    pub fn registration_request(&self) -> Result<RegistrationRequest> {
+        let (req, state) = self.0.request(&mut rand::thread_rng())?;
+        Ok(RegistrationRequest(Pending::new(
+            (),
+            self.0.encode_request(&req),
+            state,
+        )))
+    }
+
+    /// Stores the credential issued by the server, checking its date against
+    /// `today`, which defaults to `today()`
+    #[wasm_bindgen(js_name = handleRegistrationResponse)]
+    pub fn handle_registration_response(
+        &mut self,
+        pending: &mut RegistrationRequest,
+        response: &[u8],
+        today: Option<u32>,
+    ) -> Result<()> {
+        let today = today.unwrap_or_else(ServerState::today);
+        pending.0.handle("registration request", |state| {
+            let reply = self.0.decode_reply::<open_registration::Reply>(response)?;
+            Ok(self.0.handle_response_at(state, reply, today)?)
+        })
+    }
+
+    /// Makes a submit request for a measurement, whose hash comes from
+    /// `submitMeasurementHash`. The ranges must match the ones of the server
+    #[wasm_bindgen(js_name = submitRequest)]
+    pub fn submit_request(
+        &self,
+        probe_cc: String,
+        probe_asn: String,
+        measurement_hash: &[u8],
+        ranges: &SubmitRanges,
+    ) -> Result<SubmitRequest> {
+        let measurement_hash: [u8; 32] = measurement_hash.try_into().map_err(|_| {
+            Error::invalid_argument(format!(
+                "measurementHash is {} bytes long, expected 32",
+                measurement_hash.len()
+            ))
+        })?;
+        ranges.check()?;
+
+        let ((req, state), nym) = self.0.submit_request(
+            &mut rand::thread_rng(),
+            probe_cc,
+            probe_asn,
+            &measurement_hash,
+            ranges.age_start..ranges.age_end,
+            ranges.min_measurement_count..ranges.max_measurement_count,
+        )?;
+        Ok(SubmitRequest(Pending::new(
+            nym,
+            self.0.encode_request(&req),
+            state,
+        )))
+    }
+
+    /// Replaces the credential with the one issued by the server in response
+    /// to a submission
+    #[wasm_bindgen(js_name = handleSubmitResponse)]
+    pub fn handle_submit_response(
+        &mut self,
+        pending: &mut SubmitRequest,
+        response: &[u8],
+    ) -> Result<()> {
+        pending.0.handle("submit request", |state| {
+            let reply = self.0.decode_reply::<submit::Reply>(response)?;
+            Ok(self.0.handle_submit_response(state, reply)?)
+        })
+    }
+
+    /// Makes a request to move the credential to the current public parameters
+    #[wasm_bindgen(js_name = updateRequest)]
+    pub fn update_request(&self) -> Result<UpdateRequest> {
+        let (req, state) = self.0.update_request(&mut rand::thread_rng())?;
+        Ok(UpdateRequest(Pending::new(
+            (),
+            self.0.encode_request(&req),
+            state,
+        )))
+    }
+
+    /// Stores the updated credential
+    #[wasm_bindgen(js_name = handleUpdateResponse)]
+    pub fn handle_update_response(
+        &mut self,
+        pending: &mut UpdateRequest,
+        response: &[u8],
+    ) -> Result<()> {
+        pending.0.handle("update request", |state| {
+            let reply = self.0.decode_reply::<update::Reply>(response)?;
+            Ok(self.0.handle_update_response(state, reply)?)
+        })
+    }
+}
+
+/// The public view of the credential, see `UserState.credentialInfo`
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialInfo {
+    /// Day of the registration
+    pub age: u32,
+    /// Number of measurements submitted with the credential
+    #[wasm_bindgen(js_name = measurementCount)]
+    pub measurement_count: u32,
+    /// Fingerprint of the public parameters the credential was issued under
+    #[wasm_bindgen(js_name = keyId)]
+    pub key_id: Vec<u8>,
+}
+
+/// A registration request waiting for the server response
+///
+/// Send `request` to the server and pass this object to
+/// `UserState.handleRegistrationResponse` along with the response.
+/// `toBytes` and `fromBytes` keep it across restarts.
+#[wasm_bindgen]
+pub struct RegistrationRequest(Pending<(), open_registration::ClientState>);
+
+#[wasm_bindgen]
+impl RegistrationRequest {
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<RegistrationRequest> {
+        Ok(Self(bindings::from_bytes(data)?))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.0.request.clone()
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.to_bytes("registration request")?)
+    }
+}
+
+/// A measurement submission waiting for the server response
+///
+/// Send `nym` and `request` to the server and pass this object to
+/// `UserState.handleSubmitResponse` along with the response. `toBytes` and
+/// `fromBytes` keep it across restarts.
+#[wasm_bindgen]
+pub struct SubmitRequest(Pending<[u8; 32], submit::ClientState>);
+
+#[wasm_bindgen]
+impl SubmitRequest {
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<SubmitRequest> {
+        Ok(Self(bindings::from_bytes(data)?))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn nym(&self) -> Vec<u8> {
+        self.0.nym.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.0.request.clone()
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.to_bytes("submit request")?)
+    }
+}
+
+/// A credential update request waiting for the server response
+///
+/// Send `request` to the server and pass this object to
+/// `UserState.handleUpdateResponse` along with the response. `toBytes` and
+/// `fromBytes` keep it across restarts.
+#[wasm_bindgen]
+pub struct UpdateRequest(Pending<(), update::ClientState>);
+
+#[wasm_bindgen]
+impl UpdateRequest {
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<UpdateRequest> {
+        Ok(Self(bindings::from_bytes(data)?))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.0.request.clone()
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(self.0.to_bytes("update request")?)
+    }
+}
@@ -0,0 +1,201 @@
+//! Runs in Node with `wasm-pack test --node ooniauth-wasm`
+#![cfg(target_arch = "wasm32")]
+
+use ooniauth_core::registration::open_registration;
+use ooniauth_core::ServerState;
+use ooniauth_wasm::{
+    submit_measurement_hash, today, Error, RegistrationRequest, SubmitPolicy, SubmitRequest,
+    UserState,
+};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn public_parameters(server: &ServerState) -> Vec<u8> {
+    bincode::serialize(server.public_parameters_ref()).unwrap()
+}
+
+fn register(server: &ServerState) -> UserState {
+    let mut user = UserState::new(&public_parameters(server)).unwrap();
+    let mut pending = user.registration_request().unwrap();
+    let (req, framing) = server
+        .decode_request::<open_registration::Request>(&pending.request())
+        .unwrap();
+    let reply = server
+        .open_registration(&mut rand::thread_rng(), req)
+        .unwrap();
+    user.handle_registration_response(&mut pending, &server.encode_reply(&reply, framing), None)
+        .unwrap();
+    user
+}
+
+fn code<T>(result: Result<T, Error>) -> &'static str {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err.code(),
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_register_and_submit() {
+    let server = ServerState::new(&mut rand::thread_rng());
+    let mut user = register(&server);
+    let info = user.credential_info().unwrap().unwrap();
+    assert_eq!(info.age, today());
+    assert_eq!(info.measurement_count, 0);
+
+    let ranges = SubmitPolicy::new(None, None, Some(10))
+        .unwrap()
+        .ranges(None)
+        .unwrap();
+    let hash = submit_measurement_hash(b"measurement");
+    let pending = user
+        .submit_request("US".into(), "AS1234".into(), &hash, &ranges)
+        .unwrap();
+    // A pending request survives a restart
+    let mut pending = SubmitRequest::from_bytes(&pending.to_bytes().unwrap()).unwrap();
+    let (req, framing) = server.decode_request(&pending.request()).unwrap();
+    let reply = server
+        .handle_submit(
+            &mut rand::thread_rng(),
+            req,
+            &pending.nym().try_into().unwrap(),
+            "US",
+            "AS1234",
+            &hash.clone().try_into().unwrap(),
+            ranges.age_start..ranges.age_end,
+            ranges.min_measurement_count..ranges.max_measurement_count,
+        )
+        .unwrap();
+    let response = server.encode_reply(&reply, framing);
+    user.handle_submit_response(&mut pending, &response)
+        .unwrap();
+    assert_eq!(
+        user.credential_info().unwrap().unwrap().measurement_count,
+        1
+    );
+    assert_eq!(
+        code(user.handle_submit_response(&mut pending, &response)),
+        "invalid_argument"
+    );
+
+    let restored = UserState::from_bytes(&user.to_bytes()).unwrap();
+    assert_eq!(restored.credential().unwrap(), user.credential().unwrap());
+}
+
+#[wasm_bindgen_test]
+fn test_update_after_key_rotation() {
+    let old_server = ServerState::new(&mut rand::thread_rng());
+    let mut user = register(&old_server);
+    let new_server = ServerState::new(&mut rand::thread_rng());
+    user.set_public_parameters(&public_parameters(&new_server))
+        .unwrap();
+
+    let mut pending = user.update_request().unwrap();
+    let (req, framing) = new_server.decode_request(&pending.request()).unwrap();
+    let reply = new_server
+        .handle_update(
+            &mut rand::thread_rng(),
+            req,
+            old_server.secret_key_ref(),
+            old_server.public_parameters_ref(),
+        )
+        .unwrap();
+    user.handle_update_response(&mut pending, &new_server.encode_reply(&reply, framing))
+        .unwrap();
+    let info = user.credential_info().unwrap().unwrap();
+    assert_eq!(
+        info.key_id,
+        ooniauth_core::wire::key_id(new_server.public_parameters_ref())
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_errors() {
+    let server = ServerState::new(&mut rand::thread_rng());
+    let user = UserState::new(&public_parameters(&server)).unwrap();
+    assert_eq!(user.credential_info().unwrap(), None);
+    assert_eq!(code(user.credential()), "missing_credential");
+    assert_eq!(code(user.update_request()), "missing_credential");
+    assert_eq!(code(UserState::new(&[1, 2, 3])), "malformed_message");
+    assert_eq!(
+        code(RegistrationRequest::from_bytes(&[1, 2, 3])),
+        "malformed_message"
+    );
+
+    let user = register(&server);
+    let ranges = SubmitPolicy::new(None, None, None)
+        .unwrap()
+        .ranges(None)
+        .unwrap();
+    assert_eq!(
+        code(user.submit_request("US".into(), "AS1234".into(), &[0; 31], &ranges)),
+        "invalid_argument"
+    );
+    assert_eq!(
+        code(user.submit_request("USA".into(), "AS1234".into(), &[0; 32], &ranges)),
+        "bad_domain"
+    );
+    assert_eq!(
+        code(SubmitPolicy::new(Some(30), Some(10), Some(10))),
+        "invalid_argument"
+    );
+    assert_eq!(
+        code(
+            SubmitPolicy::new(Some(30), None, None)
+                .unwrap()
+                .ranges(Some(29))
+        ),
+        "invalid_argument"
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_bad_response_keeps_pending_request() {
+    let server = ServerState::new(&mut rand::thread_rng());
+    let mut user = UserState::new(&public_parameters(&server)).unwrap();
+    let mut pending = user.registration_request().unwrap();
+    let other = user.registration_request().unwrap();
+    let reply_to = |pending: &RegistrationRequest| {
+        let (req, framing) = server
+            .decode_request::<open_registration::Request>(&pending.request())
+            .unwrap();
+        let reply = server
+            .open_registration(&mut rand::thread_rng(), req)
+            .unwrap();
+        server.encode_reply(&reply, framing)
+    };
+    let response = reply_to(&pending);
+
+    assert_eq!(
+        code(user.handle_registration_response(&mut pending, &[0; 8], None)),
+        "malformed_message"
+    );
+    // A valid response, but to another request
+    assert!(user
+        .handle_registration_response(&mut pending, &reply_to(&other), None)
+        .is_err());
+    assert!(pending.to_bytes().is_ok());
+
+    user.handle_registration_response(&mut pending, &response, None)
+        .unwrap();
+    assert!(user.credential_info().unwrap().is_some());
+    // Reported as handled before the response is decoded
+    assert_eq!(
+        code(user.handle_registration_response(&mut pending, &[0; 8], None)),
+        "invalid_argument"
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_errors_are_js_errors() {
+    let err = match UserState::new(&[1, 2, 3]) {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => JsValue::from(err),
+    };
+    let err = js_sys::Error::from(err);
+    assert_eq!(err.name(), "OoniauthError");
+    assert_eq!(
+        js_sys::Reflect::get(&err, &"code".into()).unwrap(),
+        "malformed_message"
+    );
+}